@@ -0,0 +1,192 @@
+//! Derive macro for `object_pool::object_pool::object_pool::Poolable`, enabled by the `derive` feature of `object_pool`.
+//!
+//! The input is parsed directly from the token stream, so only non-generic structs with named fields are supported.
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+
+/// Derive `Poolable` for a struct whose fields are all `Default`.
+///
+/// `new()` default constructs every field. `reset()` clears fields that implement `Clear`, which keeps their
+/// capacity, and assigns `Default::default()` to every other field. Field attributes change this per field:
+/// - `#[poolable(skip)]` leaves the field untouched on reset.
+/// - `#[poolable(default)]` assigns `Default::default()` even if the field implements `Clear`.
+/// - `#[poolable(reset = path)]` calls `path(&mut field)`.
+#[proc_macro_derive(Poolable, attributes(poolable))]
+pub fn derive_poolable(input: TokenStream) -> TokenStream {
+    let source = match parse_struct(input) {
+        Ok(parsed) => expand(&parsed),
+        Err(message) => format!("compile_error!({:?});", message)
+    };
+    source.parse().expect("generated code should parse")
+}
+
+/// How a field is reset.
+enum Reset {
+    /// `Clear::clear` if the field implements it, otherwise `Default::default()`.
+    Auto,
+    Skip,
+    Default,
+    With(String)
+}
+
+struct Field {
+    name: String,
+    reset: Reset
+}
+
+struct Struct {
+    name: String,
+    fields: Vec<Field>
+}
+
+fn is_punct(token: Option<&TokenTree>, ch: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+fn is_ident(token: Option<&TokenTree>, name: &str) -> bool {
+    matches!(token, Some(TokenTree::Ident(ident)) if ident.to_string() == name)
+}
+
+/// Skip a visibility such as `pub` or `pub(crate)` at `tokens[*i]`.
+fn skip_visibility(tokens: &[TokenTree], i: &mut usize) {
+    if is_ident(tokens.get(*i), "pub") {
+        *i += 1;
+        if let Some(TokenTree::Group(group)) = tokens.get(*i) {
+            if group.delimiter() == Delimiter::Parenthesis {
+                *i += 1;
+            }
+        }
+    }
+}
+
+/// Collect the attributes at `tokens[*i]`, which are a `#` followed by a bracketed group.
+fn take_attributes(tokens: &[TokenTree], i: &mut usize) -> Vec<Group> {
+    let mut attributes = Vec::new();
+    while is_punct(tokens.get(*i), '#') {
+        match tokens.get(*i + 1) {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Bracket => attributes.push(group.clone()),
+            _ => break
+        }
+        *i += 2;
+    }
+    attributes
+}
+
+fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut i = 0;
+    take_attributes(&tokens, &mut i);
+    skip_visibility(&tokens, &mut i);
+    if !is_ident(tokens.get(i), "struct") {
+        return Err("Poolable can only be derived for structs".to_string());
+    }
+    let name = match tokens.get(i + 1) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected a struct name".to_string())
+    };
+    let fields = match tokens.get(i + 2) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => parse_fields(group.stream())?,
+        Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => Vec::new(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("Poolable cannot be derived for generic structs".to_string());
+        }
+        _ => return Err("Poolable can only be derived for structs with named fields".to_string())
+    };
+    Ok(Struct {
+        name,
+        fields
+    })
+}
+
+fn parse_fields(stream: TokenStream) -> Result<Vec<Field>, String> {
+    let tokens: Vec<TokenTree> = stream.into_iter().collect();
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let mut reset = Reset::Auto;
+        for attribute in take_attributes(&tokens, &mut i) {
+            if let Some(options) = poolable_options(&attribute) {
+                reset = parse_options(options)?;
+            }
+        }
+        skip_visibility(&tokens, &mut i);
+        let name = match tokens.get(i) {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            _ => return Err("expected a field name".to_string())
+        };
+        if !is_punct(tokens.get(i + 1), ':') {
+            return Err(format!("expected `:` after field `{}`", name));
+        }
+        i += 2;
+        // Skip the type up to the next comma that is not inside angle brackets. The `>` of `->` is not a bracket.
+        let mut depth = 0usize;
+        while let Some(token) = tokens.get(i) {
+            if let TokenTree::Punct(punct) = token {
+                let after_dash = i > 0 && matches!(&tokens[i - 1], TokenTree::Punct(prev) if prev.as_char() == '-' && prev.spacing() == Spacing::Joint);
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    '>' if !after_dash => depth = depth.saturating_sub(1),
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+            i += 1;
+        }
+        i += 1;
+        fields.push(Field {
+            name,
+            reset
+        });
+    }
+    Ok(fields)
+}
+
+/// Get the tokens inside `poolable(...)` if the attribute is a poolable attribute.
+fn poolable_options(attribute: &Group) -> Option<TokenStream> {
+    let tokens: Vec<TokenTree> = attribute.stream().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Ident(ident), TokenTree::Group(options)] if ident.to_string() == "poolable" => Some(options.stream()),
+        _ => None
+    }
+}
+
+fn parse_options(options: TokenStream) -> Result<Reset, String> {
+    let tokens: Vec<TokenTree> = options.into_iter().collect();
+    match tokens.first() {
+        Some(TokenTree::Ident(ident)) if tokens.len() == 1 && ident.to_string() == "skip" => Ok(Reset::Skip),
+        Some(TokenTree::Ident(ident)) if tokens.len() == 1 && ident.to_string() == "default" => Ok(Reset::Default),
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "reset" && is_punct(tokens.get(1), '=') && tokens.len() > 2 => {
+            Ok(Reset::With(tokens[2..].iter().cloned().collect::<TokenStream>().to_string()))
+        }
+        _ => Err("expected `#[poolable(skip)]`, `#[poolable(default)]` or `#[poolable(reset = path)]`".to_string())
+    }
+}
+
+fn expand(parsed: &Struct) -> String {
+    let mut inits = String::new();
+    let mut resets = String::new();
+    for field in &parsed.fields {
+        let name = &field.name;
+        inits += &format!("{}: ::core::default::Default::default(),", name);
+        resets += &match &field.reset {
+            Reset::Auto => format!("(&mut ::object_pool::object_pool::object_pool::__derive::Field(&mut self.{})).reset_field();", name),
+            Reset::Skip => String::new(),
+            Reset::Default => format!("self.{} = ::core::default::Default::default();", name),
+            Reset::With(path) => format!("{}(&mut self.{});", path, name)
+        };
+    }
+    format!(
+        "impl ::object_pool::object_pool::object_pool::Poolable for {name} {{
+            fn new() -> Self {{
+                {name} {{ {inits} }}
+            }}
+
+            #[allow(unused_imports)]
+            fn reset(&mut self) {{
+                use ::object_pool::object_pool::object_pool::__derive::{{ResetViaClear, ResetViaDefault}};
+                {resets}
+            }}
+        }}",
+        name = parsed.name
+    )
+}
@@ -0,0 +1,260 @@
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::object_pool::object_pool::Poolable;
+
+/// Bump arena for memory that lives as long as a job, such as a request or a frame.
+/// Allocating only moves a cursor, and `reset` reclaims everything allocated from the arena at once.
+/// ArenaPools borrow the arena, so it can only be reset once every pool using it is gone.
+pub struct Arena {
+    /// Chunks of memory, the current one last.
+    chunks: RefCell<Vec<(NonNull<u8>, Layout)>>,
+    /// Bytes of the current chunk already handed out.
+    used: Cell<usize>,
+    chunk_size: usize
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Arena {
+    /// Create an Arena that allocates its memory in chunks of 64 KiB.
+    pub fn new() -> Arena {
+        Arena::with_chunk_size(64 * 1024)
+    }
+
+    /// Create an Arena that allocates its memory in chunks of `chunk_size` bytes, or larger for larger objects.
+    pub fn with_chunk_size(chunk_size: usize) -> Arena {
+        Arena {
+            chunks: RefCell::new(Vec::new()),
+            used: Cell::new(0),
+            chunk_size: chunk_size.max(1)
+        }
+    }
+
+    /// Allocate memory for `layout` from the current chunk, starting a new chunk if it does not fit.
+    fn allocate(&self, layout: Layout) -> NonNull<u8> {
+        let mut chunks = self.chunks.borrow_mut();
+        if let Some(&(chunk, chunk_layout)) = chunks.last() {
+            let start = self.used.get() + (chunk.as_ptr() as usize + self.used.get()).wrapping_neg() % layout.align();
+            if start + layout.size() <= chunk_layout.size() {
+                self.used.set(start + layout.size());
+                // SAFETY: `start` plus the size is within the chunk.
+                return unsafe { chunk.add(start) };
+            }
+        }
+        let chunk_layout = Layout::from_size_align(self.chunk_size.max(layout.size()), layout.align().max(16))
+            .expect("arena chunk size overflows");
+        // SAFETY: the chunk layout is never zero-sized.
+        let chunk = NonNull::new(unsafe { alloc::alloc(chunk_layout) }).unwrap_or_else(|| alloc::handle_alloc_error(chunk_layout));
+        chunks.push((chunk, chunk_layout));
+        self.used.set(layout.size());
+        chunk
+    }
+
+    /// Move `value` into the arena. Its memory is reclaimed by `reset`, but it is never dropped by the arena.
+    fn alloc<T>(&self, value: T) -> NonNull<T> {
+        let ptr = self.allocate(Layout::new::<T>()).cast::<T>();
+        // SAFETY: the memory is fresh and laid out for a T.
+        unsafe {
+            ptr.as_ptr().write(value);
+        }
+        ptr
+    }
+
+    /// Get the number of bytes the arena holds in its chunks.
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks.borrow().iter().map(|(_, layout)| layout.size()).sum()
+    }
+
+    /// Reclaim all memory allocated from the arena at once, keeping the current chunk for the next job.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        let current = chunks.pop();
+        for (chunk, layout) in chunks.drain(..) {
+            // SAFETY: nothing borrows the arena, so nothing points into its chunks.
+            unsafe {
+                alloc::dealloc(chunk.as_ptr(), layout);
+            }
+        }
+        chunks.extend(current);
+        self.used.set(0);
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for &(chunk, layout) in self.chunks.get_mut().iter() {
+            unsafe {
+                alloc::dealloc(chunk.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+/// Variant of AutoReturnObjectPool whose objects are allocated from a user-supplied Arena instead of the heap.
+/// Dropping the pool drops its objects, but their memory stays with the arena until `Arena::reset`, so a
+/// whole job's pools are reclaimed in one step.
+pub struct ArenaPool<'a, T: Poolable> {
+    arena: &'a Arena,
+    available: RefCell<Vec<NonNull<T>>>,
+    len: Cell<usize>
+}
+
+impl<'a, T: Poolable> ArenaPool<'a, T> {
+    /// Create an ArenaPool that allocates its objects from `arena`.
+    pub fn new(arena: &'a Arena) -> ArenaPool<'a, T> {
+        ArenaPool {
+            arena,
+            available: RefCell::new(Vec::new()),
+            len: Cell::new(0)
+        }
+    }
+
+    /// Allocate a new object from the arena.
+    fn allocate(&self) -> NonNull<T> {
+        self.len.set(self.len.get() + 1);
+        self.arena.alloc(T::new())
+    }
+
+    /// Get an ArenaPoolBox from the pool.
+    pub fn get(&self) -> ArenaPoolBox<'_, T> {
+        let item = self.available.borrow_mut().pop().unwrap_or_else(|| self.allocate());
+        ArenaPoolBox {
+            item,
+            pool: self
+        }
+    }
+
+    /// Reserve a number of items in the pool.
+    pub fn reserve(&self, count: usize) {
+        let items: Vec<_> = (0..count).map(|_| self.allocate()).collect();
+        self.available.borrow_mut().extend(items);
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.available.borrow().len()
+    }
+}
+
+impl<T: Poolable> Drop for ArenaPool<'_, T> {
+    fn drop(&mut self) {
+        // Guards borrow the pool, so every object is available by now.
+        for item in self.available.get_mut().drain(..) {
+            unsafe {
+                item.as_ptr().drop_in_place();
+            }
+        }
+    }
+}
+
+/// Guard handed out by an ArenaPool, which resets the item and makes it available again when dropped.
+pub struct ArenaPoolBox<'p, T: Poolable> {
+    item: NonNull<T>,
+    pool: &'p ArenaPool<'p, T>
+}
+
+impl<T: Poolable> Deref for ArenaPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            self.item.as_ref()
+        }
+    }
+}
+
+impl<T: Poolable> DerefMut for ArenaPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            self.item.as_mut()
+        }
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for ArenaPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable> Drop for ArenaPoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.item.as_mut().reset();
+        }
+        self.pool.available.borrow_mut().push(self.item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    struct TestObject {
+        pub value: i32,
+        pub drops: Option<Rc<Cell<usize>>>
+    }
+
+    impl Poolable for TestObject {
+        fn new() -> TestObject {
+            TestObject {
+                value: 0,
+                drops: None
+            }
+        }
+
+        fn reset(&mut self) {
+            self.value = 0;
+        }
+    }
+
+    impl Drop for TestObject {
+        fn drop(&mut self) {
+            if let Some(drops) = &self.drops {
+                drops.set(drops.get() + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_arena_pool() {
+        let mut arena = Arena::with_chunk_size(256);
+        let drops = Rc::new(Cell::new(0));
+        for _ in 0..3 {
+            let pool = ArenaPool::<TestObject>::new(&arena);
+            pool.reserve(2);
+            let mut held: Vec<_> = (0..20).map(|_| pool.get()).collect();
+            held[0].value = 5;
+            held[0].drops = Some(drops.clone());
+            drop(held);
+            assert_eq!((pool.len(), pool.available()), (20, 20));
+            assert_eq!(pool.get().value, 0);
+            drop(pool);
+            // Resetting keeps one chunk, so later jobs reuse its memory.
+            assert!(arena.allocated_bytes() > 256);
+            arena.reset();
+            assert_eq!(arena.allocated_bytes(), 256);
+        }
+        // Dropping a pool drops its objects, even though the arena owns their memory.
+        assert_eq!(drops.get(), 3);
+    }
+}
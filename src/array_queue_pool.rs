@@ -0,0 +1,353 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+
+use crate::object_pool::object_pool::Poolable;
+use crate::sync::{self, fence, AtomicU32, AtomicUsize, CachePadded};
+
+/// Slot of an IndexQueue. The stamp tells whether the slot is ready to be written or read in the current lap.
+struct QueueSlot {
+    stamp: AtomicUsize,
+    index: AtomicU32
+}
+
+/// Bounded multi-producer multi-consumer queue of slot indices.
+/// This is the array queue of Dmitry Vyukov, the same algorithm as `crossbeam::queue::ArrayQueue`:
+/// `head` and `tail` each pack a position with a lap counter, and a slot's stamp is advanced by one lap
+/// every time it is consumed, so producers and consumers never wait on each other's locks.
+/// `head` and `tail` sit on their own cache lines so consumers and producers do not invalidate each other.
+struct IndexQueue {
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[QueueSlot]>,
+    one_lap: usize
+}
+
+impl IndexQueue {
+    /// Create a queue holding the indices `0..capacity`.
+    fn full(capacity: usize) -> IndexQueue {
+        let one_lap = (capacity + 1).next_power_of_two();
+        let buffer: Box<[QueueSlot]> = (0..capacity).map(|position| QueueSlot {
+            // Slot `position` has been written in lap 0 and is ready to be read.
+            stamp: AtomicUsize::new(position + 1),
+            index: AtomicU32::new(position as u32)
+        }).collect();
+        // The queue is full: the tail is one lap ahead of the head.
+        IndexQueue {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(one_lap)),
+            buffer,
+            one_lap
+        }
+    }
+
+    /// Get the position following `position`, wrapping into the next lap after the last slot.
+    fn next(&self, position: usize) -> usize {
+        let index = position & (self.one_lap - 1);
+        let lap = position & !(self.one_lap - 1);
+        if index + 1 < self.buffer.len() {
+            position + 1
+        } else {
+            lap.wrapping_add(self.one_lap)
+        }
+    }
+
+    /// Push an index. Returns false if the queue is full.
+    fn push(&self, value: u32) -> bool {
+        if self.buffer.is_empty() {
+            return false;
+        }
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[tail & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if tail == stamp {
+                match self.tail.compare_exchange_weak(tail, self.next(tail), Ordering::SeqCst, Ordering::Relaxed) {
+                    Ok(_) => {
+                        slot.index.store(value, Ordering::Relaxed);
+                        slot.stamp.store(tail + 1, Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => tail = current
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                fence(Ordering::SeqCst);
+                let head = self.head.load(Ordering::Relaxed);
+                if head.wrapping_add(self.one_lap) == tail {
+                    return false;
+                }
+                tail = self.tail.load(Ordering::Relaxed);
+            } else {
+                // A consumer has claimed the slot but not yet advanced its stamp.
+                sync::spin_loop();
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pop an index, or None if the queue is empty.
+    fn pop(&self) -> Option<u32> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[head & (self.one_lap - 1)];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+            if head + 1 == stamp {
+                match self.head.compare_exchange_weak(head, self.next(head), Ordering::SeqCst, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let value = slot.index.load(Ordering::Relaxed);
+                        slot.stamp.store(head.wrapping_add(self.one_lap), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => head = current
+                }
+            } else if stamp == head {
+                fence(Ordering::SeqCst);
+                let tail = self.tail.load(Ordering::Relaxed);
+                if tail == head {
+                    return None;
+                }
+                head = self.head.load(Ordering::Relaxed);
+            } else {
+                // A producer has claimed the slot but not yet advanced its stamp.
+                sync::spin_loop();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Get the number of indices in the queue.
+    fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            // Retry until both positions were read without the tail moving in between.
+            if self.tail.load(Ordering::SeqCst) == tail {
+                let head_index = head & (self.one_lap - 1);
+                let tail_index = tail & (self.one_lap - 1);
+                return if head_index < tail_index {
+                    tail_index - head_index
+                } else if head_index > tail_index {
+                    self.buffer.len() - head_index + tail_index
+                } else if tail == head {
+                    0
+                } else {
+                    self.buffer.len()
+                };
+            }
+        }
+    }
+}
+
+/// Fixed capacity pool whose available list is a bounded array queue of slot indices.
+/// Unlike the Treiber stack of LockFreePool, acquisitions and releases work on different ends of the queue,
+/// so they do not contend on a single head, and items are reused in FIFO order.
+pub struct ArrayQueuePool<T: Poolable> {
+    items: Box<[UnsafeCell<T>]>,
+    available: IndexQueue
+}
+
+// SAFETY: an item is only accessed by the single guard that popped its index.
+unsafe impl<T: Poolable + Send> Send for ArrayQueuePool<T> {}
+unsafe impl<T: Poolable + Send> Sync for ArrayQueuePool<T> {}
+
+impl<T: Poolable> ArrayQueuePool<T> {
+    /// Create an ArrayQueuePool holding `capacity` items, which are all created up front.
+    pub fn with_capacity(capacity: usize) -> ArrayQueuePool<T> {
+        assert!(capacity < u32::MAX as usize, "ArrayQueuePool capacity must be below u32::MAX");
+        ArrayQueuePool {
+            items: (0..capacity).map(|_| UnsafeCell::new(T::new())).collect(),
+            available: IndexQueue::full(capacity)
+        }
+    }
+
+    /// Get an item from the pool, or None if every item is handed out.
+    pub fn try_get(&self) -> Option<ArrayQueuePoolBox<'_, T>> {
+        let index = self.available.pop()?;
+        Some(ArrayQueuePoolBox {
+            index,
+            pool: self
+        })
+    }
+
+    /// Reset the item at `index` and push the index back onto the queue.
+    /// If `reset()` panics, the item is replaced by a new one before the index is pushed, and the panic continues
+    /// once the old item is dropped.
+    fn release(&self, index: u32) {
+        let value = self.items[index as usize].get();
+        // SAFETY: the index was popped by the releasing guard, which had exclusive access to its item.
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*value).reset() }));
+        let broken = reset.is_err().then(|| unsafe { mem::replace(&mut *value, T::new()) });
+        // The queue has room for every index, so the push cannot fail.
+        let pushed = self.available.push(index);
+        debug_assert!(pushed, "ArrayQueuePool available queue overflowed");
+        if let Err(payload) = reset {
+            drop(broken);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the number of items in the pool.
+    pub fn capacity(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.available.len()
+    }
+}
+
+/// Guard handed out by an ArrayQueuePool. It releases its item when dropped.
+pub struct ArrayQueuePoolBox<'a, T: Poolable> {
+    index: u32,
+    pool: &'a ArrayQueuePool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item.
+unsafe impl<T: Poolable + Send> Send for ArrayQueuePoolBox<'_, T> {}
+unsafe impl<T: Poolable + Sync> Sync for ArrayQueuePoolBox<'_, T> {}
+
+impl<T: Poolable> Deref for ArrayQueuePoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.pool.items[self.index as usize].get()
+        }
+    }
+}
+
+impl<T: Poolable> DerefMut for ArrayQueuePoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.pool.items[self.index as usize].get()
+        }
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for ArrayQueuePoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable> Drop for ArrayQueuePoolBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    struct PanickyObject {
+        pub fail: bool
+    }
+
+    impl Poolable for PanickyObject {
+        fn new() -> PanickyObject {
+            PanickyObject {
+                fail: false
+            }
+        }
+
+        fn reset(&mut self) {
+            assert!(!self.fail, "reset failed");
+        }
+    }
+
+    #[test]
+    fn test_array_queue_pool() {
+        let pool = ArrayQueuePool::<TestObject>::with_capacity(3);
+        assert_eq!(pool.available(), 3);
+        let mut a = pool.try_get().unwrap();
+        let b = pool.try_get().unwrap();
+        let c = pool.try_get().unwrap();
+        assert!(pool.try_get().is_none());
+        assert_eq!(pool.available(), 0);
+        a.value = 1;
+        drop(a);
+        drop(c);
+        assert_eq!(pool.available(), 2);
+        // Items are reused in the order they were released.
+        let d = pool.try_get().unwrap();
+        assert_eq!(d.index, 0);
+        assert_eq!(d.value, 0);
+        drop(b);
+        drop(d);
+        assert_eq!(pool.available(), 3);
+        assert!(ArrayQueuePool::<TestObject>::with_capacity(0).try_get().is_none());
+    }
+
+    #[test]
+    fn test_panicking_reset_keeps_slot() {
+        let pool = ArrayQueuePool::<PanickyObject>::with_capacity(1);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.try_get().unwrap().fail = true;
+        }));
+        assert!(result.is_err());
+        // The index is back in the queue with a new item.
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.try_get().unwrap().fail);
+    }
+
+    #[test]
+    fn test_array_queue_pool_contention() {
+        let pool = ArrayQueuePool::<TestObject>::with_capacity(5);
+        thread::scope(|scope| {
+            for worker in 1..=8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        if let Some(mut obj) = pool.try_get() {
+                            assert_eq!(obj.value, 0);
+                            obj.value = worker;
+                            thread::yield_now();
+                            assert_eq!(obj.value, worker);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available(), 5);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+
+    #[test]
+    fn loom_index_queue_keeps_every_index_once() {
+        loom::model(|| {
+            let queue = Arc::new(IndexQueue::full(2));
+            let workers: Vec<_> = (0..2).map(|_| {
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    if let Some(index) = queue.pop() {
+                        assert!(queue.push(index));
+                    }
+                })
+            }).collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            let mut indices = [queue.pop().unwrap(), queue.pop().unwrap()];
+            indices.sort();
+            assert_eq!(indices, [0, 1]);
+            assert!(queue.pop().is_none());
+        });
+    }
+}
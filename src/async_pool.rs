@@ -0,0 +1,916 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::{self, Future};
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::task::{ready, Context, Poll, Waker};
+use std::thread;
+#[cfg(feature = "std")]
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::object_pool::object_pool::{ExhaustedBehavior, PoolError, Poolable};
+use crate::sync::{Mutex, MutexGuard};
+#[cfg(feature = "std")]
+use crate::executor;
+#[cfg(feature = "std")]
+use crate::timer::{Sleep, Timeout};
+
+/// Trait for objects whose construction or reset has to await, such as connections or sessions.
+/// Every `Poolable + Send` type is also `AsyncPoolable`, with futures that complete right away.
+pub trait AsyncPoolable: Send + Sized {
+    /// Create a new object. The pool awaits this when it has room and no idle object.
+    fn create() -> impl Future<Output = Self> + Send;
+    /// Reset the object before it is handed out again.
+    fn reset(&mut self) -> impl Future<Output = ()> + Send;
+    /// Check whether an idle object can still be used, such as a connection that may have died while idle.
+    /// The pool runs this before handing out an idle object, and replaces the object if it returns false.
+    fn is_healthy(&mut self) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
+}
+
+impl<T: Poolable + Send> AsyncPoolable for T {
+    async fn create() -> T {
+        T::new()
+    }
+
+    async fn reset(&mut self) {
+        Poolable::reset(self);
+    }
+}
+
+/// What a task is entitled to once it is its turn: an idle object, or room to create a new one.
+enum Grant<T> {
+    /// An idle object and whether it still has to be reset.
+    Idle(Box<T>, bool),
+    Create
+}
+
+/// Where the object of an AsyncPoolBox is in its reset cycle.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// The object may have been used and has to be reset before it is handed out again.
+    InUse,
+    /// The object is being reset, so a panic means it has to be discarded.
+    Resetting,
+    /// The object has been reset by `release()`.
+    Clean
+}
+
+/// Object waiting in an AsyncPool to be handed out.
+struct Idle<T> {
+    item: Box<T>,
+    /// Whether the object still has to be reset.
+    dirty: bool,
+    /// When the object became idle.
+    since: Instant
+}
+
+/// State of an AsyncPool, protected by its mutex.
+struct AsyncState<T> {
+    /// Idle objects, from the longest idle to the most recently returned.
+    idle: Vec<Idle<T>>,
+    /// Number of objects owned by the pool, idle, handed out or being created.
+    size: usize,
+    /// Tasks waiting for an object in arrival order, each with its ticket and the waker of its last poll.
+    waiters: VecDeque<(u64, Waker)>,
+    /// Grants made to waiting tasks that have not been polled yet, keyed by ticket.
+    grants: HashMap<u64, Grant<T>>,
+    next_ticket: u64
+}
+
+/// Pool for async code. Acquiring from an exhausted bounded pool suspends the task instead of blocking the thread,
+/// and a released object is handed directly to the longest waiting task, which is then woken.
+/// Objects are created with `AsyncPoolable::create()` on a miss. A guard that is simply dropped cannot await,
+/// so its object is reset by the next task that acquires it; `AsyncPoolBox::release()` resets it right away instead.
+/// The pool only relies on `std::task` wakers and, for timeouts and maintenance loops, its own timer thread,
+/// so it works with any executor; every future it returns is `Send`.
+pub struct AsyncPool<T: AsyncPoolable> {
+    state: Mutex<AsyncState<T>>,
+    max_size: usize,
+    exhausted: ExhaustedBehavior,
+    idle_timeout: Option<Duration>,
+    min_idle: usize,
+    max_idle: usize
+}
+
+impl<T: AsyncPoolable> Default for AsyncPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: AsyncPoolable> AsyncPool<T> {
+    /// Create a new unbounded AsyncPool.
+    pub fn new() -> AsyncPool<T> {
+        AsyncPool::bounded(usize::MAX)
+    }
+
+    /// Create a new AsyncPool that holds at most `max_size` objects.
+    pub fn bounded(max_size: usize) -> AsyncPool<T> {
+        AsyncPool {
+            state: Mutex::new(AsyncState {
+                idle: Vec::new(),
+                size: 0,
+                waiters: VecDeque::new(),
+                grants: HashMap::new(),
+                next_ticket: 0
+            }),
+            max_size,
+            exhausted: ExhaustedBehavior::Wait,
+            idle_timeout: None,
+            min_idle: 0,
+            max_idle: usize::MAX
+        }
+    }
+
+    /// Choose what `acquire()` does when the pool is at its maximum size and every object is in use.
+    /// The default is `Wait`; `Block` waits as well, without blocking the thread. `Grow` makes the maximum size a
+    /// soft limit, and `Fail` makes `checked_acquire` return `PoolError::Exhausted`.
+    pub fn with_exhausted_behavior(mut self, behavior: ExhaustedBehavior) -> AsyncPool<T> {
+        self.exhausted = behavior;
+        self
+    }
+
+    /// Let `maintain()` drop objects that have been idle for longer than `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> AsyncPool<T> {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Let `maintain()` keep at least `min_idle` idle objects, creating them ahead of demand.
+    /// Idle eviction never goes below this number either.
+    pub fn with_min_idle(mut self, min_idle: usize) -> AsyncPool<T> {
+        self.min_idle = min_idle;
+        self
+    }
+
+    /// Keep at most `max_idle` objects idle: an object returned while the idle list is full is dropped.
+    pub fn with_max_idle(mut self, max_idle: usize) -> AsyncPool<T> {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Lock the pool state, ignoring poisoning like ConcurrentObjectPool does.
+    fn lock(&self) -> MutexGuard<'_, AsyncState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Grant an idle object, or room to create one, without waiting.
+    fn grant_now(&self, state: &mut AsyncState<T>) -> Option<Grant<T>> {
+        if let Some(idle) = state.idle.pop() {
+            return Some(Grant::Idle(idle.item, idle.dirty));
+        }
+        if state.size < self.max_size || self.exhausted == ExhaustedBehavior::Grow {
+            state.size += 1;
+            return Some(Grant::Create);
+        }
+        None
+    }
+
+    /// Hand a grant to the longest waiting task, or give it back to the pool if no task is waiting.
+    fn pass_on(&self, grant: Grant<T>) {
+        let mut state = self.lock();
+        match state.waiters.pop_front() {
+            Some((ticket, waker)) => {
+                state.grants.insert(ticket, grant);
+                drop(state);
+                waker.wake();
+            }
+            None => match grant {
+                Grant::Idle(item, _) if state.idle.len() >= self.max_idle => {
+                    state.size -= 1;
+                    drop(state);
+                    drop(item);
+                }
+                Grant::Idle(item, dirty) => state.idle.push(Idle {
+                    item,
+                    dirty,
+                    since: Instant::now()
+                }),
+                Grant::Create => state.size -= 1
+            }
+        }
+    }
+
+    /// Turn a grant into a guard, awaiting the reset and health check of an idle object,
+    /// or the creation of a new one if there was none or it was unhealthy.
+    async fn fulfil(&self, grant: Grant<T>) -> AsyncPoolBox<'_, T> {
+        // Gives the room back if creation is cancelled or panics.
+        let room = match grant {
+            Grant::Idle(item, dirty) => {
+                let mut obj = AsyncPoolBox::new(item, self);
+                if dirty {
+                    obj.phase = Phase::Resetting;
+                    obj.item.reset().await;
+                    obj.phase = Phase::InUse;
+                }
+                if obj.item.is_healthy().await {
+                    return obj;
+                }
+                obj.discard()
+            }
+            Grant::Create => Room { pool: self }
+        };
+        let item = Box::new(T::create().await);
+        mem::forget(room);
+        AsyncPoolBox::new(item, self)
+    }
+
+    /// Wait for the task's turn in the queue of waiters.
+    fn wait_turn(&self) -> WaitTurn<'_, T> {
+        WaitTurn {
+            pool: self,
+            ticket: None
+        }
+    }
+
+    /// Acquire an object, suspending the task until one is available if the pool is exhausted.
+    /// This is cancellation safe: dropping the future at any point returns a granted object to the pool.
+    /// Panics if the pool is exhausted and its ExhaustedBehavior is `Fail`.
+    pub async fn acquire(&self) -> AsyncPoolBox<'_, T> {
+        match self.checked_acquire().await {
+            Ok(obj) => obj,
+            Err(_) => panic!("pool is exhausted: all {} objects are in use", self.max_size)
+        }
+    }
+
+    /// Acquire an object, applying the pool's ExhaustedBehavior if every object is in use.
+    /// Returns `PoolError::Exhausted` instead of waiting if the behavior is `Fail`.
+    pub async fn checked_acquire(&self) -> Result<AsyncPoolBox<'_, T>, PoolError> {
+        if self.exhausted == ExhaustedBehavior::Fail {
+            return self.try_acquire().await.ok_or(PoolError::Exhausted);
+        }
+        let grant = self.wait_turn().await;
+        Ok(self.fulfil(grant).await)
+    }
+
+    /// Acquire an object, giving up and returning None if none became available within `timeout`.
+    /// The timeout covers the wait for an object, not its creation or reset. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub async fn acquire_timeout(&self, timeout: Duration) -> Option<AsyncPoolBox<'_, T>> {
+        let grant = Timeout::new(self.wait_turn(), Instant::now() + timeout).await?;
+        Some(self.fulfil(grant).await)
+    }
+
+    /// Acquire an object if one is idle or there is room to create one, without waiting for a release.
+    /// Returns None if the pool is exhausted or tasks are waiting.
+    pub async fn try_acquire(&self) -> Option<AsyncPoolBox<'_, T>> {
+        let grant = {
+            let mut state = self.lock();
+            if !state.waiters.is_empty() {
+                return None;
+            }
+            self.grant_now(&mut state)?
+        };
+        Some(self.fulfil(grant).await)
+    }
+
+    /// Run one maintenance pass: drop objects idle for longer than the idle timeout,
+    /// then create objects until `min_idle` are idle, as far as the maximum size allows.
+    pub async fn maintain(&self) {
+        let (evicted, missing) = {
+            let mut state = self.lock();
+            let mut expired = 0;
+            if let Some(timeout) = self.idle_timeout {
+                let now = Instant::now();
+                let evictable = state.idle.len().saturating_sub(self.min_idle);
+                expired = state.idle.iter().take(evictable).take_while(|idle| now - idle.since >= timeout).count();
+            }
+            let evicted: Vec<Idle<T>> = state.idle.drain(..expired).collect();
+            state.size -= expired;
+            let missing = self.min_idle.saturating_sub(state.idle.len()).min(self.max_size.saturating_sub(state.size));
+            state.size += missing;
+            (evicted, missing)
+        };
+        drop(evicted);
+        let mut rooms: Vec<_> = (0..missing).map(|_| Room { pool: self }).collect();
+        while let Some(room) = rooms.pop() {
+            let item = Box::new(T::create().await);
+            mem::forget(room);
+            self.pass_on(Grant::Idle(item, false));
+        }
+    }
+
+    /// Get a future that runs `maintain()` every `interval`, to be spawned on any executor,
+    /// and the handle that stops it. The future also finishes once the pool is dropped.
+    /// Requires the `std` feature, which provides the timer thread the loop sleeps on.
+    #[cfg(feature = "std")]
+    pub fn maintenance(self: &Arc<Self>, interval: Duration) -> (impl Future<Output = ()> + Send + 'static, MaintenanceHandle)
+    where
+        T: 'static
+    {
+        MaintenanceHandle::task(self, interval, |pool| async move { pool.maintain().await })
+    }
+
+    /// Run `maintain()` every `interval` on a background thread until the returned handle is stopped or dropped,
+    /// or the pool itself is dropped. Use `maintenance()` to run the loop on an executor instead.
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn spawn_maintenance(self: &Arc<Self>, interval: Duration) -> MaintenanceHandle
+    where
+        T: 'static
+    {
+        let (task, handle) = self.maintenance(interval);
+        handle.spawn(task)
+    }
+
+    /// Get a stream that yields a guard every time an object is available.
+    /// Guards that are held on to apply backpressure: once a bounded pool is exhausted, the stream waits for a release.
+    pub fn stream(&self) -> PoolStream<'_, T> {
+        PoolStream {
+            pool: self,
+            pending: None
+        }
+    }
+
+    /// Get the maximum number of objects the pool holds, which is `usize::MAX` for an unbounded pool.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Get the number of objects owned by the pool.
+    pub fn len(&self) -> usize {
+        self.lock().size
+    }
+
+    /// Check whether the pool owns no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of idle objects in the pool.
+    pub fn available(&self) -> usize {
+        self.lock().idle.len()
+    }
+
+    /// Get the number of tasks waiting for an object.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiters.len()
+    }
+}
+
+/// Flag that stops a maintenance loop, with the waker of the task running it.
+#[cfg(feature = "std")]
+struct StopSignal {
+    stopped: AtomicBool,
+    waker: Mutex<Option<Waker>>
+}
+
+/// Handle that stops the maintenance loop of `AsyncPool::maintenance`, `AsyncPool::spawn_maintenance` or
+/// `ConcurrentObjectPool::start_maintenance`. Dropping it stops the loop too. Requires the `std` feature.
+#[cfg(feature = "std")]
+pub struct MaintenanceHandle {
+    signal: Arc<StopSignal>,
+    /// The thread running the loop, if it was started on its own thread.
+    thread: Option<JoinHandle<()>>
+}
+
+#[cfg(feature = "std")]
+impl MaintenanceHandle {
+    /// Get a future that runs `pass` on `pool` every `interval`, and the handle that stops it.
+    /// The future also finishes once the pool is dropped.
+    pub(crate) fn task<P, F, Fut>(pool: &Arc<P>, interval: Duration, pass: F) -> (impl Future<Output = ()> + Send + 'static, MaintenanceHandle)
+    where
+        P: Send + Sync + 'static,
+        F: Fn(Arc<P>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send
+    {
+        let signal = Arc::new(StopSignal {
+            stopped: AtomicBool::new(false),
+            waker: Mutex::new(None)
+        });
+        let pool = Arc::downgrade(pool);
+        let task_signal = Arc::clone(&signal);
+        let task = async move {
+            loop {
+                if !(Stopped { signal: &task_signal, sleep: Sleep::until(Instant::now() + interval) }).await {
+                    return;
+                }
+                let Some(pool) = pool.upgrade() else {
+                    return;
+                };
+                pass(pool).await;
+            }
+        };
+        let handle = MaintenanceHandle {
+            signal,
+            thread: None
+        };
+        (task, handle)
+    }
+
+    /// Run the future of `task` on its own thread, which `stop` then joins.
+    pub(crate) fn spawn<F: Future<Output = ()> + Send + 'static>(mut self, task: F) -> MaintenanceHandle {
+        let thread = thread::Builder::new()
+            .name("object_pool-maintenance".into())
+            .spawn(move || executor::block_on(task))
+            .expect("failed to spawn the maintenance thread");
+        self.thread = Some(thread);
+        self
+    }
+
+    /// Stop the maintenance loop. If it runs on its own thread, this waits for a pass in progress to finish.
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.signal.stopped.store(true, Ordering::Release);
+        let waker = self.signal.waker.lock().unwrap_or_else(PoisonError::into_inner).take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        if let Some(thread) = self.thread.take() {
+            // A panic in the maintenance pass has already been reported by the thread itself.
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// Future that sleeps until the next maintenance pass, resolving to false early if maintenance is stopped.
+#[cfg(feature = "std")]
+struct Stopped<'a> {
+    signal: &'a StopSignal,
+    sleep: Sleep
+}
+
+#[cfg(feature = "std")]
+impl Future for Stopped<'_> {
+    type Output = bool;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+        *self.signal.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+        // Checked after storing the waker, so a concurrent stop either is seen here or wakes the task.
+        if self.signal.stopped.load(Ordering::Acquire) {
+            return Poll::Ready(false);
+        }
+        Pin::new(&mut self.sleep).poll(cx).map(|()| true)
+    }
+}
+
+/// Endless stream of guards returned by `AsyncPool::stream`.
+/// `poll_next` has the signature of `futures::Stream::poll_next`, so the stream can be wrapped into one directly.
+pub struct PoolStream<'a, T: AsyncPoolable> {
+    pool: &'a AsyncPool<T>,
+    /// The acquisition in progress, kept across polls so the stream keeps its place in the queue.
+    pending: Option<Pin<Box<dyn Future<Output = AsyncPoolBox<'a, T>> + Send + 'a>>>
+}
+
+impl<'a, T: AsyncPoolable> PoolStream<'a, T> {
+    /// Poll for the next guard. The stream never ends, so this only ever resolves to `Some`.
+    pub fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<AsyncPoolBox<'a, T>>> {
+        let pool = self.pool;
+        let pending = self.pending.get_or_insert_with(|| Box::pin(pool.acquire()));
+        let obj = ready!(pending.as_mut().poll(cx));
+        self.pending = None;
+        Poll::Ready(Some(obj))
+    }
+
+    /// Wait for the next guard.
+    pub async fn next(&mut self) -> Option<AsyncPoolBox<'a, T>> {
+        future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+/// Future that waits for the task's turn in the queue of waiters.
+/// Dropping it while it waits removes the task from the queue, and passes on a grant it has not picked up yet.
+struct WaitTurn<'a, T: AsyncPoolable> {
+    pool: &'a AsyncPool<T>,
+    ticket: Option<u64>
+}
+
+impl<T: AsyncPoolable> Future for WaitTurn<'_, T> {
+    type Output = Grant<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Grant<T>> {
+        let pool = self.pool;
+        let mut state = pool.lock();
+        let Some(ticket) = self.ticket else {
+            if state.waiters.is_empty() {
+                if let Some(grant) = pool.grant_now(&mut state) {
+                    return Poll::Ready(grant);
+                }
+            }
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiters.push_back((ticket, cx.waker().clone()));
+            self.ticket = Some(ticket);
+            return Poll::Pending;
+        };
+        if let Some(grant) = state.grants.remove(&ticket) {
+            self.ticket = None;
+            return Poll::Ready(grant);
+        }
+        if let Some((_, waker)) = state.waiters.iter_mut().find(|(waiting, _)| *waiting == ticket) {
+            waker.clone_from(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T: AsyncPoolable> Drop for WaitTurn<'_, T> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut state = self.pool.lock();
+        state.waiters.retain(|(waiting, _)| *waiting != ticket);
+        if let Some(grant) = state.grants.remove(&ticket) {
+            drop(state);
+            self.pool.pass_on(grant);
+        }
+    }
+}
+
+/// Room for one object that is being created. Dropping it passes the room on to the next waiting task.
+struct Room<'a, T: AsyncPoolable> {
+    pool: &'a AsyncPool<T>
+}
+
+impl<T: AsyncPoolable> Drop for Room<'_, T> {
+    fn drop(&mut self) {
+        self.pool.pass_on(Grant::Create);
+    }
+}
+
+/// Guard handed out by an AsyncPool. It returns its object to the pool when dropped,
+/// leaving the reset to the next task that acquires it.
+pub struct AsyncPoolBox<'a, T: AsyncPoolable> {
+    item: ManuallyDrop<Box<T>>,
+    phase: Phase,
+    pool: &'a AsyncPool<T>
+}
+
+impl<'a, T: AsyncPoolable> AsyncPoolBox<'a, T> {
+    fn new(item: Box<T>, pool: &'a AsyncPool<T>) -> AsyncPoolBox<'a, T> {
+        AsyncPoolBox {
+            item: ManuallyDrop::new(item),
+            phase: Phase::InUse,
+            pool
+        }
+    }
+
+    /// Drop the object, keeping its room in the pool for a replacement.
+    fn discard(self) -> Room<'a, T> {
+        let mut obj = ManuallyDrop::new(self);
+        // SAFETY: the item is never used again, and the guard is not dropped.
+        drop(unsafe {
+            ManuallyDrop::take(&mut obj.item)
+        });
+        Room { pool: obj.pool }
+    }
+
+    /// Reset the object now and return it to the pool ready to be handed out.
+    /// If this future is dropped before it completes, the object is returned to be reset later.
+    pub async fn release(mut self) {
+        self.phase = Phase::Resetting;
+        self.item.reset().await;
+        self.phase = Phase::Clean;
+    }
+}
+
+impl<T: AsyncPoolable> Deref for AsyncPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl<T: AsyncPoolable> DerefMut for AsyncPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
+
+impl<T: AsyncPoolable + fmt::Debug> fmt::Debug for AsyncPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: AsyncPoolable> Drop for AsyncPoolBox<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the item is never used again after being taken here.
+        let item = unsafe {
+            ManuallyDrop::take(&mut self.item)
+        };
+        if self.phase == Phase::Resetting && thread::panicking() {
+            // The reset panicked, so the object cannot be trusted; make room for a new one instead.
+            drop(item);
+            self.pool.pass_on(Grant::Create);
+        } else {
+            self.pool.pass_on(Grant::Idle(item, self.phase != Phase::Clean));
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::executor::block_on;
+    use crate::test_support::TestObject;
+
+    /// Future that is pending once before completing, like a real I/O operation.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Connection {
+        pub id: usize,
+        pub resets: usize,
+        pub dirty: bool,
+        pub alive: bool
+    }
+
+    impl AsyncPoolable for Connection {
+        async fn create() -> Connection {
+            YieldOnce(false).await;
+            Connection {
+                id: CONNECTIONS.fetch_add(1, Ordering::Relaxed),
+                resets: 0,
+                dirty: false,
+                alive: true
+            }
+        }
+
+        async fn reset(&mut self) {
+            YieldOnce(false).await;
+            self.resets += 1;
+            self.dirty = false;
+        }
+
+        async fn is_healthy(&mut self) -> bool {
+            YieldOnce(false).await;
+            self.alive
+        }
+    }
+
+    #[test]
+    fn test_async_pool() {
+        let pool = AsyncPool::<TestObject>::new();
+        block_on(async {
+            let mut obj = pool.acquire().await;
+            obj.value = 4;
+            assert_eq!(pool.len(), 1);
+        });
+        assert_eq!(pool.available(), 1);
+        let obj = block_on(pool.acquire());
+        assert_eq!(obj.value, 0);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_acquire_waits_for_release() {
+        let pool = AsyncPool::<TestObject>::bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut held = block_on(pool.try_acquire()).unwrap();
+        held.value = 1;
+        let mut first = pin!(pool.acquire());
+        let mut second = pin!(pool.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(block_on(pool.try_acquire()).is_none());
+        assert_eq!(pool.waiting(), 2);
+
+        drop(held);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(obj) = first.as_mut().poll(&mut cx) else {
+            panic!("the first waiter should be served first");
+        };
+        assert_eq!(obj.value, 0);
+        drop(obj);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_dropped_waiter_leaves_queue() {
+        let pool = AsyncPool::<TestObject>::bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let held = block_on(pool.try_acquire()).unwrap();
+        {
+            let mut waiting = pin!(pool.acquire());
+            assert!(waiting.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(pool.waiting(), 1);
+        }
+        assert_eq!(pool.waiting(), 0);
+        drop(held);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_async_poolable() {
+        let pool = AsyncPool::<Connection>::bounded(2);
+        let (first, second) = block_on(async {
+            let mut first = pool.acquire().await;
+            let second = pool.acquire().await;
+            first.dirty = true;
+            let ids = (first.id, second.id);
+            // Dropped: reset when acquired again. Released: reset right away.
+            drop(first);
+            second.release().await;
+            ids
+        });
+        assert_ne!(first, second);
+        assert_eq!(pool.len(), 2);
+        block_on(async {
+            let a = pool.acquire().await;
+            let b = pool.acquire().await;
+            assert!(!a.dirty && !b.dirty);
+            assert!(a.resets == 1 && b.resets == 1);
+        });
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_async_pool_threads() {
+        let pool = AsyncPool::<TestObject>::bounded(2);
+        thread::scope(|scope| {
+            for worker in 1..=4 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        block_on(async {
+                            let mut obj = pool.acquire().await;
+                            assert_eq!(obj.value, 0);
+                            obj.value = worker;
+                        });
+                    }
+                });
+            }
+        });
+        assert!(pool.len() <= 2);
+        assert_eq!(pool.available(), pool.len());
+        assert_eq!(pool.waiting(), 0);
+    }
+
+    #[test]
+    fn test_cancelled_acquire_returns_grant() {
+        let pool = AsyncPool::<TestObject>::bounded(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let held = block_on(pool.try_acquire()).unwrap();
+        let mut first = Box::pin(pool.acquire());
+        let mut second = pin!(pool.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        // The object is granted to the first waiter, which is dropped before it picks it up.
+        drop(held);
+        drop(first);
+        assert_eq!(pool.waiting(), 0);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_acquire_timeout() {
+        let pool = AsyncPool::<TestObject>::bounded(1);
+        let held = block_on(pool.acquire());
+        assert!(block_on(pool.acquire_timeout(Duration::from_millis(10))).is_none());
+        assert_eq!(pool.waiting(), 0);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                drop(held);
+            });
+            assert!(block_on(pool.acquire_timeout(Duration::from_secs(10))).is_some());
+        });
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_stream_applies_backpressure() {
+        let pool = AsyncPool::<TestObject>::bounded(2);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut stream = pool.stream();
+        let first = block_on(stream.next()).unwrap();
+        let _second = block_on(stream.next()).unwrap();
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        assert_eq!(pool.waiting(), 1);
+        drop(first);
+        assert!(matches!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(Some(_))));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_maintain() {
+        let pool = AsyncPool::<TestObject>::bounded(3).with_min_idle(2).with_idle_timeout(Duration::ZERO);
+        block_on(pool.maintain());
+        assert_eq!(pool.available(), 2);
+        let objs = block_on(async { vec![pool.acquire().await, pool.acquire().await, pool.acquire().await] });
+        // The pool is at its maximum size, so nothing can be refilled.
+        block_on(pool.maintain());
+        assert_eq!(pool.len(), 3);
+        drop(objs);
+        // Every object has timed out, but eviction keeps `min_idle` of them.
+        block_on(pool.maintain());
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_spawn_maintenance() {
+        let pool = Arc::new(AsyncPool::<TestObject>::new().with_min_idle(3));
+        let handle = pool.spawn_maintenance(Duration::from_millis(1));
+        while pool.available() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.stop();
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_maintenance_task() {
+        fn assert_send<F: Future + Send>(future: F) -> F {
+            future
+        }
+        let pool = Arc::new(AsyncPool::<Connection>::new().with_min_idle(1));
+        let (task, handle) = pool.maintenance(Duration::from_millis(1));
+        thread::scope(|scope| {
+            // Any executor can drive the task; here it is a plain thread.
+            let runner = scope.spawn(|| block_on(assert_send(task)));
+            while pool.available() < 1 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            handle.stop();
+            runner.join().unwrap();
+        });
+        drop(assert_send(pool.acquire()));
+        drop(assert_send(pool.acquire_timeout(Duration::ZERO)));
+    }
+
+    #[test]
+    fn test_unhealthy_objects_are_replaced() {
+        let pool = AsyncPool::<Connection>::bounded(1);
+        let (dead, replacement) = block_on(async {
+            let mut obj = pool.acquire().await;
+            obj.alive = false;
+            let dead = obj.id;
+            drop(obj);
+            (dead, pool.acquire().await.id)
+        });
+        assert_ne!(dead, replacement);
+        assert_eq!(pool.len(), 1);
+        let again = block_on(async { pool.acquire().await.id });
+        assert_eq!(again, replacement);
+    }
+
+    #[test]
+    fn test_exhausted_behavior() {
+        let failing = AsyncPool::<TestObject>::bounded(1).with_exhausted_behavior(ExhaustedBehavior::Fail);
+        let held = block_on(failing.acquire());
+        assert_eq!(block_on(failing.checked_acquire()).err(), Some(PoolError::Exhausted));
+        drop(held);
+
+        let growing = AsyncPool::<TestObject>::bounded(1).with_exhausted_behavior(ExhaustedBehavior::Grow).with_min_idle(1);
+        let held = (block_on(growing.acquire()), block_on(growing.acquire()));
+        assert_eq!(growing.len(), 2);
+        // A pool that has grown past its maximum size has no room to refill.
+        block_on(growing.maintain());
+        assert_eq!((growing.len(), growing.available()), (2, 0));
+        drop(held);
+    }
+
+    #[test]
+    fn test_max_idle() {
+        let pool = AsyncPool::<TestObject>::new().with_max_idle(1);
+        let burst = block_on(async { vec![pool.acquire().await, pool.acquire().await] });
+        assert_eq!(pool.len(), 2);
+        drop(burst);
+        assert_eq!((pool.len(), pool.available()), (1, 1));
+    }
+}
@@ -0,0 +1,210 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+
+use crate::object_pool::object_pool::Poolable;
+use crate::sync::AtomicU64;
+
+/// Number of slots tracked by one word of the bitmap.
+const BITS: usize = u64::BITS as usize;
+
+/// Fixed capacity pool that tracks its free slots in an atomic bitmap, one bit per slot.
+///
+/// A set bit marks an available slot. Acquiring finds the first set bit and clears it with a single atomic
+/// `fetch_and`, which only fails if another thread took the same slot first; then the search goes on within
+/// the same word. Slots with low indices are reused first, which keeps the working set compact.
+/// Unlike the free list of a LockFreePool there is nothing to tag, since a slot is identified by its bit alone.
+pub struct BitmapPool<T: Poolable> {
+    slots: Box<[UnsafeCell<T>]>,
+    bits: Box<[AtomicU64]>
+}
+
+// SAFETY: items are only accessed by the single guard that cleared their bit.
+unsafe impl<T: Poolable + Send> Send for BitmapPool<T> {}
+unsafe impl<T: Poolable + Send> Sync for BitmapPool<T> {}
+
+impl<T: Poolable> BitmapPool<T> {
+    /// Create a BitmapPool holding `capacity` items, which are all created up front.
+    pub fn with_capacity(capacity: usize) -> BitmapPool<T> {
+        let bits = (0..capacity.div_ceil(BITS)).map(|word| {
+            let slots = (capacity - word * BITS).min(BITS);
+            AtomicU64::new(if slots == BITS { u64::MAX } else { (1 << slots) - 1 })
+        }).collect();
+        BitmapPool {
+            slots: (0..capacity).map(|_| UnsafeCell::new(T::new())).collect(),
+            bits
+        }
+    }
+
+    /// Get the first available item from the pool, or None if every item is handed out.
+    pub fn try_get(&self) -> Option<BitmapPoolBox<'_, T>> {
+        for (word, bits) in self.bits.iter().enumerate() {
+            let mut free = bits.load(Ordering::Relaxed);
+            while free != 0 {
+                let mask = 1 << free.trailing_zeros();
+                let previous = bits.fetch_and(!mask, Ordering::Acquire);
+                if previous & mask != 0 {
+                    return Some(BitmapPoolBox {
+                        index: word * BITS + mask.trailing_zeros() as usize,
+                        pool: self
+                    });
+                }
+                free = previous & !mask;
+            }
+        }
+        None
+    }
+
+    /// Reset the item of a slot and mark the slot available again.
+    /// If `reset()` panics, the item is replaced by a new one before its bit is set, and the panic continues
+    /// once the old item is dropped.
+    fn release(&self, index: usize) {
+        let value = self.slots[index].get();
+        // SAFETY: the releasing guard had exclusive access to the slot.
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*value).reset() }));
+        let broken = reset.is_err().then(|| unsafe { mem::replace(&mut *value, T::new()) });
+        self.bits[index / BITS].fetch_or(1 << (index % BITS), Ordering::Release);
+        if let Err(payload) = reset {
+            drop(broken);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the number of items in the pool.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Get the number of available items in the pool.
+    /// The words of the bitmap are read one after the other, so the count may be off under contention.
+    pub fn available(&self) -> usize {
+        self.bits.iter().map(|bits| bits.load(Ordering::Relaxed).count_ones() as usize).sum()
+    }
+}
+
+/// Guard handed out by a BitmapPool. It releases its item when dropped.
+pub struct BitmapPoolBox<'a, T: Poolable> {
+    index: usize,
+    pool: &'a BitmapPool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item.
+unsafe impl<T: Poolable + Send> Send for BitmapPoolBox<'_, T> {}
+unsafe impl<T: Poolable + Sync> Sync for BitmapPoolBox<'_, T> {}
+
+impl<T: Poolable> BitmapPoolBox<'_, T> {
+    /// Get the slot index of the item.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T: Poolable> Deref for BitmapPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.pool.slots[self.index].get()
+        }
+    }
+}
+
+impl<T: Poolable> DerefMut for BitmapPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.pool.slots[self.index].get()
+        }
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for BitmapPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable> Drop for BitmapPoolBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    struct PanickyObject {
+        pub fail: bool
+    }
+
+    impl Poolable for PanickyObject {
+        fn new() -> PanickyObject {
+            PanickyObject {
+                fail: false
+            }
+        }
+
+        fn reset(&mut self) {
+            assert!(!self.fail, "reset failed");
+        }
+    }
+
+    #[test]
+    fn test_bitmap_pool() {
+        let pool = BitmapPool::<TestObject>::with_capacity(70);
+        let mut held: Vec<_> = (0..70).map(|_| pool.try_get().unwrap()).collect();
+        assert!(held.iter().enumerate().all(|(index, obj)| obj.index() == index));
+        assert!(pool.try_get().is_none());
+        held[65].value = 1;
+        // The first free slot is found again, across words.
+        drop(held.remove(65));
+        drop(held.remove(3));
+        assert_eq!(pool.available(), 2);
+        let reused = pool.try_get().unwrap();
+        assert_eq!(reused.index(), 3);
+        let second = pool.try_get().unwrap();
+        assert_eq!((second.index(), second.value), (65, 0));
+        drop((reused, second, held));
+        assert_eq!(pool.available(), pool.capacity());
+        assert!(BitmapPool::<TestObject>::with_capacity(0).try_get().is_none());
+    }
+
+    #[test]
+    fn test_panicking_reset_keeps_slot() {
+        let pool = BitmapPool::<PanickyObject>::with_capacity(1);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.try_get().unwrap().fail = true;
+        }));
+        assert!(result.is_err());
+        // The bit is set again, with a new item in the slot.
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.try_get().unwrap().fail);
+    }
+
+    #[test]
+    fn test_bitmap_pool_contention() {
+        let pool = BitmapPool::<TestObject>::with_capacity(4);
+        thread::scope(|scope| {
+            for worker in 1..=8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        if let Some(mut obj) = pool.try_get() {
+                            // Another thread sharing this item would overwrite the value.
+                            assert_eq!(obj.value, 0);
+                            obj.value = worker;
+                            thread::yield_now();
+                            assert_eq!(obj.value, worker);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available(), 4);
+    }
+}
@@ -0,0 +1,459 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::async_pool::AsyncPool;
+use crate::concurrent_pool::ConcurrentObjectPool;
+use crate::object_pool::object_pool::{
+    AutoReturnObjectPool, ExhaustedBehavior, GrowthPolicy, LeakReport, ObjectPool, Poolable, PoolableWith, ResetTiming, ReuseOrder, Validate
+};
+use crate::registry;
+use crate::semaphore_pool::SemaphorePool;
+use crate::sharded_pool::ShardedPool;
+use crate::thread_local_pool::ThreadLocalPool;
+
+/// Fluent configuration of a pool, which then builds whichever pool variant is needed.
+///
+/// The factory is chosen when the builder is created: `PoolBuilder::new()` uses `Poolable::new`,
+/// `PoolBuilder::with_params` uses `PoolableWith::new_with`, and `PoolBuilder::with_factory` uses a closure.
+///
+/// Options that the chosen variant cannot honour are rejected when building instead of being dropped:
+/// AsyncPool, SemaphorePool and ShardedPool do not wrap a configurable ObjectPool, so they only take
+/// the options documented on their builders.
+pub struct PoolBuilder<T> {
+    raw: ObjectPool<T>,
+    /// Options applied to `raw`, which only the variants built on an ObjectPool honour.
+    raw_options: Vec<&'static str>,
+    initial_size: usize,
+    max_size: usize,
+    exhausted: Option<ExhaustedBehavior>,
+    /// Idle limits, kept apart from `raw` as well for the variants that do not wrap an ObjectPool.
+    max_idle: usize,
+    min_idle: usize,
+    idle_timeout: Option<Duration>,
+    name: Option<String>
+}
+
+impl<T: Poolable> Default for PoolBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> PoolBuilder<T> {
+    /// Start configuring a pool of a Poolable type.
+    pub fn new() -> PoolBuilder<T> {
+        PoolBuilder::from_raw(ObjectPool::new(), Vec::new())
+    }
+}
+
+impl<T> PoolBuilder<T> {
+    fn from_raw(raw: ObjectPool<T>, raw_options: Vec<&'static str>) -> PoolBuilder<T> {
+        PoolBuilder {
+            raw,
+            raw_options,
+            initial_size: 0,
+            max_size: usize::MAX,
+            exhausted: None,
+            max_idle: usize::MAX,
+            min_idle: 0,
+            idle_timeout: None,
+            name: None
+        }
+    }
+
+    /// Apply an option to the raw pool, remembering its name for the variants that cannot honour it.
+    fn raw_option(mut self, option: &'static str, apply: impl FnOnce(ObjectPool<T>) -> ObjectPool<T>) -> PoolBuilder<T> {
+        self.raw = apply(self.raw);
+        self.raw_options.push(option);
+        self
+    }
+
+    /// Panic if an option was set that `variant` cannot honour: one of `unsupported` that is set,
+    /// or any raw pool option if the variant does not wrap an ObjectPool.
+    #[track_caller]
+    fn reject(&self, variant: &str, wraps_raw: bool, unsupported: &[(&'static str, bool)]) {
+        let raw_options = if wraps_raw { &[][..] } else { &self.raw_options[..] };
+        let set = unsupported.iter().filter(|(_, set)| *set).map(|(option, _)| *option);
+        let rejected: Vec<_> = raw_options.iter().copied().chain(set).collect();
+        assert!(rejected.is_empty(), "{variant} does not support the builder options: {}", rejected.join(", "));
+    }
+
+    /// Start configuring a pool whose objects are created by `factory`, like `ObjectPool::with_factory`.
+    pub fn with_factory<F: FnMut() -> T + Send + 'static>(factory: F) -> PoolBuilder<T> {
+        PoolBuilder::from_raw(ObjectPool::with_factory(factory), vec!["with_factory"])
+    }
+
+    /// Start configuring a pool whose objects are created and reset with `params`, like `ObjectPool::with_params`.
+    pub fn with_params(params: T::Params) -> PoolBuilder<T>
+    where
+        T: PoolableWith,
+        T::Params: Send + Sync + 'static
+    {
+        PoolBuilder::from_raw(ObjectPool::with_params(params), vec!["with_params"])
+    }
+
+    /// Create `initial_size` objects when the pool is built.
+    pub fn initial_size(mut self, initial_size: usize) -> PoolBuilder<T> {
+        self.initial_size = initial_size;
+        self
+    }
+
+    /// Hold at most `max_size` objects.
+    pub fn max_size(mut self, max_size: usize) -> PoolBuilder<T> {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Choose what happens when every object is in use at the maximum size.
+    /// Defaults to `Block` for the concurrent variants and to `Fail` for the single-threaded ones, which cannot block.
+    pub fn exhausted(mut self, behavior: ExhaustedBehavior) -> PoolBuilder<T> {
+        self.exhausted = Some(behavior);
+        self
+    }
+
+    /// Reset released objects with `reset` instead of `Poolable::reset`.
+    pub fn reset<F: FnMut(&mut T) + Send + 'static>(self, reset: F) -> PoolBuilder<T> {
+        self.raw_option("reset", |raw| raw.with_reset(reset))
+    }
+
+    /// Run `reset` on released objects after `Poolable::reset`.
+    pub fn extra_reset<F: FnMut(&mut T) + Send + 'static>(self, reset: F) -> PoolBuilder<T> {
+        self.raw_option("extra_reset", |raw| raw.with_extra_reset(reset))
+    }
+
+    /// Discard released objects whose `try_reset` fails, like `ObjectPool::with_try_reset`.
+    pub fn try_reset<E, F: FnMut(&mut T) -> Result<(), E> + Send + 'static>(self, try_reset: F) -> PoolBuilder<T> {
+        self.raw_option("try_reset", |raw| raw.with_try_reset(try_reset))
+    }
+
+    /// Replace objects discarded by a failed `try_reset` with new ones.
+    pub fn replace_discarded(self) -> PoolBuilder<T> {
+        self.raw_option("replace_discarded", |raw| raw.with_replace_discarded())
+    }
+
+    /// Never reset released objects, like `ObjectPool::without_reset`.
+    pub fn no_reset(self) -> PoolBuilder<T> {
+        self.raw_option("no_reset", |raw| raw.without_reset())
+    }
+
+    /// Reset released objects on release or when they are acquired again.
+    pub fn reset_timing(self, timing: ResetTiming) -> PoolBuilder<T> {
+        self.raw_option("reset_timing", |raw| raw.with_reset_timing(timing))
+    }
+
+    /// Check objects with `Validate::is_valid` before handing them out, replacing invalid ones.
+    pub fn validate(self) -> PoolBuilder<T>
+    where
+        T: Validate
+    {
+        self.raw_option("validate", |raw| raw.with_validation())
+    }
+
+    /// Also check objects with `Validate::is_valid` when they are released.
+    pub fn validate_on_release(self) -> PoolBuilder<T>
+    where
+        T: Validate
+    {
+        self.raw_option("validate_on_release", |raw| raw.with_release_validation())
+    }
+
+    /// Destroy objects on release once they have been handed out `max_uses` times.
+    pub fn max_uses(self, max_uses: u64) -> PoolBuilder<T> {
+        self.raw_option("max_uses", |raw| raw.with_max_uses(max_uses))
+    }
+
+    /// Choose how many objects are created when `get()` finds no idle object.
+    pub fn growth_policy<P: GrowthPolicy + 'static>(self, policy: P) -> PoolBuilder<T> {
+        self.raw_option("growth_policy", |raw| raw.with_growth_policy(policy))
+    }
+
+    /// Choose which idle object is handed out next.
+    pub fn reuse_order(self, order: ReuseOrder) -> PoolBuilder<T> {
+        self.raw_option("reuse_order", |raw| raw.with_reuse_order(order))
+    }
+
+    /// Cap the pool by the total size hint of its objects instead of their number.
+    pub fn max_bytes(self, max_bytes: usize) -> PoolBuilder<T> {
+        self.raw_option("max_bytes", |raw| raw.with_max_bytes(max_bytes))
+    }
+
+    /// Allocate objects in slabs of `chunk_size`.
+    pub fn chunk_size(self, chunk_size: usize) -> PoolBuilder<T> {
+        self.raw_option("chunk_size", |raw| raw.with_chunk_size(chunk_size))
+    }
+
+    /// Prefetch the first `lines` cache lines of every object handed out.
+    pub fn prefetch(self, lines: usize) -> PoolBuilder<T> {
+        self.raw_option("prefetch", |raw| raw.with_prefetch(lines))
+    }
+
+    /// Give every object its own cache line, so threads working on different objects do not false-share.
+    pub fn cache_line_padding(self) -> PoolBuilder<T> {
+        self.raw_option("cache_line_padding", |raw| raw.with_cache_line_padding())
+    }
+
+    /// Align every object to at least `align` bytes, which must be a power of two.
+    pub fn alignment(self, align: usize) -> PoolBuilder<T> {
+        self.raw_option("alignment", |raw| raw.with_alignment(align))
+    }
+
+    /// Back the objects with huge pages. Only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn huge_pages(self, huge_pages: crate::object_pool::object_pool::HugePages) -> PoolBuilder<T> {
+        self.raw_option("huge_pages", |raw| raw.with_huge_pages(huge_pages))
+    }
+
+    /// Give the memory of destroyed objects back to the kernel. Only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn decommit(self) -> PoolBuilder<T> {
+        self.raw_option("decommit", |raw| raw.with_decommit())
+    }
+
+    /// Keep the objects in locked memory that is wiped when they are destroyed. Only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn secure_memory(self) -> PoolBuilder<T> {
+        self.raw_option("secure_memory", |raw| raw.with_secure_memory())
+    }
+
+    /// Wipe the bytes that `secret` selects whenever an object is released, e.g. key material in secure memory.
+    pub fn zeroize(self, secret: fn(&mut T) -> &mut [u8]) -> PoolBuilder<T> {
+        self.raw_option("zeroize", |raw| raw.with_zeroize(secret))
+    }
+
+    /// Allocate objects from `allocator`. Requires the `allocator_api` feature and a nightly compiler.
+    #[cfg(feature = "allocator_api")]
+    pub fn allocator<A: std::alloc::Allocator + Send + Sync + 'static>(self, allocator: A) -> PoolBuilder<T> {
+        self.raw_option("allocator", |raw| raw.with_allocator(allocator))
+    }
+
+    /// Keep at most `max_idle` objects idle, destroying objects released beyond that.
+    pub fn max_idle(mut self, max_idle: usize) -> PoolBuilder<T> {
+        self.max_idle = max_idle;
+        self.raw = self.raw.with_max_idle(max_idle);
+        self
+    }
+
+    /// Let `maintain()` keep at least `min_idle` objects idle.
+    pub fn min_idle(mut self, min_idle: usize) -> PoolBuilder<T> {
+        self.min_idle = min_idle;
+        self.raw = self.raw.with_min_idle(min_idle);
+        self
+    }
+
+    /// Let `maintain()` evict objects idle for longer than `timeout`.
+    pub fn idle_timeout(mut self, timeout: Duration) -> PoolBuilder<T> {
+        self.idle_timeout = Some(timeout);
+        self.raw = self.raw.with_idle_timeout(timeout);
+        self
+    }
+
+    /// Retire objects older than `max_age`.
+    pub fn max_age(self, max_age: Duration) -> PoolBuilder<T> {
+        self.raw_option("max_age", |raw| raw.with_max_age(max_age))
+    }
+
+    /// Run `hook` on every newly created object.
+    pub fn on_create<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> PoolBuilder<T> {
+        self.raw_option("on_create", |raw| raw.with_on_create(hook))
+    }
+
+    /// Run `hook` on every object right before it is handed out.
+    pub fn on_acquire<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> PoolBuilder<T> {
+        self.raw_option("on_acquire", |raw| raw.with_on_acquire(hook))
+    }
+
+    /// Run `hook` on every released object before it is reset.
+    pub fn on_release<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> PoolBuilder<T> {
+        self.raw_option("on_release", |raw| raw.with_on_release(hook))
+    }
+
+    /// Run `hook` on every object the pool drops.
+    pub fn on_destroy<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> PoolBuilder<T> {
+        self.raw_option("on_destroy", |raw| raw.with_on_destroy(hook))
+    }
+
+    /// Send a LeakReport to `sink` if the pool is dropped while objects are in use.
+    pub fn leak_sink<F: Fn(&LeakReport) + Send + 'static>(self, sink: F) -> PoolBuilder<T> {
+        self.raw_option("leak_sink", |mut raw| {
+            raw.set_leak_sink(sink);
+            raw
+        })
+    }
+
+    /// Register the pool in the registry under `name`, so its stats show up in `registry::inspect()`.
+    /// Only `build_shared` and `build_thread_local` can register a pool, since the registry needs to observe it
+    /// through an `Arc`; the other builders reject this option.
+    pub fn metrics(mut self, name: impl Into<String>) -> PoolBuilder<T> {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Build a raw ObjectPool.
+    /// Panics if a metrics name was set.
+    #[track_caller]
+    pub fn build_raw(mut self) -> ObjectPool<T> {
+        self.reject("ObjectPool", true, &[("metrics", self.name.is_some())]);
+        if self.max_size != usize::MAX {
+            let behavior = self.exhausted.unwrap_or(ExhaustedBehavior::Fail);
+            self.raw = self.raw.with_max_size(self.max_size, behavior);
+        }
+        self.raw.reserve(self.initial_size);
+        self.raw
+    }
+
+    /// Build an AutoReturnObjectPool.
+    /// Panics if a metrics name was set.
+    #[track_caller]
+    pub fn build(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.build_raw())
+    }
+}
+
+impl<T: Send> PoolBuilder<T> {
+    /// Build a ConcurrentObjectPool bounded by the maximum size.
+    /// Panics if a metrics name was set; use `build_shared` to register the pool.
+    #[track_caller]
+    pub fn build_concurrent(mut self) -> ConcurrentObjectPool<T> {
+        self.reject("ConcurrentObjectPool", true, &[("metrics", self.name.is_some())]);
+        // The concurrent pool enforces the maximum size itself, so the raw pool stays unbounded.
+        self.raw.reserve(self.initial_size.min(self.max_size));
+        let behavior = self.exhausted.unwrap_or(ExhaustedBehavior::Block);
+        ConcurrentObjectPool::from_raw(self.raw, self.max_size).with_exhausted_behavior(behavior)
+    }
+
+    /// Build a ConcurrentObjectPool inside an `Arc`, registering it if a metrics name was set.
+    #[track_caller]
+    pub fn build_shared(mut self) -> Arc<ConcurrentObjectPool<T>>
+    where
+        T: 'static
+    {
+        let name = self.name.take();
+        let pool = Arc::new(self.build_concurrent());
+        if let Some(name) = name {
+            registry::register(name, &pool);
+        }
+        pool
+    }
+}
+
+impl<T: Poolable + Send + 'static> PoolBuilder<T> {
+    /// Build a ThreadLocalPool that caches up to `cache_size` objects per thread in front of a shared pool
+    /// built like `build_shared`, registering the shared pool if a metrics name was set.
+    #[track_caller]
+    pub fn build_thread_local(self, cache_size: usize) -> ThreadLocalPool<T> {
+        ThreadLocalPool::from_shared(self.build_shared(), cache_size)
+    }
+}
+
+impl<T: Poolable + Send> PoolBuilder<T> {
+    /// Build an AsyncPool, which takes the maximum size, the ExhaustedBehavior and the idle limits.
+    /// Objects are created on demand or by `AsyncPool::maintain()`, so an initial size is rejected,
+    /// as are the metrics name and every option of the raw pool. Panics if any of them is set.
+    #[track_caller]
+    pub fn build_async(self) -> AsyncPool<T> {
+        self.reject("AsyncPool", false, &[("initial_size", self.initial_size > 0), ("metrics", self.name.is_some())]);
+        let pool = AsyncPool::bounded(self.max_size)
+            .with_exhausted_behavior(self.exhausted.unwrap_or(ExhaustedBehavior::Wait))
+            .with_max_idle(self.max_idle)
+            .with_min_idle(self.min_idle);
+        match self.idle_timeout {
+            Some(timeout) => pool.with_idle_timeout(timeout),
+            None => pool
+        }
+    }
+
+    /// Build a SemaphorePool with one permit per object up to the maximum size.
+    /// A SemaphorePool always waits for a permit and keeps every object it creates, so only the maximum size
+    /// is taken. Panics if any other option is set.
+    #[track_caller]
+    pub fn build_semaphore(self) -> SemaphorePool<T> {
+        self.reject("SemaphorePool", false, &[
+            ("initial_size", self.initial_size > 0),
+            ("exhausted", self.exhausted.is_some()),
+            ("max_idle", self.max_idle != usize::MAX),
+            ("min_idle", self.min_idle > 0),
+            ("idle_timeout", self.idle_timeout.is_some()),
+            ("metrics", self.name.is_some())
+        ]);
+        SemaphorePool::new(self.max_size)
+    }
+
+    /// Build a ShardedPool with `shards` shards and the initial size spread over them.
+    /// Shards are unbounded raw pools created with `Poolable::new`, so only the initial size is taken.
+    /// Panics if any other option is set.
+    #[track_caller]
+    pub fn build_sharded(self, shards: usize) -> ShardedPool<T> {
+        self.reject("ShardedPool", false, &[
+            ("max_size", self.max_size != usize::MAX),
+            ("exhausted", self.exhausted.is_some()),
+            ("max_idle", self.max_idle != usize::MAX),
+            ("min_idle", self.min_idle > 0),
+            ("idle_timeout", self.idle_timeout.is_some()),
+            ("metrics", self.name.is_some())
+        ]);
+        let pool = ShardedPool::with_shards(shards);
+        pool.reserve(self.initial_size);
+        pool
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::executor::block_on;
+    use std::time::Duration;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_builder() {
+        let pool = PoolBuilder::<TestObject>::new().initial_size(3).extra_reset(|obj| obj.value = 7).build();
+        assert_eq!(pool.available(), 3);
+        pool.get().value = 1;
+        assert_eq!(pool.get().value, 7);
+
+        let buffers = PoolBuilder::with_factory(|| Vec::<u8>::with_capacity(16))
+            .reset(Vec::clear)
+            .initial_size(1)
+            .max_size(1)
+            .build_concurrent();
+        let mut buffer = buffers.get();
+        buffer.push(1);
+        assert!(buffers.get_timeout(Duration::ZERO).is_none());
+        drop(buffer);
+        assert!(buffers.get().is_empty());
+    }
+
+    #[test]
+    fn test_build_shared_registers_metrics() {
+        let pool = PoolBuilder::<TestObject>::new().initial_size(2).metrics("builder::test").build_shared();
+        let stats = registry::inspect().into_iter().find(|stats| stats.name == "builder::test").unwrap();
+        assert_eq!(stats.counters.available, 2);
+        assert!(registry::unregister("builder::test"));
+        drop(pool);
+
+        let pool = PoolBuilder::<TestObject>::new().initial_size(1).metrics("builder::local").build_thread_local(4);
+        assert!(registry::inspect().iter().any(|stats| stats.name == "builder::local"));
+        assert_eq!(pool.shared().available(), 1);
+        assert!(registry::unregister("builder::local"));
+    }
+
+    #[test]
+    fn test_build_other_variants() {
+        let pool = PoolBuilder::<TestObject>::new().max_size(1).exhausted(ExhaustedBehavior::Fail).build_async();
+        let obj = block_on(pool.acquire());
+        assert!(block_on(pool.checked_acquire()).is_err());
+        drop(obj);
+
+        let pool = PoolBuilder::<TestObject>::new().max_size(2).build_semaphore();
+        assert_eq!(pool.available_permits(), 2);
+
+        let pool = PoolBuilder::<TestObject>::new().initial_size(4).build_sharded(2);
+        assert_eq!((pool.shard_available(0), pool.shard_available(1)), (2, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "AsyncPool does not support the builder options: reset, metrics")]
+    fn test_unsupported_options_are_rejected() {
+        PoolBuilder::<TestObject>::new().reset(|obj| obj.value = 1).metrics("builder::rejected").build_async();
+    }
+}
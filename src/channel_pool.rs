@@ -0,0 +1,187 @@
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::object_pool::object_pool::{ObjectPool, Poolable};
+
+/// Item travelling back to its pool through the return channel.
+/// If the pool is gone before the item is received, dropping the message frees the item.
+struct Returned<T: Poolable> {
+    item: *mut T,
+    generation: u64
+}
+
+// SAFETY: the message is the only holder of its item.
+unsafe impl<T: Poolable + Send> Send for Returned<T> {}
+
+impl<T: Poolable> Drop for Returned<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ObjectPool::destroy(self.item);
+        }
+    }
+}
+
+/// Pool owned by a single thread whose guards can be dropped on any thread.
+/// Dropped guards send their item back through an MPSC channel; the owning thread
+/// resets and recycles returned items on its next `get()` or `maintain()`.
+pub struct ChannelPool<T: Poolable + Send> {
+    pool: ObjectPool<T>,
+    returns: Receiver<Returned<T>>,
+    sender: Sender<Returned<T>>
+}
+
+impl<T: Poolable + Send> Default for ChannelPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable + Send> ChannelPool<T> {
+    /// Create a new ChannelPool.
+    pub fn new() -> ChannelPool<T> {
+        let (sender, returns) = mpsc::channel();
+        ChannelPool {
+            pool: ObjectPool::new(),
+            returns,
+            sender
+        }
+    }
+
+    /// Release every item waiting in the return channel.
+    /// Returns the number of items released.
+    pub fn maintain(&mut self) -> usize {
+        let mut count = 0;
+        for returned in self.returns.try_iter() {
+            let returned = ManuallyDrop::new(returned);
+            unsafe {
+                self.pool.release_from(returned.item, returned.generation);
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// Get a ChannelPoolBox from the pool, recycling returned items first.
+    #[track_caller]
+    pub fn get(&mut self) -> ChannelPoolBox<T> {
+        self.maintain();
+        let item = self.pool.get();
+        ChannelPoolBox {
+            item,
+            generation: self.pool.generation(),
+            returns: self.sender.clone()
+        }
+    }
+
+    /// Reserve a number of items in the pool.
+    #[track_caller]
+    pub fn reserve(&mut self, count: usize) {
+        self.pool.reserve(count);
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// Get the number of available items in the pool, not counting items waiting in the return channel.
+    pub fn available(&self) -> usize {
+        self.pool.available()
+    }
+}
+
+impl<T: Poolable + Send> Drop for ChannelPool<T> {
+    fn drop(&mut self) {
+        self.maintain();
+    }
+}
+
+/// Guard handed out by a ChannelPool. It owns no reference to the pool, so it can move to any thread;
+/// when dropped, its item is sent back to the pool.
+pub struct ChannelPoolBox<T: Poolable + Send> {
+    item: *mut T,
+    generation: u64,
+    returns: Sender<Returned<T>>
+}
+
+// SAFETY: the guard has exclusive access to its item and returns it through the channel.
+unsafe impl<T: Poolable + Send> Send for ChannelPoolBox<T> {}
+unsafe impl<T: Poolable + Send + Sync> Sync for ChannelPoolBox<T> {}
+
+impl<T: Poolable + Send> Deref for ChannelPoolBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send> DerefMut for ChannelPoolBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send + fmt::Debug> fmt::Debug for ChannelPoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + Send> Drop for ChannelPoolBox<T> {
+    fn drop(&mut self) {
+        // If the pool is gone, the message is handed back and dropped, which frees the item.
+        let _ = self.returns.send(Returned {
+            item: self.item,
+            generation: self.generation
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_release_on_foreign_thread() {
+        let mut pool = ChannelPool::<TestObject>::new();
+        let objs: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        thread::spawn(move || {
+            for mut obj in objs {
+                obj.value = 1;
+            }
+        }).join().unwrap();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(pool.maintain(), 3);
+        assert_eq!(pool.available(), 3);
+
+        let mut obj = pool.get();
+        obj.value = 2;
+        drop(obj);
+        // The next get() recycles the returned item instead of allocating.
+        assert_eq!(pool.get().value, 0);
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[test]
+    fn test_guard_outlives_pool() {
+        let mut pool = ChannelPool::<TestObject>::new();
+        let mut obj = pool.get();
+        drop(pool);
+        obj.value = 3;
+        thread::spawn(move || drop(obj)).join().unwrap();
+    }
+}
@@ -0,0 +1,1141 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem;
+#[cfg(feature = "std")]
+use std::future;
+#[cfg(feature = "std")]
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, PoisonError};
+#[cfg(feature = "std")]
+use std::thread::{self, Scope, ScopedJoinHandle};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+use crate::async_pool::MaintenanceHandle;
+use crate::object_pool::object_pool::{ExhaustedBehavior, LeakReport, ObjectPool, PoolError, Poolable, ReserveError};
+use crate::sync::{AtomicU64, CachePadded, Condvar, Mutex, MutexGuard};
+
+/// State shared by all threads using a ConcurrentObjectPool, protected by its mutex.
+struct State<T> {
+    raw: ObjectPool<T>,
+    /// Threads waiting for an item, each with its ticket, its priority and its own condition variable.
+    /// Ordered by descending priority, and by arrival among equal priorities.
+    waiters: VecDeque<(u64, u8, Arc<Condvar>)>,
+    /// Items handed to waiters that have not woken up yet, keyed by ticket.
+    grants: HashMap<u64, (*mut T, u64)>,
+    next_ticket: u64
+}
+
+// SAFETY: granted items are exclusively owned by the waiter they are granted to.
+unsafe impl<T: Send> Send for State<T> {}
+
+/// Counters updated on every handout and return.
+/// They are only written while the pool lock is held but read without it, so scraping them never touches the lock.
+struct Stats {
+    acquired: AtomicU64,
+    released: AtomicU64,
+    created: AtomicU64,
+    waited: AtomicU64,
+    timed_out: AtomicU64
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            acquired: AtomicU64::new(0),
+            released: AtomicU64::new(0),
+            created: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            timed_out: AtomicU64::new(0)
+        }
+    }
+
+    /// Bump a counter. Release ordering lets `snapshot()` read the counters in an order that keeps them consistent.
+    fn bump(counter: &AtomicU64, count: u64) {
+        counter.fetch_add(count, Ordering::Release);
+    }
+}
+
+/// Point-in-time copy of the statistics of a ConcurrentObjectPool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConcurrentPoolStats {
+    /// Number of items handed out.
+    pub acquired: u64,
+    /// Number of items that came back to the pool.
+    pub released: u64,
+    /// Number of items allocated to satisfy an acquisition.
+    pub created: u64,
+    /// Number of acquisitions that had to wait for an item.
+    pub waited: u64,
+    /// Number of waiting acquisitions that gave up at their deadline.
+    pub timed_out: u64
+}
+
+impl ConcurrentPoolStats {
+    /// Get the number of items handed out and not yet returned at the time of the snapshot.
+    pub fn in_use(&self) -> u64 {
+        self.acquired - self.released
+    }
+}
+
+/// Thread safe variant of AutoReturnObjectPool. The raw pool is protected by a `Mutex`,
+/// so the pool can be shared between threads by reference or inside an `Arc`.
+/// A bounded pool never holds more than `max_size` items; once they are all handed out,
+/// acquiring parks the caller until an item is released.
+/// Waiting threads are served in FIFO order: a released item is handed directly to the longest waiting thread,
+/// and new callers queue behind existing waiters instead of barging in. `get_with_priority` lets a caller
+/// queue ahead of waiters with a lower priority.
+/// `with_exhausted_behavior` lets a bounded pool grow or fail instead of blocking.
+pub struct ConcurrentObjectPool<T> {
+    state: Mutex<State<T>>,
+    stats: CachePadded<Stats>,
+    max_size: usize,
+    exhausted: ExhaustedBehavior
+}
+
+impl<T: Poolable + Clone + Send> Clone for ConcurrentObjectPool<T> {
+    /// Clone the idle items into a new, independent pool with the same maximum size, like `ObjectPool::clone`.
+    fn clone(&self) -> ConcurrentObjectPool<T> {
+        let raw = self.lock().raw.clone();
+        ConcurrentObjectPool::from_raw(raw, self.max_size).with_exhausted_behavior(self.exhausted)
+    }
+}
+
+impl<T: Poolable + Send> Default for ConcurrentObjectPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send> Extend<T> for ConcurrentObjectPool<T> {
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.lock().raw.extend(iter);
+    }
+}
+
+impl<T: Poolable + Send> FromIterator<T> for ConcurrentObjectPool<T> {
+    #[track_caller]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ConcurrentObjectPool<T> {
+        ConcurrentObjectPool::from_raw(iter.into_iter().collect(), usize::MAX)
+    }
+}
+
+impl<T: Poolable + Send> ConcurrentObjectPool<T> {
+    /// Create a new ConcurrentObjectPool.
+    pub fn new() -> ConcurrentObjectPool<T> {
+        ConcurrentObjectPool::bounded(usize::MAX)
+    }
+
+    /// Create a new ConcurrentObjectPool that holds at most `max_size` items.
+    pub fn bounded(max_size: usize) -> ConcurrentObjectPool<T> {
+        ConcurrentObjectPool::from_raw(ObjectPool::new(), max_size)
+    }
+
+    /// Move up to `count` idle items into a new pool with the same maximum size and ExhaustedBehavior,
+    /// like `ObjectPool::split`.
+    pub fn split(&self, count: usize) -> ConcurrentObjectPool<T> {
+        let raw = self.lock().raw.split(count);
+        ConcurrentObjectPool::from_raw(raw, self.max_size).with_exhausted_behavior(self.exhausted)
+    }
+}
+
+impl<T: Send> ConcurrentObjectPool<T> {
+    /// Wrap a raw pool, such as one created with `ObjectPool::with_factory`, holding at most `max_size` items.
+    pub fn from_raw(raw: ObjectPool<T>, max_size: usize) -> ConcurrentObjectPool<T> {
+        ConcurrentObjectPool {
+            state: Mutex::new(State {
+                raw,
+                waiters: VecDeque::new(),
+                grants: HashMap::new(),
+                next_ticket: 0
+            }),
+            stats: CachePadded::new(Stats::new()),
+            max_size,
+            exhausted: ExhaustedBehavior::Block
+        }
+    }
+
+    /// Choose what `get()` does when the pool is at its maximum size and every item is in use.
+    /// The default is `Block`; `Wait` blocks as well. `Grow` makes the maximum size a soft limit.
+    pub fn with_exhausted_behavior(mut self, behavior: ExhaustedBehavior) -> ConcurrentObjectPool<T> {
+        self.exhausted = behavior;
+        self
+    }
+
+    /// Lock the pool state.
+    /// A panic while the lock was held cannot leave the raw pool inconsistent, so poisoning is ignored.
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check whether an item can be handed out without waiting.
+    fn has_room(&self, state: &State<T>) -> bool {
+        state.raw.available() > 0 || state.raw.can_grow(self.growth_limit())
+    }
+
+    /// Get the number of items the pool may grow to on a miss.
+    fn growth_limit(&self) -> usize {
+        match self.exhausted {
+            ExhaustedBehavior::Grow => usize::MAX,
+            _ => self.max_size
+        }
+    }
+
+    /// Hand out an item, allocating it if none is available, and count it.
+    /// Returns None if every idle item was discarded, e.g. for failing validation, and the pool cannot grow.
+    #[track_caller]
+    fn hand_out(&self, state: &mut State<T>) -> Option<(*mut T, u64)> {
+        let created = state.raw.available() == 0;
+        let item = state.raw.get_within(self.growth_limit())?;
+        Stats::bump(&self.stats.acquired, 1);
+        if created {
+            Stats::bump(&self.stats.created, 1);
+        }
+        Some((item, state.raw.generation()))
+    }
+
+    /// Hand items to waiting threads in FIFO order while there is room.
+    /// A waiter only leaves the queue once it has been granted an item.
+    fn grant_waiters(&self, state: &mut State<T>) {
+        while !state.waiters.is_empty() && self.has_room(state) {
+            let Some(grant) = self.hand_out(state) else {
+                break;
+            };
+            let (ticket, _, ready) = state.waiters.pop_front().unwrap();
+            state.grants.insert(ticket, grant);
+            ready.notify_one();
+        }
+    }
+
+    /// Hand out an item, waiting until `deadline` if the pool is bounded and every item is in use.
+    /// The caller queues behind every waiter with at least its priority. Returns None if the deadline passes first.
+    #[track_caller]
+    fn acquire(&self, deadline: Option<Instant>, priority: u8) -> Option<(*mut T, u64)> {
+        let mut state = self.lock();
+        if state.waiters.is_empty() && self.has_room(&state) {
+            if let Some(grant) = self.hand_out(&mut state) {
+                return Some(grant);
+            }
+        }
+        Stats::bump(&self.stats.waited, 1);
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        let ready = Arc::new(Condvar::new());
+        let position = state.waiters.iter().position(|&(_, waiting, _)| waiting < priority).unwrap_or(state.waiters.len());
+        state.waiters.insert(position, (ticket, priority, Arc::clone(&ready)));
+        loop {
+            if let Some(grant) = state.grants.remove(&ticket) {
+                return Some(grant);
+            }
+            state = match deadline {
+                None => ready.wait(state).unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        state.waiters.retain(|(waiting, _, _)| *waiting != ticket);
+                        Stats::bump(&self.stats.timed_out, 1);
+                        return None;
+                    }
+                    ready.wait_timeout(state, deadline - now).unwrap_or_else(PoisonError::into_inner).0
+                }
+            };
+        }
+    }
+
+    /// Read the statistics of the pool without taking its lock.
+    /// `released` and `created` are read before `acquired`, so neither ever exceeds it.
+    pub fn snapshot(&self) -> ConcurrentPoolStats {
+        let released = self.stats.released.load(Ordering::Acquire);
+        let created = self.stats.created.load(Ordering::Acquire);
+        let waited = self.stats.waited.load(Ordering::Acquire);
+        let timed_out = self.stats.timed_out.load(Ordering::Acquire);
+        ConcurrentPoolStats {
+            acquired: self.stats.acquired.load(Ordering::Acquire),
+            released,
+            created,
+            waited,
+            timed_out
+        }
+    }
+
+    /// Get the number of threads waiting for an item.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiters.len()
+    }
+
+    /// Get a ConcurrentPoolBox from the pool.
+    /// On an exhausted bounded pool this waits until an item is available, like `get_blocking`, unless the
+    /// pool's ExhaustedBehavior is `Fail`, in which case it panics.
+    #[track_caller]
+    pub fn get(&self) -> ConcurrentPoolBox<'_, T> {
+        match self.checked_get() {
+            Ok(obj) => obj,
+            Err(_) => panic!("pool is exhausted: all {} items are in use", self.max_size)
+        }
+    }
+
+    /// Get a ConcurrentPoolBox from the pool, applying the pool's ExhaustedBehavior if every item is in use.
+    /// Returns `PoolError::Exhausted` instead of waiting if the behavior is `Fail`.
+    #[track_caller]
+    pub fn checked_get(&self) -> Result<ConcurrentPoolBox<'_, T>, PoolError> {
+        if self.exhausted != ExhaustedBehavior::Fail {
+            return Ok(self.get_blocking());
+        }
+        let mut state = self.lock();
+        if !state.waiters.is_empty() || !self.has_room(&state) {
+            return Err(PoolError::Exhausted);
+        }
+        let (item, generation) = self.hand_out(&mut state).ok_or(PoolError::Exhausted)?;
+        Ok(ConcurrentPoolBox {
+            item,
+            generation,
+            pool: self
+        })
+    }
+
+    /// Get a ConcurrentPoolBox with an idle item, or None if no item is idle or threads are waiting.
+    /// This never allocates and never blocks.
+    #[track_caller]
+    pub fn try_get_idle(&self) -> Option<ConcurrentPoolBox<'_, T>> {
+        let mut state = self.lock();
+        if !state.waiters.is_empty() {
+            return None;
+        }
+        let item = state.raw.try_get_idle()?;
+        Stats::bump(&self.stats.acquired, 1);
+        Some(ConcurrentPoolBox {
+            item,
+            generation: state.raw.generation(),
+            pool: self
+        })
+    }
+
+    /// Get `n` ConcurrentPoolBoxes at once, or None without taking any if the pool cannot hand out all of them
+    /// right now. Items are taken under a single lock and this never waits, so callers that need several items
+    /// cannot deadlock by each holding part of a set while waiting for the rest.
+    #[track_caller]
+    pub fn try_get_n(&self, n: usize) -> Option<Vec<ConcurrentPoolBox<'_, T>>> {
+        let mut state = self.lock();
+        if !state.waiters.is_empty() {
+            return None;
+        }
+        let generation = state.raw.generation();
+        let idle = state.raw.available();
+        let items = state.raw.get_n_within(n, self.growth_limit())?;
+        Stats::bump(&self.stats.acquired, n as u64);
+        Stats::bump(&self.stats.created, n.saturating_sub(idle) as u64);
+        drop(state);
+        Some(items.into_iter().map(|item| ConcurrentPoolBox {
+            item,
+            generation,
+            pool: self
+        }).collect())
+    }
+
+    /// Get a ConcurrentPoolBox from the pool like `get`, and run `init` on its item before returning it.
+    /// If `init` panics, the item goes back to the pool.
+    #[track_caller]
+    pub fn get_with<F: FnOnce(&mut T)>(&self, init: F) -> ConcurrentPoolBox<'_, T> {
+        let mut obj = self.get();
+        init(&mut obj);
+        obj
+    }
+
+    /// Get a ConcurrentPoolBox from the pool, waiting as long as it takes for an item on a bounded pool.
+    #[track_caller]
+    pub fn get_blocking(&self) -> ConcurrentPoolBox<'_, T> {
+        let (item, generation) = self.acquire(None, 0).unwrap();
+        ConcurrentPoolBox {
+            item,
+            generation,
+            pool: self
+        }
+    }
+
+    /// Get a ConcurrentPoolBox from the pool like `get_blocking`, waiting with `priority`.
+    /// A released item goes to the waiting thread with the highest priority, and to the longest waiting one
+    /// among equals. Every other method waits with priority 0, so background work can leave it at that while
+    /// latency sensitive callers use a higher one.
+    #[track_caller]
+    pub fn get_with_priority(&self, priority: u8) -> ConcurrentPoolBox<'_, T> {
+        let (item, generation) = self.acquire(None, priority).unwrap();
+        ConcurrentPoolBox {
+            item,
+            generation,
+            pool: self
+        }
+    }
+
+    /// Get a ConcurrentPoolBox from the pool, waiting at most `timeout` for an item on a bounded pool.
+    #[track_caller]
+    pub fn get_timeout(&self, timeout: Duration) -> Option<ConcurrentPoolBox<'_, T>> {
+        let (item, generation) = self.acquire(Some(Instant::now() + timeout), 0)?;
+        Some(ConcurrentPoolBox {
+            item,
+            generation,
+            pool: self
+        })
+    }
+
+    /// Get an OwnedPoolBox from the pool. The guard keeps the pool alive, so it is `'static`
+    /// and can be moved into spawned threads or tasks.
+    /// On a bounded pool this waits until an item is available.
+    #[track_caller]
+    pub fn get_owned(self: &Arc<Self>) -> OwnedPoolBox<T> {
+        let (item, generation) = self.acquire(None, 0).unwrap();
+        OwnedPoolBox {
+            item,
+            generation,
+            pool: Arc::clone(self)
+        }
+    }
+
+    /// Lend a pooled object to a new thread in `scope` and run `f` on it there.
+    /// The object is reset and back in the pool when the thread finishes, so always before the scope ends.
+    /// On a bounded pool this waits until an item is available. Requires the `std` feature.
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn lend_scoped<'scope, 'env, F, R>(&'env self, scope: &'scope Scope<'scope, 'env>, f: F) -> ScopedJoinHandle<'scope, R>
+    where
+        F: FnOnce(&mut T) -> R + Send + 'scope,
+        R: Send + 'scope
+    {
+        let mut obj = self.get();
+        scope.spawn(move || f(&mut obj))
+    }
+
+    /// Run `f` on every item in parallel, lending each worker thread one pooled object as scratch space.
+    /// The items are split into one batch per available core; every object is back in the pool when this returns.
+    /// On a bounded pool smaller than the number of workers, the remaining batches start as objects are released.
+    /// Requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn par_process<I, F>(&self, items: I, f: F)
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(&mut T, I::Item) + Sync
+    {
+        let mut items: Vec<I::Item> = items.into_iter().collect();
+        let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get).min(items.len());
+        if workers == 0 {
+            return;
+        }
+        let batch_size = items.len().div_ceil(workers);
+        let f = &f;
+        thread::scope(|scope| {
+            while !items.is_empty() {
+                let batch = items.split_off(items.len().saturating_sub(batch_size));
+                self.lend_scoped(scope, move |obj| {
+                    for item in batch {
+                        f(obj, item);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Release an item handed out during `generation` and hand it on to a waiting thread.
+    pub(crate) fn release(&self, item: *mut T, generation: u64) {
+        let mut state = self.lock();
+        unsafe {
+            state.raw.release_from(item, generation);
+        }
+        Stats::bump(&self.stats.released, 1);
+        self.grant_waiters(&mut state);
+    }
+
+    /// Take up to `count` idle items under a single lock, or create one if none is idle and the pool may grow.
+    /// Returns no items if threads are waiting, so they are served first, or if the pool is exhausted;
+    /// `take_one` then waits in line. The items stay handed out until they are restored.
+    #[track_caller]
+    pub(crate) fn take_batch(&self, count: usize) -> Vec<(*mut T, u64)> {
+        let mut state = self.lock();
+        if !state.waiters.is_empty() {
+            return Vec::new();
+        }
+        let limit = self.growth_limit();
+        let raw = &mut state.raw;
+        let generation = raw.generation();
+        let mut items: Vec<_> = (0..count).map_while(|_| raw.try_get_idle()).map(|item| (item, generation)).collect();
+        let mut created = 0;
+        if items.is_empty() && count > 0 {
+            if let Some(item) = raw.get_within(limit) {
+                items.push((item, generation));
+                created = 1;
+            }
+        }
+        // `acquired` is bumped before `created`, like in `hand_out`.
+        Stats::bump(&self.stats.acquired, items.len() as u64);
+        Stats::bump(&self.stats.created, created);
+        items
+    }
+
+    /// Take a single item like `get`, waiting in line on an exhausted bounded pool.
+    /// The item stays handed out until it is restored.
+    #[track_caller]
+    pub(crate) fn take_one(&self) -> (*mut T, u64) {
+        let obj = self.get();
+        let parts = (obj.item, obj.generation);
+        mem::forget(obj);
+        parts
+    }
+
+    /// Make already reset items taken with `take_batch` available again under a single lock.
+    pub(crate) fn restore(&self, items: impl IntoIterator<Item = (*mut T, u64)>) {
+        let mut state = self.lock();
+        let mut restored = 0;
+        for (item, generation) in items {
+            unsafe {
+                state.raw.restore_from(item, generation);
+            }
+            restored += 1;
+        }
+        Stats::bump(&self.stats.released, restored);
+        self.grant_waiters(&mut state);
+    }
+
+    /// Get the reset that releasing runs when resetting is all it does, like `ObjectPool::plain_reset`.
+    pub(crate) fn plain_reset(&self) -> Option<fn(&mut T)> {
+        self.lock().raw.plain_reset()
+    }
+
+    /// Remove an item taken with `take_batch` from the pool and return it by value.
+    pub(crate) fn take(&self, item: *mut T, generation: u64) -> T {
+        let mut state = self.lock();
+        let value = unsafe {
+            state.raw.take_from(item, generation)
+        };
+        Stats::bump(&self.stats.released, 1);
+        self.grant_waiters(&mut state);
+        value
+    }
+
+    /// Reserve a number of items in the pool.
+    #[track_caller]
+    pub fn reserve(&self, count: usize) {
+        let mut state = self.lock();
+        state.raw.reserve(count);
+        self.grant_waiters(&mut state);
+    }
+
+    /// Reserve a number of items, returning an error if memory runs out, like `ObjectPool::try_reserve`.
+    /// Items reserved before a failure are handed to waiting threads all the same.
+    #[track_caller]
+    pub fn try_reserve(&self, count: usize) -> Result<(), ReserveError> {
+        let mut state = self.lock();
+        let reserved = state.raw.try_reserve(count);
+        self.grant_waiters(&mut state);
+        reserved
+    }
+
+    /// Take ownership of an object constructed outside the pool and make it available, like `ObjectPool::adopt`.
+    #[allow(clippy::boxed_local)]
+    #[track_caller]
+    pub fn adopt(&self, item: Box<T>) {
+        self.adopt_value(*item);
+    }
+
+    /// Take ownership of an object constructed outside the pool, like `ObjectPool::adopt_value`.
+    #[track_caller]
+    pub fn adopt_value(&self, value: T) {
+        let mut state = self.lock();
+        state.raw.adopt_value(value);
+        self.grant_waiters(&mut state);
+    }
+
+    /// Set the sink that receives a LeakReport if the pool is dropped while items are in use.
+    pub fn set_leak_sink<F: Fn(&LeakReport) + Send + 'static>(&self, sink: F) {
+        self.lock().raw.set_leak_sink(sink);
+    }
+
+    /// Clear the pool completely.
+    /// Outstanding guards keep their item and free it when dropped.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.raw.clear();
+        self.grant_waiters(&mut state);
+    }
+
+    /// Clear the pool completely, but only if no guards are outstanding.
+    pub fn try_clear(&self) -> Result<(), PoolError> {
+        self.lock().raw.try_clear()
+    }
+
+    /// Move every idle item of `other` into this pool, like `ObjectPool::merge`, and hand them to waiting threads.
+    /// The pools are locked one after the other, so concurrent merges in both directions cannot deadlock.
+    pub fn merge(&self, other: &ConcurrentObjectPool<T>) -> usize {
+        let items = {
+            let raw = &mut other.lock().raw;
+            let available = raw.available();
+            raw.detach_idle(available)
+        };
+        let count = items.len();
+        let mut state = self.lock();
+        state.raw.adopt_idle(items);
+        self.grant_waiters(&mut state);
+        count
+    }
+
+    /// Free every available item, leaving outstanding guards untouched.
+    /// Returns the number of items freed.
+    pub fn clear_idle(&self) -> usize {
+        let mut state = self.lock();
+        let count = state.raw.clear_idle();
+        self.grant_waiters(&mut state);
+        count
+    }
+
+    /// Stop creating items, like `ObjectPool::freeze`. Once every item is in use, `get()` waits for a release
+    /// instead of allocating, or fails with `PoolError::Exhausted` if the ExhaustedBehavior is `Fail`.
+    pub fn freeze(&self) {
+        self.lock().raw.freeze();
+    }
+
+    /// Let the pool create items again, like `ObjectPool::unfreeze`.
+    pub fn unfreeze(&self) {
+        let mut state = self.lock();
+        state.raw.unfreeze();
+        self.grant_waiters(&mut state);
+    }
+
+    /// Check whether the pool is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.lock().raw.is_frozen()
+    }
+
+    /// Free every idle item for which `keep` returns false, like `ObjectPool::retain`.
+    /// The pool stays locked while `keep` runs.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, keep: F) -> usize {
+        let mut state = self.lock();
+        let count = state.raw.retain(keep);
+        self.grant_waiters(&mut state);
+        count
+    }
+
+    /// Free idle items until at most `idle` are left, like `ObjectPool::shrink_to`.
+    pub fn shrink_to(&self, idle: usize) -> usize {
+        let mut state = self.lock();
+        let count = state.raw.shrink_to(idle);
+        self.grant_waiters(&mut state);
+        count
+    }
+
+    /// Free every idle item and release the memory the pool no longer needs, like `ObjectPool::shrink_to_fit`.
+    pub fn shrink_to_fit(&self) -> usize {
+        self.shrink_to(0)
+    }
+
+    /// Retire expired and timed out idle items and refill up to `min_idle`, like `ObjectPool::maintain`.
+    /// Refilling never grows the pool past its maximum size.
+    #[track_caller]
+    pub fn maintain(&self) -> usize {
+        let mut state = self.lock();
+        let count = state.raw.retire_expired();
+        state.raw.fill_idle(self.max_size);
+        self.grant_waiters(&mut state);
+        count
+    }
+
+    /// Run `maintain()` every `interval` on a background thread until the returned handle is stopped or dropped,
+    /// or the pool itself is dropped. This keeps eviction, refill and health checks going without an async runtime.
+    #[cfg(feature = "std")]
+    pub fn start_maintenance(self: &Arc<Self>, interval: Duration) -> MaintenanceHandle
+    where
+        T: 'static
+    {
+        let (task, handle) = MaintenanceHandle::task(self, interval, |pool: Arc<Self>| {
+            pool.maintain();
+            future::ready(())
+        });
+        handle.spawn(task)
+    }
+
+    /// Get the maximum number of items the pool holds, which is `usize::MAX` for an unbounded pool.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().raw.len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.lock().raw.is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.lock().raw.available()
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.lock().raw.in_use()
+    }
+
+    /// Get the largest number of items that were handed out at the same time.
+    pub fn high_water_mark(&self) -> usize {
+        self.lock().raw.high_water_mark()
+    }
+}
+
+/// Guard handed out by a ConcurrentObjectPool. It can be sent to other threads as long as the pool outlives it.
+pub struct ConcurrentPoolBox<'a, T: Send> {
+    item: *mut T,
+    generation: u64,
+    pool: &'a ConcurrentObjectPool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item and releases it through the pool's mutex.
+unsafe impl<T: Send> Send for ConcurrentPoolBox<'_, T> {}
+// SAFETY: shared access to the guard only hands out shared references to the item.
+unsafe impl<T: Send + Sync> Sync for ConcurrentPoolBox<'_, T> {}
+
+impl<T: Send> ConcurrentPoolBox<'_, T> {
+    /// Get a reference to the item.
+    pub fn ref_item(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+
+    /// Get a mutable reference to the item.
+    pub fn ref_mut_item(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+
+    /// Release the item back to the pool now instead of at the end of the scope.
+    pub fn release(self) {
+        drop(self);
+    }
+}
+
+impl<T: Send> Deref for ConcurrentPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.ref_item()
+    }
+}
+
+impl<T: Send> DerefMut for ConcurrentPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ref_mut_item()
+    }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for ConcurrentPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Send> Drop for ConcurrentPoolBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.item, self.generation);
+    }
+}
+
+/// Guard handed out by `ConcurrentObjectPool::get_owned`. It holds an `Arc` to the pool instead of borrowing it.
+pub struct OwnedPoolBox<T: Send> {
+    item: *mut T,
+    generation: u64,
+    pool: Arc<ConcurrentObjectPool<T>>
+}
+
+// SAFETY: the guard has exclusive access to its item and releases it through the pool's mutex.
+unsafe impl<T: Send> Send for OwnedPoolBox<T> {}
+unsafe impl<T: Send + Sync> Sync for OwnedPoolBox<T> {}
+
+impl<T: Send> OwnedPoolBox<T> {
+    /// Get the pool this guard belongs to.
+    pub fn pool(&self) -> &Arc<ConcurrentObjectPool<T>> {
+        &self.pool
+    }
+
+    /// Release the item back to the pool now instead of at the end of the scope.
+    pub fn release(self) {
+        drop(self);
+    }
+}
+
+impl<T: Send> Deref for OwnedPoolBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+}
+
+impl<T: Send> DerefMut for OwnedPoolBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for OwnedPoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Send> Drop for OwnedPoolBox<T> {
+    fn drop(&mut self) {
+        self.pool.release(self.item, self.generation);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::object_pool::object_pool::Validate;
+    use crate::test_support::TestObject;
+
+    impl Validate for TestObject {
+        fn is_valid(&self) -> bool {
+            self.value >= 0
+        }
+    }
+
+    #[test]
+    fn test_concurrent_pool() {
+        let pool = Arc::new(ConcurrentObjectPool::<TestObject>::new());
+        pool.reserve(4);
+        let workers: Vec<_> = (0..4).map(|worker| {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let mut obj = pool.get();
+                    assert_eq!(obj.value, 0);
+                    obj.value = worker;
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(pool.in_use(), 0);
+        assert_eq!(pool.available(), pool.len());
+        assert!(pool.len() <= 4);
+    }
+
+    #[test]
+    fn test_owned_guard_moved_into_thread() {
+        let pool = Arc::new(ConcurrentObjectPool::<TestObject>::new());
+        let mut obj = pool.get_owned();
+        obj.value = 4;
+        drop(pool);
+        let worker = thread::spawn(move || {
+            assert_eq!(obj.value, 4);
+            let pool = Arc::clone(obj.pool());
+            obj.release();
+            pool
+        });
+        let pool = worker.join().unwrap();
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_bounded_pool_blocks() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(2);
+        let a = pool.get();
+        let b = pool.get();
+        assert!(pool.get_timeout(Duration::from_millis(10)).is_none());
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.get_blocking().value);
+            thread::sleep(Duration::from_millis(10));
+            drop(a);
+            assert_eq!(waiter.join().unwrap(), 0);
+        });
+        let c = pool.get_timeout(Duration::from_secs(1));
+        assert!(c.is_some());
+        assert_eq!(pool.len(), 2);
+        drop(b);
+        drop(c);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_waiters_are_served_in_order() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(1);
+        let held = pool.get();
+        let order = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for waiter in 0..4 {
+                let (pool, order) = (&pool, &order);
+                scope.spawn(move || {
+                    let obj = pool.get();
+                    order.lock().unwrap().push(waiter);
+                    drop(obj);
+                });
+                // Make sure the waiters queue up in a known order.
+                while pool.waiting() <= waiter {
+                    thread::yield_now();
+                }
+            }
+            drop(held);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(pool.waiting(), 0);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_guard_sent_to_scoped_thread() {
+        let pool = ConcurrentObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        thread::scope(|scope| {
+            scope.spawn(|| obj.value = 5);
+        });
+        assert_eq!(obj.value, 5);
+        thread::scope(|scope| {
+            scope.spawn(move || obj.release());
+        });
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_par_process() {
+        use std::sync::atomic::AtomicUsize;
+
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(2);
+        let total = AtomicUsize::new(0);
+        pool.par_process(1..=100, |obj, item| {
+            // Objects are scratch space for one worker at a time.
+            obj.value += 1;
+            total.fetch_add(item, Ordering::Relaxed);
+        });
+        assert_eq!(total.load(Ordering::Relaxed), 5050);
+        assert_eq!(pool.in_use(), 0);
+        assert!(pool.len() <= 2);
+        assert_eq!(pool.get().value, 0);
+        pool.par_process(Vec::<usize>::new(), |_, _| unreachable!());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_lend_scoped() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(1);
+        let values: Vec<_> = thread::scope(|scope| {
+            let first = pool.lend_scoped(scope, |obj| {
+                obj.value = 3;
+                obj.value
+            });
+            // The single object comes back once the first thread is done with it.
+            let second = pool.lend_scoped(scope, |obj| obj.value);
+            vec![first.join().unwrap(), second.join().unwrap()]
+        });
+        assert_eq!(values, vec![3, 0]);
+        assert_eq!(pool.in_use(), 0);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(1);
+        let first = pool.get();
+        assert!(pool.get_timeout(Duration::from_millis(5)).is_none());
+        drop(first);
+        drop(pool.get());
+        assert_eq!(pool.snapshot(), ConcurrentPoolStats {
+            acquired: 2,
+            released: 2,
+            created: 1,
+            waited: 1,
+            timed_out: 1
+        });
+
+        let pool = ConcurrentObjectPool::<TestObject>::new();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    let stats = pool.snapshot();
+                    assert!(stats.released <= stats.acquired && stats.created <= stats.acquired);
+                }
+            });
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..250 {
+                        drop(pool.get());
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.snapshot().in_use(), 0);
+        assert_eq!(pool.snapshot().acquired, 1000);
+    }
+
+    #[test]
+    fn test_exhausted_behavior() {
+        let failing = ConcurrentObjectPool::<TestObject>::bounded(1).with_exhausted_behavior(ExhaustedBehavior::Fail);
+        let held = failing.get();
+        assert_eq!(failing.checked_get().err(), Some(PoolError::Exhausted));
+        drop(held);
+        assert!(failing.checked_get().is_ok());
+
+        let growing = ConcurrentObjectPool::<TestObject>::bounded(1).with_exhausted_behavior(ExhaustedBehavior::Grow);
+        let held = (growing.get(), growing.get());
+        assert_eq!(growing.len(), 2);
+        drop(held);
+    }
+
+    #[test]
+    fn test_maintain_refills_within_max_size() {
+        let pool = ConcurrentObjectPool::from_raw(ObjectPool::<TestObject>::new().with_min_idle(4), 2);
+        pool.maintain();
+        assert_eq!((pool.len(), pool.available()), (2, 2));
+        assert_eq!(pool.snapshot().created, 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_start_maintenance() {
+        let pool = Arc::new(ConcurrentObjectPool::from_raw(ObjectPool::<TestObject>::new().with_min_idle(2), 4));
+        let handle = pool.start_maintenance(Duration::from_millis(1));
+        while pool.available() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.stop();
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_priority_waiters_are_served_first() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(1);
+        let held = pool.get();
+        let order = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for (waiter, priority) in [(0, 0), (1, 0), (2, 5), (3, 1)].into_iter() {
+                let (pool, order) = (&pool, &order);
+                scope.spawn(move || {
+                    let obj = pool.get_with_priority(priority);
+                    order.lock().unwrap().push(waiter);
+                    drop(obj);
+                });
+                while pool.waiting() <= waiter {
+                    thread::yield_now();
+                }
+            }
+            drop(held);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_try_get_n_is_all_or_nothing() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(3);
+        let mut pair = pool.try_get_n(2).unwrap();
+        pair[0].value = 1;
+        assert!(pool.try_get_n(2).is_none());
+        // The item created by the failed attempt went back to the pool.
+        assert_eq!((pool.in_use(), pool.available()), (2, 1));
+        drop(pair);
+        let all = pool.try_get_n(3).unwrap();
+        assert_eq!(all.len(), 3);
+        assert!(all.iter().all(|obj| obj.value == 0));
+        assert!(pool.try_get_n(1).is_none());
+        drop(all);
+        assert_eq!(pool.available(), 3);
+        assert_eq!(pool.snapshot().acquired, 5);
+    }
+
+    #[test]
+    fn test_failed_try_get_n_runs_no_hooks() {
+        use std::sync::atomic::AtomicUsize;
+
+        let events = Arc::new(AtomicUsize::new(0));
+        let (acquired, released) = (Arc::clone(&events), Arc::clone(&events));
+        let raw = ObjectPool::<TestObject>::new()
+            .with_on_acquire(move |_| {
+                acquired.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_on_release(move |_| {
+                released.fetch_add(1, Ordering::Relaxed);
+            });
+        let pool = ConcurrentObjectPool::from_raw(raw, 3);
+        let held = pool.get();
+        assert_eq!(events.swap(0, Ordering::Relaxed), 1);
+        assert!(pool.try_get_n(3).is_none());
+        // The two items taken by the failed attempt were never handed out.
+        assert_eq!(events.load(Ordering::Relaxed), 0);
+        assert_eq!((pool.in_use(), pool.available(), pool.high_water_mark()), (1, 2, 1));
+        drop(held);
+        assert_eq!(pool.try_get_n(3).unwrap().len(), 3);
+        assert_eq!(pool.high_water_mark(), 3);
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(4);
+        pool.reserve(4);
+        let shards: Vec<_> = (0..2).map(|_| pool.split(2)).collect();
+        assert_eq!((pool.len(), shards[0].available(), shards[0].max_size()), (0, 2, 4));
+        thread::scope(|scope| {
+            for shard in &shards {
+                scope.spawn(move || shard.get().value = 1);
+            }
+        });
+        // The emptied pool no longer creates items, so the waiter is served by the merge.
+        pool.freeze();
+        let waiter = thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.get_blocking().value);
+            while pool.waiting() == 0 {
+                thread::yield_now();
+            }
+            for shard in &shards {
+                pool.merge(shard);
+            }
+            waiter.join().unwrap()
+        });
+        assert_eq!(waiter, 0);
+        assert_eq!((pool.len(), pool.available()), (4, 4));
+    }
+
+    #[test]
+    fn test_waiter_stays_queued_when_released_item_is_discarded() {
+        let pool = ConcurrentObjectPool::from_raw(ObjectPool::<TestObject>::new().without_reset().with_validation(), 1);
+        let mut obj = pool.get();
+        pool.freeze();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.get_timeout(Duration::from_secs(10)).map(|obj| obj.value));
+            while pool.waiting() == 0 {
+                thread::yield_now();
+            }
+            // The released item fails validation and the frozen pool cannot replace it, so the waiter keeps waiting.
+            obj.value = -1;
+            drop(obj);
+            assert_eq!((pool.waiting(), pool.len()), (1, 0));
+            pool.unfreeze();
+            assert_eq!(waiter.join().unwrap(), Some(0));
+        });
+    }
+
+    #[test]
+    fn test_take_batch_respects_max_size() {
+        let pool = ConcurrentObjectPool::<TestObject>::bounded(2);
+        let batch = pool.take_batch(4);
+        assert_eq!((batch.len(), pool.in_use()), (1, 1));
+        let held = pool.get();
+        // The pool is at its maximum size, so a refill takes nothing instead of growing past it.
+        assert!(pool.take_batch(4).is_empty());
+        assert_eq!(pool.len(), 2);
+        pool.restore(batch);
+        drop(held);
+        let all = pool.take_batch(4);
+        assert_eq!(all.len(), 2);
+        pool.restore(all);
+    }
+}
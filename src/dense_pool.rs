@@ -0,0 +1,197 @@
+use crate::handle_pool::PoolHandle;
+use crate::object_pool::object_pool::{PoolError, Poolable};
+
+/// Slot of a DensePool, which a handle refers to. It follows its item as the item moves within the storage.
+struct Slot {
+    /// Position of the item in the dense storage.
+    position: u32,
+    generation: u32,
+    in_use: bool
+}
+
+/// Handle based pool that keeps all of its items in one contiguous vector, with the items in use packed at
+/// the front. Iterating over the live items scans a single slice, which suits workloads that touch every live
+/// item each frame. Releasing an item swaps it with the last live item, so handles go through a slot table.
+pub struct DensePool<T: Poolable> {
+    /// Items in use, followed by the available items.
+    values: Vec<T>,
+    /// Slot of the item at each position of `values`.
+    owners: Vec<u32>,
+    slots: Vec<Slot>,
+    live: usize
+}
+
+impl<T: Poolable> Default for DensePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> DensePool<T> {
+    /// Create a new DensePool.
+    pub fn new() -> DensePool<T> {
+        DensePool {
+            values: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            live: 0
+        }
+    }
+
+    /// Allocate a new item at the end of the storage.
+    fn allocate(&mut self) {
+        let index = u32::try_from(self.slots.len()).expect("DensePool cannot hold more than u32::MAX items");
+        self.values.push(T::new());
+        self.owners.push(index);
+        self.slots.push(Slot {
+            position: index,
+            generation: 0,
+            in_use: false
+        });
+    }
+
+    /// Reserve a number of items in the pool.
+    pub fn reserve(&mut self, count: usize) {
+        self.values.reserve(count);
+        self.owners.reserve(count);
+        self.slots.reserve(count);
+        for _ in 0..count {
+            self.allocate();
+        }
+    }
+
+    /// Get an item from the pool. It is placed right after the items already in use.
+    pub fn get(&mut self) -> PoolHandle {
+        if self.live == self.values.len() {
+            self.allocate();
+        }
+        let index = self.owners[self.live];
+        self.live += 1;
+        let slot = &mut self.slots[index as usize];
+        slot.in_use = true;
+        PoolHandle::new(index, slot.generation)
+    }
+
+    /// Get the slot of a handle if the handle is still live.
+    fn slot(&self, handle: PoolHandle) -> Option<&Slot> {
+        self.slots.get(handle.index())
+            .filter(|slot| slot.in_use && slot.generation == handle.generation())
+    }
+
+    /// Release an item back to the pool, moving the last item in use into its place.
+    /// Fails if the handle was already released.
+    pub fn release(&mut self, handle: PoolHandle) -> Result<(), PoolError> {
+        let position = self.slot(handle).ok_or(PoolError::StaleHandle)?.position as usize;
+        self.values[position].reset();
+        let last = self.live - 1;
+        self.values.swap(position, last);
+        self.owners.swap(position, last);
+        self.slots[self.owners[position] as usize].position = position as u32;
+        let slot = &mut self.slots[handle.index()];
+        slot.position = last as u32;
+        slot.in_use = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.live = last;
+        Ok(())
+    }
+
+    /// Check whether a handle still refers to an item that is handed out.
+    pub fn contains(&self, handle: PoolHandle) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    /// Get a reference to the item of a handle, or None if the handle is stale.
+    pub fn get_ref(&self, handle: PoolHandle) -> Option<&T> {
+        let position = self.slot(handle)?.position;
+        Some(&self.values[position as usize])
+    }
+
+    /// Get a mutable reference to the item of a handle, or None if the handle is stale.
+    pub fn get_mut(&mut self, handle: PoolHandle) -> Option<&mut T> {
+        let position = self.slot(handle)?.position;
+        Some(&mut self.values[position as usize])
+    }
+
+    /// Get the items in use as one contiguous slice. Their order changes when items are released.
+    pub fn as_slice(&self) -> &[T] {
+        &self.values[..self.live]
+    }
+
+    /// Get the items in use as one contiguous mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.values[..self.live]
+    }
+
+    /// Iterate over the items in use.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+
+    /// Iterate mutably over the items in use.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.as_mut_slice().iter_mut()
+    }
+
+    /// Iterate over the handles of the items in use, in the order of `as_slice`.
+    pub fn handles(&self) -> impl Iterator<Item = PoolHandle> + '_ {
+        self.owners[..self.live].iter().map(|&index| PoolHandle::new(index, self.slots[index as usize].generation))
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.values.len() - self.live
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_dense_pool() {
+        let mut pool = DensePool::<TestObject>::new();
+        pool.reserve(2);
+        let handles: Vec<_> = (1..=4).map(|value| {
+            let handle = pool.get();
+            pool.get_mut(handle).unwrap().value = value;
+            handle
+        }).collect();
+        assert_eq!((pool.len(), pool.in_use()), (4, 4));
+
+        // Releasing moves the last live item into the gap, so the live items stay contiguous.
+        assert_eq!(pool.release(handles[1]), Ok(()));
+        assert_eq!(pool.release(handles[1]), Err(PoolError::StaleHandle));
+        let values: Vec<_> = pool.iter().map(|obj| obj.value).collect();
+        assert_eq!(values, vec![1, 4, 3]);
+        assert_eq!(pool.get_ref(handles[3]).unwrap().value, 4);
+        assert!(pool.handles().eq([handles[0], handles[3], handles[2]]));
+
+        for obj in pool.iter_mut() {
+            obj.value *= 10;
+        }
+        assert_eq!(pool.get_ref(handles[2]).unwrap().value, 30);
+
+        // The released item is reused, reset, and stale handles stay stale.
+        let reused = pool.get();
+        assert_eq!(reused.index(), handles[1].index());
+        assert!(!pool.contains(handles[1]));
+        assert_eq!(pool.as_slice().last().unwrap().value, 0);
+        assert_eq!((pool.available(), pool.in_use()), (0, 4));
+    }
+}
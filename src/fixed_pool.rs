@@ -0,0 +1,195 @@
+use std::array;
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::object_pool::object_pool::{PoolError, Poolable};
+
+/// Pool of exactly `N` items stored inline in the pool itself, for targets without a heap.
+///
+/// All items are created up front and the free list is an array of slot indices next to them, so the pool
+/// never allocates: kept on the stack or inside another value, it needs no allocator at all.
+/// The pool is not `Sync` and `new()` is not `const`, so it cannot be placed in a `static`;
+/// use a `thread_local!` instead, or `static_pool!` for a pool shared across threads.
+/// Getting an item when all `N` are handed out fails with `PoolError::Exhausted` instead of growing.
+/// The most recently released slot is reused first.
+pub struct FixedPool<T: Poolable, const N: usize> {
+    slots: [UnsafeCell<T>; N],
+    /// Index of the slot that follows each free slot on the free list, `N` for the end of the list.
+    next: [Cell<usize>; N],
+    /// First free slot, or `N` if every slot is handed out.
+    head: Cell<usize>,
+    in_use: Cell<usize>
+}
+
+impl<T: Poolable, const N: usize> FixedPool<T, N> {
+    /// Create a FixedPool and all of its `N` items.
+    pub fn new() -> FixedPool<T, N> {
+        FixedPool {
+            slots: array::from_fn(|_| UnsafeCell::new(T::new())),
+            next: array::from_fn(|index| Cell::new(index + 1)),
+            head: Cell::new(0),
+            in_use: Cell::new(0)
+        }
+    }
+
+    /// Get an item from the pool.
+    /// Returns `PoolError::Exhausted` if all `N` items are handed out.
+    pub fn try_get(&self) -> Result<FixedPoolBox<'_, T, N>, PoolError> {
+        let index = self.head.get();
+        if index == N {
+            return Err(PoolError::Exhausted);
+        }
+        self.head.set(self.next[index].get());
+        self.in_use.set(self.in_use.get() + 1);
+        Ok(FixedPoolBox {
+            index,
+            pool: self
+        })
+    }
+
+    /// Reset the item of a slot and push the slot onto the free list.
+    /// If `reset()` panics, the item is replaced by a new one before the slot is pushed, and the panic continues
+    /// once the old item is dropped.
+    fn release(&self, index: usize) {
+        let value = self.slots[index].get();
+        // SAFETY: the releasing guard had exclusive access to the slot.
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*value).reset() }));
+        let broken = reset.is_err().then(|| unsafe { mem::replace(&mut *value, T::new()) });
+        self.next[index].set(self.head.get());
+        self.head.set(index);
+        self.in_use.set(self.in_use.get() - 1);
+        if let Err(payload) = reset {
+            drop(broken);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the number of items in the pool, which is always `N`.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        N - self.in_use.get()
+    }
+
+    /// Get the number of items handed out.
+    pub fn in_use(&self) -> usize {
+        self.in_use.get()
+    }
+}
+
+impl<T: Poolable, const N: usize> Default for FixedPool<T, N> {
+    fn default() -> FixedPool<T, N> {
+        FixedPool::new()
+    }
+}
+
+/// Guard handed out by a FixedPool. It releases its item when dropped.
+pub struct FixedPoolBox<'a, T: Poolable, const N: usize> {
+    index: usize,
+    pool: &'a FixedPool<T, N>
+}
+
+impl<T: Poolable, const N: usize> FixedPoolBox<'_, T, N> {
+    /// Get the slot index of the item.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T: Poolable, const N: usize> Deref for FixedPoolBox<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.pool.slots[self.index].get()
+        }
+    }
+}
+
+impl<T: Poolable, const N: usize> DerefMut for FixedPoolBox<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.pool.slots[self.index].get()
+        }
+    }
+}
+
+impl<T: Poolable + fmt::Debug, const N: usize> fmt::Debug for FixedPoolBox<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable, const N: usize> Drop for FixedPoolBox<'_, T, N> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_fixed_pool() {
+        let pool = FixedPool::<TestObject, 3>::new();
+        let mut first = pool.try_get().unwrap();
+        let second = pool.try_get().unwrap();
+        let third = pool.try_get().unwrap();
+        assert_eq!((first.index(), second.index(), third.index()), (0, 1, 2));
+        assert_eq!(pool.try_get().err(), Some(PoolError::Exhausted));
+        first.value = 1;
+        drop(first);
+        drop(third);
+        assert_eq!((pool.available(), pool.in_use()), (2, 1));
+        // The most recently released slot is reused first, and items come back reset.
+        let reused = pool.try_get().unwrap();
+        assert_eq!(reused.index(), 2);
+        assert_eq!(pool.try_get().unwrap().value, 0);
+        drop((reused, second));
+        assert_eq!(pool.available(), pool.capacity());
+        assert!(FixedPool::<TestObject, 0>::new().try_get().is_err());
+    }
+
+    struct PanickyObject {
+        pub fail: bool
+    }
+
+    impl Poolable for PanickyObject {
+        fn new() -> PanickyObject {
+            PanickyObject {
+                fail: false
+            }
+        }
+
+        fn reset(&mut self) {
+            assert!(!self.fail, "reset failed");
+        }
+    }
+
+    #[test]
+    fn test_panicking_reset_keeps_slot() {
+        let pool = FixedPool::<PanickyObject, 1>::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.try_get().unwrap().fail = true;
+        }));
+        assert!(result.is_err());
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.try_get().unwrap().fail);
+    }
+
+    #[test]
+    fn test_fixed_pool_is_inline() {
+        // Items and free list live in the pool itself, with no pointer to heap memory.
+        let slots = mem::size_of::<[TestObject; 16]>() + mem::size_of::<[usize; 16]>();
+        assert_eq!(mem::size_of::<FixedPool<TestObject, 16>>(), slots + 2 * mem::size_of::<usize>());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::object_pool::object_pool::{PoolError, Poolable};
+
+/// Handle to an item of a HandlePool: a slot index plus the generation of the slot when the item was handed out.
+/// Handles are plain data, so they can be copied and stored anywhere; releasing the item invalidates every copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PoolHandle {
+    index: u32,
+    generation: u32
+}
+
+impl PoolHandle {
+    /// Create a handle to slot `index` in `generation`.
+    pub(crate) fn new(index: u32, generation: u32) -> PoolHandle {
+        PoolHandle {
+            index,
+            generation
+        }
+    }
+
+    /// Get the slot index of the handle.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    /// Get the generation of the slot when the item was handed out.
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Slot of a HandlePool. The generation is advanced every time the item is released.
+struct Slot<T> {
+    value: T,
+    generation: u32,
+    in_use: bool
+}
+
+/// Handle based variant of ObjectPool with a fully safe API.
+/// Items live inline in a vector and are only reachable through their PoolHandle,
+/// so the storage can reallocate freely and stale handles are detected by their generation.
+pub struct HandlePool<T: Poolable> {
+    slots: Vec<Slot<T>>,
+    available: Vec<u32>
+}
+
+impl<T: Poolable> Default for HandlePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> HandlePool<T> {
+    /// Create a new HandlePool.
+    pub fn new() -> HandlePool<T> {
+        HandlePool {
+            slots: Vec::new(),
+            available: Vec::new()
+        }
+    }
+
+    /// Allocate a new slot and return its index.
+    fn allocate(&mut self) -> u32 {
+        let index = u32::try_from(self.slots.len()).expect("HandlePool cannot hold more than u32::MAX items");
+        self.slots.push(Slot {
+            value: T::new(),
+            generation: 0,
+            in_use: false
+        });
+        index
+    }
+
+    /// Reserve a number of items in the pool.
+    pub fn reserve(&mut self, count: usize) {
+        self.slots.reserve(count);
+        for _ in 0..count {
+            let index = self.allocate();
+            self.available.push(index);
+        }
+    }
+
+    /// Get an item from the pool.
+    pub fn get(&mut self) -> PoolHandle {
+        let index = match self.available.pop() {
+            Some(index) => index,
+            None => self.allocate()
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.in_use = true;
+        PoolHandle {
+            index,
+            generation: slot.generation
+        }
+    }
+
+    /// Get the slot of a handle if the handle is still live.
+    fn slot(&self, handle: PoolHandle) -> Option<&Slot<T>> {
+        self.slots.get(handle.index as usize)
+            .filter(|slot| slot.in_use && slot.generation == handle.generation)
+    }
+
+    /// Get the slot of a handle mutably if the handle is still live.
+    fn slot_mut(&mut self, handle: PoolHandle) -> Option<&mut Slot<T>> {
+        self.slots.get_mut(handle.index as usize)
+            .filter(|slot| slot.in_use && slot.generation == handle.generation)
+    }
+
+    /// Release an item back to the pool.
+    /// Fails if the handle was already released.
+    pub fn release(&mut self, handle: PoolHandle) -> Result<(), PoolError> {
+        let slot = self.slot_mut(handle).ok_or(PoolError::StaleHandle)?;
+        slot.value.reset();
+        slot.in_use = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.available.push(handle.index);
+        Ok(())
+    }
+
+    /// Check whether a handle still refers to an item that is handed out.
+    pub fn contains(&self, handle: PoolHandle) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    /// Get a reference to the item of a handle, or None if the handle is stale.
+    pub fn get_ref(&self, handle: PoolHandle) -> Option<&T> {
+        self.slot(handle).map(|slot| &slot.value)
+    }
+
+    /// Get a mutable reference to the item of a handle, or None if the handle is stale.
+    pub fn get_mut(&mut self, handle: PoolHandle) -> Option<&mut T> {
+        self.slot_mut(handle).map(|slot| &mut slot.value)
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.available.len()
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.slots.len() - self.available.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_handle_pool() {
+        let mut pool = HandlePool::<TestObject>::new();
+        pool.reserve(1);
+        let a = pool.get();
+        let b = pool.get();
+        pool.get_mut(a).unwrap().value = 1;
+        pool.get_mut(b).unwrap().value = 2;
+        assert_eq!(pool.get_ref(a).unwrap().value, 1);
+        assert_eq!(pool.in_use(), 2);
+
+        // Released handles go stale, even when their slot is handed out again.
+        assert_eq!(pool.release(a), Ok(()));
+        assert_eq!(pool.release(a), Err(PoolError::StaleHandle));
+        let c = pool.get();
+        assert_eq!(c.index(), a.index());
+        assert!(!pool.contains(a));
+        assert!(pool.get_ref(a).is_none());
+        assert_eq!(pool.get_ref(c).unwrap().value, 0);
+
+        // Handles survive reallocation of the storage.
+        let handles: Vec<_> = (0..100).map(|_| pool.get()).collect();
+        assert_eq!(pool.get_ref(b).unwrap().value, 2);
+        for handle in handles {
+            pool.release(handle).unwrap();
+        }
+        assert_eq!(pool.len(), 102);
+        assert_eq!(pool.available(), 100);
+    }
+}
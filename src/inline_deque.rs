@@ -0,0 +1,271 @@
+use std::collections::{vec_deque, TryReserveError, VecDeque};
+use std::iter::Flatten;
+use std::{array, mem};
+
+/// Double-ended queue that keeps up to `N` elements inline and only moves them to a heap allocated VecDeque when
+/// it grows beyond that, so short queues never allocate. The inline part shifts its elements on `pop_front`,
+/// which is cheap for the small `N` it is meant for.
+pub(crate) enum InlineDeque<P: Copy, const N: usize> {
+    Inline([Option<P>; N], usize),
+    Heap(VecDeque<P>)
+}
+
+impl<P: Copy, const N: usize> Default for InlineDeque<P, N> {
+    fn default() -> Self {
+        InlineDeque::Inline([None; N], 0)
+    }
+}
+
+impl<P: Copy, const N: usize> InlineDeque<P, N> {
+    pub(crate) fn new() -> InlineDeque<P, N> {
+        InlineDeque::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            InlineDeque::Inline(_, len) => *len,
+            InlineDeque::Heap(deque) => deque.len()
+        }
+    }
+
+    /// Move the elements to a heap allocated queue with room for `capacity` elements.
+    fn spill(&mut self, capacity: usize) -> Result<&mut VecDeque<P>, TryReserveError> {
+        if let InlineDeque::Inline(elements, len) = self {
+            let mut deque = VecDeque::new();
+            deque.try_reserve(capacity)?;
+            deque.extend(elements[..*len].iter().flatten());
+            *self = InlineDeque::Heap(deque);
+        }
+        match self {
+            InlineDeque::Heap(deque) => Ok(deque),
+            InlineDeque::Inline(..) => unreachable!()
+        }
+    }
+
+    /// Make room for `additional` more elements, moving to the heap if they do not fit inline.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        if let Err(error) = self.try_reserve(additional) {
+            panic!("available list capacity overflow: {}", error);
+        }
+    }
+
+    /// Make room for `additional` more elements like `reserve`, returning an error if allocating fails.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let len = self.len();
+        match self {
+            InlineDeque::Inline(..) if len.saturating_add(additional) <= N => Ok(()),
+            InlineDeque::Inline(..) => self.spill(len.saturating_add(additional)).map(drop),
+            InlineDeque::Heap(deque) => deque.try_reserve(additional)
+        }
+    }
+
+    pub(crate) fn push_back(&mut self, element: P) {
+        match self {
+            InlineDeque::Inline(elements, len) if *len < N => {
+                elements[*len] = Some(element);
+                *len += 1;
+            }
+            InlineDeque::Inline(_, len) => {
+                let capacity = (*len * 2).max(1);
+                self.spill(capacity).expect("available list capacity overflow").push_back(element);
+            }
+            InlineDeque::Heap(deque) => deque.push_back(element)
+        }
+    }
+
+    pub(crate) fn push_front(&mut self, element: P) {
+        match self {
+            InlineDeque::Inline(elements, len) if *len < N => {
+                elements.rotate_right(1);
+                elements[0] = Some(element);
+                *len += 1;
+            }
+            InlineDeque::Inline(_, len) => {
+                let capacity = (*len * 2).max(1);
+                self.spill(capacity).expect("available list capacity overflow").push_front(element);
+            }
+            InlineDeque::Heap(deque) => deque.push_front(element)
+        }
+    }
+
+    pub(crate) fn pop_back(&mut self) -> Option<P> {
+        match self {
+            InlineDeque::Inline(elements, len) => {
+                *len = len.checked_sub(1)?;
+                elements[*len].take()
+            }
+            InlineDeque::Heap(deque) => deque.pop_back()
+        }
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<P> {
+        match self {
+            InlineDeque::Inline(elements, len) => {
+                *len = len.checked_sub(1)?;
+                let first = elements[0].take();
+                elements.rotate_left(1);
+                first
+            }
+            InlineDeque::Heap(deque) => deque.pop_front()
+        }
+    }
+
+    /// Remove the first `count` elements, or all of them if there are fewer.
+    pub(crate) fn drain_front(&mut self, count: usize) -> Vec<P> {
+        let count = count.min(self.len());
+        (0..count).filter_map(|_| self.pop_front()).collect()
+    }
+
+    /// Remove the last `count` elements, or all of them if there are fewer, keeping their order.
+    pub(crate) fn split_back(&mut self, count: usize) -> Vec<P> {
+        let mut back: Vec<P> = (0..count.min(self.len())).filter_map(|_| self.pop_back()).collect();
+        back.reverse();
+        back
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = P> + '_ {
+        let (inline, heap) = match self {
+            InlineDeque::Inline(elements, len) => (&elements[..*len], None),
+            InlineDeque::Heap(deque) => (&[][..], Some(deque))
+        };
+        inline.iter().flatten().copied().chain(heap.into_iter().flatten().copied())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        match self {
+            InlineDeque::Inline(elements, len) => {
+                elements[..*len].fill(None);
+                *len = 0;
+            }
+            InlineDeque::Heap(deque) => deque.clear()
+        }
+    }
+
+    /// Release unused heap memory, moving the elements back inline if they fit.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if let InlineDeque::Heap(deque) = self {
+            if deque.len() <= N {
+                let deque = mem::take(deque);
+                *self = InlineDeque::new();
+                self.extend(deque);
+            } else {
+                deque.shrink_to_fit();
+            }
+        }
+    }
+}
+
+impl<P: Copy, const N: usize> Extend<P> for InlineDeque<P, N> {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        for element in iter {
+            self.push_back(element);
+        }
+    }
+}
+
+/// Owning iterator over the elements of an InlineDeque, from front to back.
+pub(crate) enum IntoIter<P: Copy, const N: usize> {
+    Inline(Flatten<array::IntoIter<Option<P>, N>>),
+    Heap(vec_deque::IntoIter<P>)
+}
+
+impl<P: Copy, const N: usize> Iterator for IntoIter<P, N> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        match self {
+            IntoIter::Inline(iter) => iter.next(),
+            IntoIter::Heap(iter) => iter.next()
+        }
+    }
+}
+
+impl<P: Copy, const N: usize> IntoIterator for InlineDeque<P, N> {
+    type Item = P;
+    type IntoIter = IntoIter<P, N>;
+
+    fn into_iter(self) -> IntoIter<P, N> {
+        match self {
+            // Only the first `len` elements are Some, so flattening skips the unused tail.
+            InlineDeque::Inline(elements, _) => IntoIter::Inline(elements.into_iter().flatten()),
+            InlineDeque::Heap(deque) => IntoIter::Heap(deque.into_iter())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_inline<const N: usize>(deque: &InlineDeque<u32, N>) -> bool {
+        matches!(deque, InlineDeque::Inline(..))
+    }
+
+    #[test]
+    fn test_spill_and_shrink_back_inline() {
+        let mut deque = InlineDeque::<u32, 2>::new();
+        deque.extend([1, 2]);
+        assert!(is_inline(&deque));
+        // The third element spills to the heap, keeping the order.
+        deque.push_back(3);
+        assert!(!is_inline(&deque));
+        assert_eq!(deque.iter().collect::<Vec<_>>(), [1, 2, 3]);
+        // FIFO from the front and LIFO from the back work the same across the spill.
+        assert_eq!((deque.pop_front(), deque.pop_back()), (Some(1), Some(3)));
+        deque.shrink_to_fit();
+        assert!(is_inline(&deque));
+        assert_eq!(deque.iter().collect::<Vec<_>>(), [2]);
+        deque.reserve(2);
+        assert!(!is_inline(&deque));
+        deque.clear();
+        assert_eq!((deque.len(), deque.pop_front(), deque.pop_back()), (0, None, None));
+    }
+
+    #[test]
+    fn test_inline_order() {
+        let mut deque = InlineDeque::<u32, 4>::new();
+        deque.extend([1, 2, 3]);
+        // pop_front rotates the remaining elements to the start of the array.
+        assert_eq!(deque.pop_front(), Some(1));
+        deque.extend([4, 5]);
+        assert!(is_inline(&deque));
+        assert_eq!(deque.iter().collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!(deque.pop_back(), Some(5));
+        assert_eq!(deque.pop_front(), Some(2));
+        assert_eq!(deque.iter().collect::<Vec<_>>(), [3, 4]);
+        deque.push_front(2);
+        // A full inline array spills when pushing at the front, too.
+        deque.extend([5, 6]);
+        deque.push_front(1);
+        assert!(!is_inline(&deque));
+        assert_eq!(deque.iter().collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_drain_and_split() {
+        for spilled in [false, true] {
+            let mut deque = InlineDeque::<u32, 8>::new();
+            deque.extend(1..=6);
+            if spilled {
+                deque.reserve(8);
+            }
+            assert_eq!(is_inline(&deque), !spilled);
+            assert_eq!(deque.drain_front(2), [1, 2]);
+            assert_eq!(deque.split_back(2), [5, 6]);
+            assert_eq!(deque.split_back(10), [3, 4]);
+            assert!(deque.drain_front(1).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut deque = InlineDeque::<u32, 4>::new();
+        deque.extend([1, 2, 3]);
+        deque.pop_front();
+        // Only the filled part of the array is yielded.
+        assert_eq!(deque.into_iter().collect::<Vec<_>>(), [2, 3]);
+        let mut deque = InlineDeque::<u32, 1>::new();
+        deque.extend([1, 2, 3]);
+        assert_eq!(deque.into_iter().collect::<Vec<_>>(), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::{HashSet, TryReserveError};
+use std::hash::Hash;
+use std::mem;
+
+/// Set that keeps up to `N` elements inline and only moves them to a heap allocated HashSet when it grows beyond
+/// that, so small sets never allocate. The inline part is searched linearly, which is cheap for the small `N` it
+/// is meant for.
+pub(crate) enum InlineSet<P: Copy + Eq + Hash, const N: usize> {
+    Inline([Option<P>; N], usize),
+    Heap(HashSet<P>)
+}
+
+impl<P: Copy + Eq + Hash, const N: usize> Default for InlineSet<P, N> {
+    fn default() -> Self {
+        InlineSet::Inline([None; N], 0)
+    }
+}
+
+impl<P: Copy + Eq + Hash, const N: usize> InlineSet<P, N> {
+    pub(crate) fn new() -> InlineSet<P, N> {
+        InlineSet::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            InlineSet::Inline(_, len) => *len,
+            InlineSet::Heap(set) => set.len()
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Move the elements to a heap allocated set with room for `capacity` elements.
+    fn spill(&mut self, capacity: usize) -> Result<&mut HashSet<P>, TryReserveError> {
+        if let InlineSet::Inline(elements, len) = self {
+            let mut set = HashSet::new();
+            set.try_reserve(capacity)?;
+            set.extend(elements[..*len].iter().flatten());
+            *self = InlineSet::Heap(set);
+        }
+        match self {
+            InlineSet::Heap(set) => Ok(set),
+            InlineSet::Inline(..) => unreachable!()
+        }
+    }
+
+    /// Make room for `additional` more elements, moving to the heap if they do not fit inline.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        if let Err(error) = self.try_reserve(additional) {
+            panic!("item set capacity overflow: {}", error);
+        }
+    }
+
+    /// Make room for `additional` more elements like `reserve`, returning an error if allocating fails.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let len = self.len();
+        match self {
+            InlineSet::Inline(..) if len.saturating_add(additional) <= N => Ok(()),
+            InlineSet::Inline(..) => self.spill(len.saturating_add(additional)).map(drop),
+            InlineSet::Heap(set) => set.try_reserve(additional)
+        }
+    }
+
+    pub(crate) fn contains(&self, element: &P) -> bool {
+        match self {
+            InlineSet::Inline(elements, len) => elements[..*len].contains(&Some(*element)),
+            InlineSet::Heap(set) => set.contains(element)
+        }
+    }
+
+    /// Add an element, returning false if it was already in the set.
+    pub(crate) fn insert(&mut self, element: P) -> bool {
+        if self.contains(&element) {
+            return false;
+        }
+        match self {
+            InlineSet::Inline(elements, len) if *len < N => {
+                elements[*len] = Some(element);
+                *len += 1;
+                true
+            }
+            InlineSet::Inline(_, len) => {
+                let capacity = (*len * 2).max(1);
+                self.spill(capacity).expect("item set capacity overflow").insert(element)
+            }
+            InlineSet::Heap(set) => set.insert(element)
+        }
+    }
+
+    /// Remove an element, returning false if it was not in the set.
+    /// The last inline element takes the place of the removed one.
+    pub(crate) fn remove(&mut self, element: &P) -> bool {
+        match self {
+            InlineSet::Inline(elements, len) => {
+                let Some(index) = elements[..*len].iter().position(|slot| *slot == Some(*element)) else {
+                    return false;
+                };
+                *len -= 1;
+                elements.swap(index, *len);
+                elements[*len] = None;
+                true
+            }
+            InlineSet::Heap(set) => set.remove(element)
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = P> + '_ {
+        let (inline, heap) = match self {
+            InlineSet::Inline(elements, len) => (&elements[..*len], None),
+            InlineSet::Heap(set) => (&[][..], Some(set))
+        };
+        inline.iter().flatten().copied().chain(heap.into_iter().flatten().copied())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        match self {
+            InlineSet::Inline(elements, len) => {
+                elements[..*len].fill(None);
+                *len = 0;
+            }
+            InlineSet::Heap(set) => set.clear()
+        }
+    }
+
+    /// Release unused heap memory, moving the elements back inline if they fit.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if let InlineSet::Heap(set) = self {
+            if set.len() <= N {
+                let set = mem::take(set);
+                *self = InlineSet::new();
+                for element in set {
+                    self.insert(element);
+                }
+            } else {
+                set.shrink_to_fit();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_inline<const N: usize>(set: &InlineSet<u32, N>) -> bool {
+        matches!(set, InlineSet::Inline(..))
+    }
+
+    fn sorted<const N: usize>(set: &InlineSet<u32, N>) -> Vec<u32> {
+        let mut elements: Vec<_> = set.iter().collect();
+        elements.sort();
+        elements
+    }
+
+    #[test]
+    fn test_spill_and_shrink_back_inline() {
+        let mut set = InlineSet::<u32, 2>::new();
+        assert!(set.insert(1) && set.insert(2));
+        assert!(!set.insert(2));
+        assert!(is_inline(&set));
+        // The third element spills to the heap, keeping the others.
+        assert!(set.insert(3));
+        assert!(!is_inline(&set));
+        assert_eq!(sorted(&set), [1, 2, 3]);
+        assert!(set.remove(&1) && !set.remove(&1));
+        set.shrink_to_fit();
+        assert!(is_inline(&set));
+        assert_eq!(sorted(&set), [2, 3]);
+        set.reserve(1);
+        assert!(!is_inline(&set));
+        set.clear();
+        assert!(set.is_empty() && !set.contains(&2));
+    }
+
+    #[test]
+    fn test_inline_remove() {
+        let mut set = InlineSet::<u32, 4>::new();
+        for element in [1, 2, 3, 4] {
+            set.insert(element);
+        }
+        // Removing from the middle moves the last element into the gap.
+        assert!(set.remove(&2));
+        assert!(!set.remove(&5));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [1, 4, 3]);
+        assert!(set.contains(&4) && !set.contains(&2));
+        assert!(set.insert(2));
+        assert!(is_inline(&set));
+        assert_eq!(set.len(), 4);
+    }
+}
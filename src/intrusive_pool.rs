@@ -0,0 +1,222 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+
+use crate::object_pool::object_pool::Poolable;
+
+/// Poolable type that holds the free list link of its pool itself, so the pool keeps no separate list of
+/// available objects. The link is a `usize` field that only the pool uses; `0` marks the end of the list.
+///
+/// # Safety
+/// `free_link` must always return the same field of `self`, and nothing but the pool may write it while the object
+/// is available. `reset` must not touch it either. A broken link would hand out one object twice.
+pub unsafe trait IntrusivePoolable: Poolable {
+    /// Get the free list link of the object.
+    fn free_link(&mut self) -> &mut usize;
+}
+
+/// Variant of AutoReturnObjectPool whose free list is threaded through the available objects.
+/// Getting and releasing an object never touches any memory but the object itself, and growing the pool allocates
+/// one chunk of objects at a time without a side list to reallocate, which suits millions of tiny objects.
+/// Objects never move and are only freed when the pool is dropped.
+pub struct IntrusivePool<T: IntrusivePoolable> {
+    /// Chunks of objects, each a leaked boxed slice that is freed when the pool is dropped.
+    chunks: RefCell<Vec<NonNull<[T]>>>,
+    /// Address of the first available object, or 0 if none is available.
+    head: Cell<usize>,
+    len: Cell<usize>,
+    available: Cell<usize>
+}
+
+impl<T: IntrusivePoolable> Default for IntrusivePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: IntrusivePoolable> IntrusivePool<T> {
+    /// Smallest number of objects in a chunk.
+    const MIN_CHUNK: usize = 16;
+
+    /// Create a new IntrusivePool.
+    pub fn new() -> IntrusivePool<T> {
+        IntrusivePool {
+            chunks: RefCell::new(Vec::new()),
+            head: Cell::new(0),
+            len: Cell::new(0),
+            available: Cell::new(0)
+        }
+    }
+
+    /// Push an available object onto the free list.
+    ///
+    /// # Safety
+    /// The object must belong to the pool and must not be handed out or already on the list.
+    unsafe fn push(&self, item: NonNull<T>) {
+        unsafe {
+            *(*item.as_ptr()).free_link() = self.head.get();
+        }
+        self.head.set(item.as_ptr().expose_provenance());
+        self.available.set(self.available.get() + 1);
+    }
+
+    /// Allocate a chunk of `count` objects and make them available.
+    fn grow(&self, count: usize) {
+        let chunk: Box<[T]> = (0..count).map(|_| T::new()).collect();
+        // SAFETY: `Box::into_raw` never returns null.
+        let chunk = unsafe { NonNull::new_unchecked(Box::into_raw(chunk)) };
+        let first = chunk.cast::<T>();
+        // Link in reverse, so the objects are handed out in address order.
+        for index in (0..count).rev() {
+            // SAFETY: the objects of the new chunk are owned by the pool alone.
+            unsafe {
+                self.push(first.add(index));
+            }
+        }
+        self.chunks.borrow_mut().push(chunk);
+        self.len.set(self.len.get() + count);
+    }
+
+    /// Reserve a number of items in the pool, in one chunk.
+    pub fn reserve(&self, count: usize) {
+        if count > 0 {
+            self.grow(count);
+        }
+    }
+
+    /// Get an IntrusivePoolBox from the pool, growing it by a chunk as large as the pool if none is available.
+    pub fn get(&self) -> IntrusivePoolBox<'_, T> {
+        if self.head.get() == 0 {
+            self.grow(self.len.get().max(IntrusivePool::<T>::MIN_CHUNK));
+        }
+        let item = NonNull::new(ptr::with_exposed_provenance_mut::<T>(self.head.get())).unwrap();
+        // SAFETY: the head is an available object owned by the pool.
+        self.head.set(unsafe { *(*item.as_ptr()).free_link() });
+        self.available.set(self.available.get() - 1);
+        IntrusivePoolBox {
+            item,
+            pool: self
+        }
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.available.get()
+    }
+}
+
+impl<T: IntrusivePoolable> Drop for IntrusivePool<T> {
+    fn drop(&mut self) {
+        // Guards borrow the pool, so no object is handed out any more.
+        for chunk in self.chunks.get_mut().drain(..) {
+            unsafe {
+                drop(Box::from_raw(chunk.as_ptr()));
+            }
+        }
+    }
+}
+
+/// Guard handed out by an IntrusivePool, which resets the item and pushes it onto the free list when dropped.
+pub struct IntrusivePoolBox<'a, T: IntrusivePoolable> {
+    item: NonNull<T>,
+    pool: &'a IntrusivePool<T>
+}
+
+impl<T: IntrusivePoolable> Deref for IntrusivePoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            self.item.as_ref()
+        }
+    }
+}
+
+impl<T: IntrusivePoolable> DerefMut for IntrusivePoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            self.item.as_mut()
+        }
+    }
+}
+
+impl<T: IntrusivePoolable + fmt::Debug> fmt::Debug for IntrusivePoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: IntrusivePoolable> Drop for IntrusivePoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.item.as_mut().reset();
+            self.pool.push(self.item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestObject {
+        pub value: i32,
+        link: usize
+    }
+
+    impl Poolable for TestObject {
+        fn new() -> TestObject {
+            TestObject {
+                value: 0,
+                link: 0
+            }
+        }
+
+        fn reset(&mut self) {
+            self.value = 0;
+        }
+    }
+
+    unsafe impl IntrusivePoolable for TestObject {
+        fn free_link(&mut self) -> &mut usize {
+            &mut self.link
+        }
+    }
+
+    #[test]
+    fn test_intrusive_pool() {
+        let pool = IntrusivePool::<TestObject>::new();
+        pool.reserve(2);
+        let mut first = pool.get();
+        first.value = 1;
+        let address = &*first as *const TestObject;
+        let second = pool.get();
+        assert!(&*second as *const TestObject > &*first as *const TestObject);
+        assert_eq!((pool.len(), pool.available()), (2, 0));
+
+        // An empty pool grows by a chunk of at least 16 objects.
+        let third = pool.get();
+        assert_eq!((pool.len(), pool.available()), (18, 15));
+        // The last object released is the first one reused, reset.
+        drop((third, second, first));
+        let reused = pool.get();
+        assert_eq!(reused.value, 0);
+        assert!(ptr::eq(&*reused, address));
+        drop(reused);
+        let held: Vec<_> = (0..18).map(|_| pool.get()).collect();
+        assert_eq!((pool.len(), pool.available()), (18, 0));
+        drop(held);
+        assert_eq!(pool.available(), 18);
+    }
+}
@@ -1 +1,35 @@
-pub mod object_pool;
\ No newline at end of file
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+pub mod object_pool;
+pub mod shared_pool;
+pub mod handle_pool;
+pub mod dense_pool;
+pub mod pinned_pool;
+pub mod concurrent_pool;
+pub mod lock_free_pool;
+pub mod bitmap_pool;
+pub mod fixed_pool;
+pub mod intrusive_pool;
+pub mod sharded_pool;
+pub mod thread_local_pool;
+pub mod array_queue_pool;
+pub mod channel_pool;
+pub mod static_pool;
+pub mod registry;
+pub mod builder;
+pub mod async_pool;
+pub mod semaphore_pool;
+pub mod quota_pool;
+#[cfg(feature = "arena")]
+pub mod arena_pool;
+#[cfg(any(feature = "std", test))]
+mod executor;
+#[cfg(target_os = "linux")]
+mod pages;
+mod inline_deque;
+mod inline_set;
+mod sync;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(test)]
+pub(crate) mod test_support;
\ No newline at end of file
@@ -0,0 +1,263 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+
+use crate::object_pool::object_pool::Poolable;
+use crate::sync::{AtomicU32, AtomicU64, AtomicUsize, CachePadded};
+
+/// Index marking the end of the free list.
+const NIL: u32 = u32::MAX;
+
+/// Pack a free list head from its ABA tag and slot index.
+fn pack(tag: u32, index: u32) -> u64 {
+    ((tag as u64) << 32) | index as u64
+}
+
+/// Split a free list head into its ABA tag and slot index.
+fn unpack(head: u64) -> (u32, u32) {
+    ((head >> 32) as u32, head as u32)
+}
+
+/// Slot of a LockFreePool: the item and the index of the next free slot.
+struct Slot<T> {
+    value: UnsafeCell<T>,
+    next: AtomicU32
+}
+
+/// Fixed capacity pool whose free list is a lock-free Treiber stack of slot indices.
+///
+/// The head of the stack packs a slot index with a tag that is incremented by every push and pop,
+/// so a stale compare-and-swap fails even if the same index is back on top (the ABA problem).
+/// Slots are allocated once and never move or get freed while the pool is alive,
+/// so reading the `next` link of a slot that was popped concurrently is always valid.
+/// The head and the available counter are padded to separate cache lines to avoid false sharing.
+pub struct LockFreePool<T: Poolable> {
+    slots: Box<[Slot<T>]>,
+    head: CachePadded<AtomicU64>,
+    available: CachePadded<AtomicUsize>
+}
+
+// SAFETY: items are only accessed by the single guard that popped their slot,
+// and the free list itself is made of atomics.
+unsafe impl<T: Poolable + Send> Send for LockFreePool<T> {}
+unsafe impl<T: Poolable + Send> Sync for LockFreePool<T> {}
+
+impl<T: Poolable> LockFreePool<T> {
+    /// Create a LockFreePool holding `capacity` items, which are all created up front.
+    pub fn with_capacity(capacity: usize) -> LockFreePool<T> {
+        assert!(capacity < NIL as usize, "LockFreePool capacity must be below u32::MAX");
+        let slots: Box<[Slot<T>]> = (0..capacity).map(|index| Slot {
+            value: UnsafeCell::new(T::new()),
+            next: AtomicU32::new(if index + 1 < capacity { index as u32 + 1 } else { NIL })
+        }).collect();
+        let first = if capacity > 0 { 0 } else { NIL };
+        LockFreePool {
+            slots,
+            head: CachePadded::new(AtomicU64::new(pack(0, first))),
+            available: CachePadded::new(AtomicUsize::new(capacity))
+        }
+    }
+
+    /// Get an item from the pool, or None if every item is handed out.
+    pub fn try_get(&self) -> Option<LockFreePoolBox<'_, T>> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let (tag, index) = unpack(head);
+            if index == NIL {
+                return None;
+            }
+            let next = self.slots[index as usize].next.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(head, pack(tag.wrapping_add(1), next), Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    self.available.fetch_sub(1, Ordering::Relaxed);
+                    return Some(LockFreePoolBox {
+                        index,
+                        pool: self
+                    });
+                }
+                Err(current) => head = current
+            }
+        }
+    }
+
+    /// Reset the item of a slot and push the slot back onto the free list.
+    /// If `reset()` panics, the item is replaced by a new one before the slot is pushed, and the panic continues
+    /// once the old item is dropped.
+    fn release(&self, index: u32) {
+        let value = self.slots[index as usize].value.get();
+        // SAFETY: the slot was popped by the releasing guard, which had exclusive access to it.
+        let reset = panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*value).reset() }));
+        let broken = reset.is_err().then(|| unsafe { mem::replace(&mut *value, T::new()) });
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let (tag, first) = unpack(head);
+            self.slots[index as usize].next.store(first, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(head, pack(tag.wrapping_add(1), index), Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => head = current
+            }
+        }
+        self.available.fetch_add(1, Ordering::Relaxed);
+        if let Err(payload) = reset {
+            drop(broken);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the number of items in the pool.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Get the number of available items in the pool.
+    /// The count is updated after the free list, so it may briefly lag behind under contention.
+    pub fn available(&self) -> usize {
+        self.available.load(Ordering::Relaxed)
+    }
+}
+
+/// Guard handed out by a LockFreePool. It releases its item when dropped.
+pub struct LockFreePoolBox<'a, T: Poolable> {
+    index: u32,
+    pool: &'a LockFreePool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item.
+unsafe impl<T: Poolable + Send> Send for LockFreePoolBox<'_, T> {}
+unsafe impl<T: Poolable + Sync> Sync for LockFreePoolBox<'_, T> {}
+
+impl<T: Poolable> Deref for LockFreePoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.pool.slots[self.index as usize].value.get()
+        }
+    }
+}
+
+impl<T: Poolable> DerefMut for LockFreePoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.pool.slots[self.index as usize].value.get()
+        }
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for LockFreePoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable> Drop for LockFreePoolBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.index);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    struct PanickyObject {
+        pub fail: bool
+    }
+
+    impl Poolable for PanickyObject {
+        fn new() -> PanickyObject {
+            PanickyObject {
+                fail: false
+            }
+        }
+
+        fn reset(&mut self) {
+            assert!(!self.fail, "reset failed");
+        }
+    }
+
+    #[test]
+    fn test_lock_free_pool() {
+        let pool = LockFreePool::<TestObject>::with_capacity(2);
+        let mut a = pool.try_get().unwrap();
+        let b = pool.try_get().unwrap();
+        assert!(pool.try_get().is_none());
+        a.value = 1;
+        drop(a);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.try_get().unwrap().value, 0);
+        drop(b);
+        assert_eq!(pool.available(), pool.capacity());
+        assert!(LockFreePool::<TestObject>::with_capacity(0).try_get().is_none());
+    }
+
+    #[test]
+    fn test_panicking_reset_keeps_slot() {
+        let pool = LockFreePool::<PanickyObject>::with_capacity(1);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.try_get().unwrap().fail = true;
+        }));
+        assert!(result.is_err());
+        // The slot is back on the free list with a new item.
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.try_get().unwrap().fail);
+    }
+
+    #[test]
+    fn test_lock_free_pool_contention() {
+        let pool = LockFreePool::<TestObject>::with_capacity(4);
+        thread::scope(|scope| {
+            for worker in 1..=8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        if let Some(mut obj) = pool.try_get() {
+                            // Another thread sharing this item would overwrite the value.
+                            assert_eq!(obj.value, 0);
+                            obj.value = worker;
+                            thread::yield_now();
+                            assert_eq!(obj.value, worker);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available(), 4);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use super::*;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn loom_item_is_never_shared() {
+        loom::model(|| {
+            let pool = Arc::new(LockFreePool::<TestObject>::with_capacity(1));
+            let workers: Vec<_> = (1..=2).map(|worker| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    if let Some(mut obj) = pool.try_get() {
+                        assert_eq!(obj.value, 0);
+                        obj.value = worker;
+                        thread::yield_now();
+                        assert_eq!(obj.value, worker);
+                    }
+                })
+            }).collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            assert_eq!(pool.available(), 1);
+        });
+    }
+}
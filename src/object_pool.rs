@@ -1,5 +1,134 @@
+#[allow(clippy::module_inception)]
 pub mod object_pool {
 
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
+use std::alloc::{self, Layout};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::TryReserveError;
+use std::convert::Infallible;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+use crate::pages;
+use crate::inline_deque::InlineDeque;
+use crate::inline_set::InlineSet;
+
+/// Errors returned by the fallible pool operations.
+/// `E` is the error of a fallible factory, which only `try_get` can return.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError<E = Infallible> {
+    /// The operation requires every item to be available, but this many are still handed out.
+    InUse(usize),
+    /// The pointer was not handed out by this pool.
+    ForeignPointer,
+    /// The item belongs to the pool but is not handed out.
+    NotInUse,
+    /// The handle refers to an item that has already been released.
+    StaleHandle,
+    /// The pool is at its maximum size, every item is in use, and its ExhaustedBehavior is not to grow.
+    Exhausted,
+    /// No item is available and the pool is frozen, so it may not create one.
+    Frozen,
+    /// A new item could not be created.
+    Create(E)
+}
+
+impl<E: fmt::Display> fmt::Display for PoolError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::InUse(count) => write!(f, "{} pool items are still in use", count),
+            PoolError::ForeignPointer => write!(f, "pointer does not belong to this pool"),
+            PoolError::NotInUse => write!(f, "pool item is not in use"),
+            PoolError::StaleHandle => write!(f, "pool handle is stale"),
+            PoolError::Exhausted => write!(f, "pool is exhausted"),
+            PoolError::Frozen => write!(f, "pool is frozen"),
+            PoolError::Create(error) => write!(f, "failed to create pool item: {}", error)
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for PoolError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PoolError::Create(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+/// Error returned by `try_reserve` when memory for the reserved objects could not be allocated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReserveError {
+    /// The bookkeeping of the pool could not grow to hold the objects.
+    Bookkeeping(TryReserveError),
+    /// An object could not be allocated. The objects reserved before the failure stay in the pool.
+    Alloc(Layout)
+}
+
+impl From<TryReserveError> for ReserveError {
+    fn from(error: TryReserveError) -> ReserveError {
+        ReserveError::Bookkeeping(error)
+    }
+}
+
+impl fmt::Display for ReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReserveError::Bookkeeping(error) => write!(f, "failed to grow the pool bookkeeping: {}", error),
+            ReserveError::Alloc(layout) => write!(f, "failed to allocate a pool item of {} bytes", layout.size())
+        }
+    }
+}
+
+impl std::error::Error for ReserveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReserveError::Bookkeeping(error) => Some(error),
+            ReserveError::Alloc(_) => None
+        }
+    }
+}
+
+/// Diagnostic emitted when a pool is dropped while some of its items are still handed out.
+#[derive(Debug, Clone)]
+pub struct LeakReport {
+    /// Number of items that were still handed out.
+    pub leaked: usize,
+    /// Where each leaked item was acquired.
+    pub sites: Vec<&'static Location<'static>>
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "object pool dropped with {} items still in use", self.leaked)?;
+        for site in self.sites.iter() {
+            write!(f, "\n  acquired at {}", site)?;
+        }
+        Ok(())
+    }
+}
+
+/// Callback that receives leak reports.
+type LeakSink = Box<dyn Fn(&LeakReport) + Send>;
+
+/// Reset closure or lifecycle hook run on a pooled object, such as one given to `ObjectPool::with_reset`.
+type Hook<T> = Box<dyn FnMut(&mut T) + Send>;
+
+/// Fallible reset given to `ObjectPool::with_try_reset`, returning whether the item can be reused.
+type TryResetHook<T> = Box<dyn FnMut(&mut T) -> bool + Send>;
+
 /// Poolable trait that must be implemented by objects that will be stored in the pool.
 /// The trait provides a way to create a new object and reset it to its initial state.
 pub trait Poolable {
@@ -8,203 +137,4297 @@ pub trait Poolable {
     
     /// Reset the object to its initial state.
     fn reset(&mut self);
+
+    /// Get the approximate number of bytes the object occupies, including memory it owns on the heap.
+    /// Pools capped with `with_max_bytes` add this up; the default only counts the object itself.
+    fn size_hint(&self) -> usize
+    where
+        Self: Sized
+    {
+        mem::size_of::<Self>()
+    }
 }
 
-/// Raw variant of ObjectPool that allows manual get and release of items.
-pub struct ObjectPool<T: Poolable> {
-    pub items: Vec<Box<T>>,
-    pub available: Vec<*mut T>
+/// Lightweight alternative to Poolable for the common case of objects that are default constructed and
+/// cleared on return: every `T: Default + Clear` is Poolable.
+pub trait Clear {
+    /// Clear the object, typically keeping allocated capacity.
+    fn clear(&mut self);
+
+    /// Get the approximate number of bytes the object occupies, like `Poolable::size_hint`.
+    fn size_hint(&self) -> usize
+    where
+        Self: Sized
+    {
+        mem::size_of::<Self>()
+    }
 }
 
-impl<T: Poolable> ObjectPool<T> {
-    /// Create a new ObjectPool.
-    pub fn new() -> ObjectPool<T> {
-        ObjectPool {
-            items: Vec::new(),
-            available: Vec::new()
+impl<T: Default + Clear> Poolable for T {
+    fn new() -> T {
+        T::default()
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+
+    fn size_hint(&self) -> usize {
+        Clear::size_hint(self)
+    }
+}
+
+/// Derive Poolable for a struct: fields are default constructed, and reset by `Clear::clear` if they implement it
+/// or by assigning their default otherwise. Use `#[poolable(skip)]`, `#[poolable(default)]` or
+/// `#[poolable(reset = path)]` to change how a field is reset.
+#[cfg(feature = "derive")]
+pub use object_pool_derive::Poolable;
+
+/// Support code for `#[derive(Poolable)]`.
+/// Picks `Clear::clear` for fields that implement it and `Default::default()` for the rest: method resolution
+/// tries the `Field` receiver of ResetViaClear before the `&mut Field` receiver of ResetViaDefault.
+#[doc(hidden)]
+pub mod __derive {
+    use super::Clear;
+
+    pub struct Field<'a, T>(pub &'a mut T);
+
+    pub trait ResetViaClear {
+        fn reset_field(&mut self);
+    }
+
+    impl<T: Clear> ResetViaClear for Field<'_, T> {
+        fn reset_field(&mut self) {
+            self.0.clear();
         }
     }
 
-    /// Reserve a number of items in the pool.
-    pub fn reserve(&mut self, count: usize) {
-        for _ in 0..count {
-            let mut item = Box::new(T::new());
-            let ptr = &mut *item as *mut T;
-            self.items.push(item);
-            self.available.push(ptr);
+    pub trait ResetViaDefault {
+        fn reset_field(&mut self);
+    }
+
+    impl<T: Default> ResetViaDefault for &mut Field<'_, T> {
+        fn reset_field(&mut self) {
+            *self.0 = T::default();
         }
     }
+}
 
-    /// Get an item from the pool.
-    pub fn get(&mut self) -> *mut T {
-        if self.available.is_empty() {
-            let mut item = Box::new(T::new());
-            let ptr = &mut *item as *mut T;
-            self.items.push(item);
-            ptr
-        } else {
-            let ptr = self.available.pop().unwrap();
-            ptr
+/// Implements Clear, and thereby Poolable, for std collections by calling their `clear()`, which keeps capacity.
+#[cfg(feature = "collections")]
+mod collections {
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::mem;
+
+    use super::Clear;
+
+    impl<T> Clear for Vec<T> {
+        fn clear(&mut self) {
+            Vec::clear(self);
+        }
+
+        fn size_hint(&self) -> usize {
+            mem::size_of::<Self>() + self.capacity() * mem::size_of::<T>()
         }
     }
 
-    /// Release an item back to the pool.
-    pub fn release(&mut self, item: *mut T) {
-        unsafe {
-            (*item).reset();
+    impl Clear for String {
+        fn clear(&mut self) {
+            String::clear(self);
+        }
+
+        fn size_hint(&self) -> usize {
+            mem::size_of::<Self>() + self.capacity()
         }
-        self.available.push(item);
     }
 
-    /// Clear the pool completely.
-    pub fn clear(&mut self) {
-        self.items.clear();
-        self.available.clear();
+    impl<K, V, S> Clear for HashMap<K, V, S> {
+        fn clear(&mut self) {
+            HashMap::clear(self);
+        }
+
+        fn size_hint(&self) -> usize {
+            mem::size_of::<Self>() + self.capacity() * mem::size_of::<(K, V)>()
+        }
     }
 
-    /// Release all items back to the pool.
-    pub fn release_all(&mut self) {
-        for item in self.items.iter_mut() {
-            item.reset();
-            self.available.push(&mut **item as *mut T);
+    impl<T, S> Clear for HashSet<T, S> {
+        fn clear(&mut self) {
+            HashSet::clear(self);
+        }
+
+        fn size_hint(&self) -> usize {
+            mem::size_of::<Self>() + self.capacity() * mem::size_of::<T>()
         }
     }
 
-    /// Get the number of items in the pool.
-    pub fn len(&self) -> usize {
-        self.items.len()
+    impl<T> Clear for VecDeque<T> {
+        fn clear(&mut self) {
+            VecDeque::clear(self);
+        }
+
+        fn size_hint(&self) -> usize {
+            mem::size_of::<Self>() + self.capacity() * mem::size_of::<T>()
+        }
     }
+}
 
-    /// Get the number of available items in the pool.
-    pub fn available(&self) -> usize {
-        self.available.len()
+/// Health check for objects that can rot while idle, such as connections. A pool configured with
+/// `ObjectPool::with_validation` destroys invalid objects and hands out fresh ones instead.
+pub trait Validate {
+    /// Check whether the object can still be used.
+    fn is_valid(&self) -> bool;
+}
+
+/// Variant of Poolable for objects whose construction can fail, such as TLS sessions or file handles.
+pub trait TryPoolable: Sized {
+    /// Error returned when an object cannot be created.
+    type Error;
+
+    /// Try to create a new instance of the object.
+    fn try_new() -> Result<Self, Self::Error>;
+
+    /// Reset the object to its initial state.
+    fn reset(&mut self);
+}
+
+/// Variant of Poolable for objects that are created and reset with per-pool parameters, such as a buffer size
+/// or a tenant id. Every Poolable type implements it with `()` as its parameters.
+pub trait PoolableWith: Sized {
+    /// Configuration shared by every object of a pool.
+    type Params;
+
+    /// Create a new instance of the object from the pool parameters.
+    fn new_with(params: &Self::Params) -> Self;
+
+    /// Reset the object to its initial state, which may depend on the pool parameters.
+    fn reset_with(&mut self, params: &Self::Params);
+}
+
+impl<T: Poolable> PoolableWith for T {
+    type Params = ();
+
+    fn new_with(_params: &()) -> T {
+        T::new()
+    }
+
+    fn reset_with(&mut self, _params: &()) {
+        self.reset();
     }
 }
 
-/// PoolBox is a wrapper around a pool item that automatically releases the item back to the pool when dropped.
-pub struct PoolBox<T: Poolable> {
-    pub item: *mut T,
-    pub pool: *mut ObjectPool<T>
+/// Decides how many objects an ObjectPool creates when `get()` finds no idle object, trading allocation
+/// frequency against memory overshoot.
+pub trait GrowthPolicy: Send {
+    /// Get the number of objects to create when the pool holds `len` objects and none of them is idle.
+    /// One of them is handed out and the rest become idle. Zero means the pool does not grow.
+    fn grow_by(&self, len: usize) -> usize;
 }
 
-impl<T: Poolable> PoolBox<T> {
-    /// Create a new PoolBox that wraps an item from the pool.
-    pub fn new(pool: *mut ObjectPool<T>) -> PoolBox<T> {
-        let item = unsafe {
-            (*pool).get()
-        };
-        PoolBox {
-            item: item,
-            pool: pool
+/// Create one object per miss. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrowByOne;
+
+impl GrowthPolicy for GrowByOne {
+    fn grow_by(&self, _len: usize) -> usize {
+        1
+    }
+}
+
+/// Create a fixed number of objects per miss.
+#[derive(Debug, Clone, Copy)]
+pub struct GrowByChunk(pub usize);
+
+impl GrowthPolicy for GrowByChunk {
+    fn grow_by(&self, _len: usize) -> usize {
+        self.0
+    }
+}
+
+/// Double the size of the pool on every miss, so a burst needs a logarithmic number of allocation rounds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Doubling;
+
+impl GrowthPolicy for Doubling {
+    fn grow_by(&self, len: usize) -> usize {
+        len.max(1)
+    }
+}
+
+/// Never create objects on a miss: `get` only hands out objects created by `reserve`, `adopt` or `maintain`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoGrowth;
+
+impl GrowthPolicy for NoGrowth {
+    fn grow_by(&self, _len: usize) -> usize {
+        0
+    }
+}
+
+/// Kind of huge pages that back the slabs of an ObjectPool, see `ObjectPool::with_huge_pages`.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePages {
+    /// Transparent huge pages, requested with `madvise(MADV_HUGEPAGE)`. The kernel backs the slabs with huge
+    /// pages when it can, which requires THP to be enabled in `madvise` or `always` mode.
+    Transparent,
+    /// Huge pages reserved through `/proc/sys/vm/nr_hugepages`, mapped with `MAP_HUGETLB`.
+    /// Falls back to transparent huge pages once none are free.
+    Explicit
+}
+
+/// Which idle object an ObjectPool hands out next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReuseOrder {
+    /// Hand out the most recently released object, whose memory is most likely still in cache.
+    #[default]
+    Lifo,
+    /// Hand out the least recently released object, so every object is used in turn. This evens out wear
+    /// and keeps objects with leases or keep-alives exercised regularly.
+    Fifo
+}
+
+/// When an ObjectPool resets released objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResetTiming {
+    /// Reset objects as soon as they are released, so acquiring is cheap.
+    #[default]
+    OnRelease,
+    /// Reset objects when they are acquired again, so releasing is cheap, e.g. when dropping guards in a hot loop.
+    /// Objects that are never acquired again are dropped without being reset.
+    OnAcquire
+}
+
+/// What a pool with a maximum size does when every object is in use and another one is requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExhaustedBehavior {
+    /// Create another object anyway, treating the maximum size as a soft limit.
+    Grow,
+    /// Fail with `PoolError::Exhausted`, or panic from methods that cannot return an error such as `get()`.
+    Fail,
+    /// Park the calling thread until an object is released. Pools owned by a single thread cannot be released
+    /// into while their owner waits, so they fail instead.
+    Block,
+    /// Suspend the calling task until an object is released, in async pools.
+    /// Thread-safe sync pools block the thread instead, and single-threaded pools fail.
+    Wait
+}
+
+/// Slot backing every pooled object, holding the per-slot state next to the value.
+/// `value` must stay the first field, so that a pointer to the value is also a pointer to its entry.
+#[repr(C)]
+struct Entry<T> {
+    value: T,
+    /// Slab the entry lives in.
+    slab: NonNull<Slab>,
+    in_use: bool,
+    /// Set while the object is available but has not been reset since its release.
+    dirty: bool,
+    /// Number of times the object has been handed out.
+    uses: u64,
+    /// Size hint of the object when it was created or last released.
+    bytes: usize,
+    created_at: Instant,
+    /// When the object was last released, or created if it never was.
+    idle_since: Instant,
+    acquired_at: &'static Location<'static>
+}
+
+impl<T> Entry<T> {
+    /// Create the entry of an object entering the pool.
+    #[track_caller]
+    fn new(value: T, slab: NonNull<Slab>) -> Entry<T> {
+        let now = Instant::now();
+        Entry {
+            value,
+            slab,
+            in_use: false,
+            dirty: false,
+            uses: 0,
+            bytes: 0,
+            created_at: now,
+            idle_since: now,
+            acquired_at: Location::caller()
         }
     }
 
-    /// Get a reference to the item.
-    pub fn ref_item(&self) -> &T {
+    /// Get the entry that holds the object behind `ptr`.
+    fn of(ptr: NonNull<T>) -> *mut Entry<T> {
+        ptr.as_ptr().cast()
+    }
+
+    /// Drop an entry and give its slot back to its slab.
+    ///
+    /// # Safety
+    /// `entry` must be live and must not be used afterwards.
+    unsafe fn free(entry: *mut Entry<T>) {
         unsafe {
-            &*self.item
+            let slab = (*entry).slab;
+            ptr::drop_in_place(entry);
+            #[cfg(target_os = "linux")]
+            Slab::vacate(slab, entry.cast());
+            Slab::release(slab);
         }
     }
 
-    /// Get a mutable reference to the item.
-    pub fn ref_mut_item(&mut self) -> &mut T {
+    /// Move the value out of an entry and give its slot back to its slab.
+    ///
+    /// # Safety
+    /// Same as `free`.
+    unsafe fn into_value(entry: *mut Entry<T>) -> T {
         unsafe {
-            &mut *self.item
+            let slab = (*entry).slab;
+            let value = ptr::read(&(*entry).value);
+            #[cfg(target_os = "linux")]
+            Slab::vacate(slab, entry.cast());
+            Slab::release(slab);
+            value
         }
     }
+}
 
-    /// Extract the item from the PoolBox without releasing it back to the pool.
-    /// The raw pool will be responsible for releasing the item.
-    pub fn extract(&mut self) -> *mut T {
-        let item = self.item;
-        self.item = std::ptr::null_mut();
-        item
-    }
+/// Header of a slab, a single allocation holding the entries of up to `capacity` objects, so growing a pool
+/// does not allocate once per object. Entries never move, and the slab is freed once none of them is live.
+/// Entries keep their slab alive even when they move to another pool or outlive the pool that created them.
+struct Slab {
+    /// Number of live entries, plus one while a pool still hands out slots of the slab.
+    live: AtomicUsize,
+    capacity: usize,
+    /// Layout of the whole slab.
+    layout: Layout,
+    /// Offset of the first entry from the start of the slab.
+    offset: usize,
+    /// Distance between two entries, which is larger than an entry if the pool pads them.
+    stride: usize,
+    /// Allocator the slab was allocated from, which also frees it.
+    allocator: SlabAllocator
 }
 
-impl<T: Poolable> Drop for PoolBox<T> {
-    fn drop(&mut self) {
-        if self.item.is_null() {
+/// Allocator that the slabs of a pool come from: the global allocator, or the one set with `with_allocator`.
+#[derive(Clone, Default)]
+struct SlabAllocator {
+    #[cfg(feature = "allocator_api")]
+    custom: Option<Arc<dyn Allocator + Send + Sync>>,
+    #[cfg(target_os = "linux")]
+    huge_pages: Option<HugePages>,
+    /// Set by `with_decommit`: the pages of freed entries go back to the kernel.
+    #[cfg(target_os = "linux")]
+    decommit: bool,
+    /// Set by `with_secure_memory`: slabs are locked in memory, and freed entries are wiped.
+    #[cfg(target_os = "linux")]
+    secure: bool
+}
+
+impl SlabAllocator {
+    /// Allocate memory for `layout`, which must not be zero-sized.
+    /// Secure memory is rounded to whole pages, so unlocking one slab never unlocks a page of another.
+    fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        #[cfg(target_os = "linux")]
+        if self.secure {
+            let layout = pages::page_layout(layout)?;
+            let memory = self.allocate_unlocked(layout)?;
+            // SAFETY: the memory was just allocated with `layout`, and is freed without locking if that fails.
+            unsafe {
+                if pages::lock(memory.as_ptr(), layout.size()) {
+                    return Some(memory);
+                }
+                self.deallocate_unlocked(memory, layout);
+            }
+            return None;
+        }
+        self.allocate_unlocked(layout)
+    }
+
+    /// Free memory returned by `allocate` with the same layout.
+    ///
+    /// # Safety
+    /// `memory` must have been allocated by this allocator with `layout` and must not be used afterwards.
+    unsafe fn deallocate(&self, memory: NonNull<u8>, layout: Layout) {
+        #[cfg(target_os = "linux")]
+        if self.secure {
+            // `allocate` succeeded with this layout, so rounding it succeeds again.
+            let layout = pages::page_layout(layout).unwrap();
+            unsafe {
+                pages::unlock(memory.as_ptr(), layout.size());
+                self.deallocate_unlocked(memory, layout);
+            }
             return;
         }
         unsafe {
-            (*self.pool).release(self.item);
+            self.deallocate_unlocked(memory, layout);
         }
     }
-}
 
-pub struct AutoReturnObjectPool<T: Poolable> {
-    pub pool: ObjectPool<T>
+    /// Allocate memory for `layout` from the source of the allocator, without locking it.
+    fn allocate_unlocked(&self, layout: Layout) -> Option<NonNull<u8>> {
+        #[cfg(feature = "allocator_api")]
+        if let Some(custom) = &self.custom {
+            return custom.allocate(layout).ok().map(NonNull::cast);
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(huge_pages) = self.huge_pages {
+            return pages::allocate(layout, huge_pages == HugePages::Explicit);
+        }
+        // SAFETY: the caller never asks for a zero-sized layout.
+        NonNull::new(unsafe { alloc::alloc(layout) })
+    }
+
+    /// Free memory returned by `allocate_unlocked` with the same layout.
+    ///
+    /// # Safety
+    /// Same as `deallocate`.
+    unsafe fn deallocate_unlocked(&self, memory: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "allocator_api")]
+        if let Some(custom) = &self.custom {
+            unsafe {
+                custom.deallocate(memory, layout);
+            }
+            return;
+        }
+        #[cfg(target_os = "linux")]
+        if self.huge_pages.is_some() {
+            unsafe {
+                pages::deallocate(memory, layout);
+            }
+            return;
+        }
+        unsafe {
+            alloc::dealloc(memory.as_ptr(), layout);
+        }
+    }
 }
 
-impl<T: Poolable> AutoReturnObjectPool<T> {
-    /// Create a new AutoReturnObjectPool.
-    pub fn new() -> AutoReturnObjectPool<T> {
-        AutoReturnObjectPool {
-            pool: ObjectPool::new()
+impl Slab {
+    /// Slabs of the default capacity hold at most this many bytes, unless a single entry is larger.
+    const DEFAULT_BYTES: usize = 4096;
+    const MAX_DEFAULT_CAPACITY: usize = 64;
+
+    /// Get the default number of entries of `T` aligned to `align` in a slab: up to 64, as far as they fit in a page.
+    /// Slabs from huge pages hold as many entries as fit in a huge page instead.
+    fn default_capacity<T>(align: usize, allocator: &SlabAllocator) -> usize {
+        let (_, offset, stride) = Slab::layout::<T>(1, align);
+        #[cfg(target_os = "linux")]
+        if allocator.huge_pages.is_some() {
+            return (pages::HUGE_PAGE_SIZE.saturating_sub(offset) / stride).max(1);
         }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (offset, allocator);
+        (Slab::DEFAULT_BYTES / stride).clamp(1, Slab::MAX_DEFAULT_CAPACITY)
     }
 
-    /// Get a PoolBox from the pool.
-    pub fn get(&mut self) -> PoolBox<T> {
-        PoolBox::new(&mut self.pool)
+    /// Get the layout of a slab with `capacity` entries of `T` aligned to at least `align`, the offset of its
+    /// first entry and the distance between entries.
+    fn layout<T>(capacity: usize, align: usize) -> (Layout, usize, usize) {
+        let entry = Layout::new::<Entry<T>>().align_to(align).expect("entry alignment is invalid").pad_to_align();
+        let entries = entry.size().checked_mul(capacity).and_then(|size| Layout::from_size_align(size, entry.align()).ok());
+        let (layout, offset) = Layout::new::<Slab>().extend(entries.expect("slab size overflows")).expect("slab size overflows");
+        (layout.pad_to_align(), offset, entry.size())
     }
-}
 
-}
+    /// Allocate a slab for `capacity` entries of `T` aligned to at least `align`, holding the reference of the
+    /// pool that hands out its slots. Returns the layout that failed to allocate on error.
+    fn allocate<T>(capacity: usize, align: usize, allocator: &SlabAllocator) -> Result<NonNull<Slab>, Layout> {
+        let (layout, offset, stride) = Slab::layout::<T>(capacity, align);
+        // The layout is never zero-sized, as it holds the header.
+        let slab = allocator.allocate(layout).ok_or(layout)?.cast::<Slab>();
+        unsafe {
+            slab.as_ptr().write(Slab {
+                live: AtomicUsize::new(1),
+                capacity,
+                layout,
+                offset,
+                stride,
+                allocator: allocator.clone()
+            });
+        }
+        Ok(slab)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::object_pool::*;
+    /// Get the uninitialized entry in slot `index` of a slab and count it as live.
+    ///
+    /// # Safety
+    /// The slab must be live, `index` must be below its capacity, and the slot must never have been handed out.
+    unsafe fn claim<T>(slab: NonNull<Slab>, index: usize) -> NonNull<Entry<T>> {
+        unsafe {
+            let header = slab.as_ref();
+            header.live.fetch_add(1, atomic::Ordering::Relaxed);
+            slab.cast::<u8>().add(header.offset + index * header.stride).cast::<Entry<T>>()
+        }
+    }
 
-    struct TestObject {
-        pub value: i32
+    /// Wipe the memory of a freed slot if the slab holds secure memory, and give it back to the kernel if it was
+    /// allocated with `with_decommit`.
+    ///
+    /// # Safety
+    /// The slab must be live, and `slot` must be one of its slots whose entry has been dropped or moved out.
+    #[cfg(target_os = "linux")]
+    unsafe fn vacate(slab: NonNull<Slab>, slot: *mut u8) {
+        unsafe {
+            let header = slab.as_ref();
+            if header.allocator.secure {
+                wipe(slot, header.stride);
+            }
+            if header.allocator.decommit {
+                pages::decommit(slot, header.stride);
+            }
+        }
     }
 
-    impl Poolable for TestObject {
-        fn new() -> TestObject {
-            TestObject {
-                value: 0
+    /// Drop one reference to a slab, freeing it if it was the last one.
+    /// Entries of one slab can be freed by different pools on different threads, so the count is atomic.
+    ///
+    /// # Safety
+    /// The caller must own one reference and must not use the slab afterwards.
+    unsafe fn release(slab: NonNull<Slab>) {
+        unsafe {
+            if slab.as_ref().live.fetch_sub(1, atomic::Ordering::Release) != 1 {
+                return;
             }
+            atomic::fence(atomic::Ordering::Acquire);
+            let layout = slab.as_ref().layout;
+            let allocator = ptr::read(&slab.as_ref().allocator);
+            allocator.deallocate(slab.cast(), layout);
         }
+    }
+}
 
-        fn reset(&mut self) {
-            self.value = 0;
+/// Overwrite `len` bytes at `memory` with zeros.
+/// The writes are volatile, as the compiler may otherwise drop stores to memory that is never read again.
+///
+/// # Safety
+/// `memory` must be valid for writes of `len` bytes.
+unsafe fn wipe(memory: *mut u8, len: usize) {
+    for offset in 0..len {
+        unsafe {
+            ptr::write_volatile(memory.add(offset), 0);
         }
     }
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
 
-    #[test]
-    fn test_object_pool() {
-        let mut pool = AutoReturnObjectPool::<TestObject>::new();
-        {
-            let mut obj = pool.get();
-            obj.ref_mut_item().value = 10;
-            assert_eq!(obj.ref_item().value, 10);
-            assert_eq!(pool.pool.len(), 1);
+/// Size that `with_cache_line_padding` pads objects to. x86_64 and aarch64 CPUs fetch cache lines in pairs,
+/// so objects on adjacent 64 byte lines still interfere there.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub(crate) const CACHE_LINE: usize = 128;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub(crate) const CACHE_LINE: usize = 64;
+
+/// Distance between the prefetches of `with_prefetch`, the size of a cache line on current CPUs.
+const PREFETCH_STRIDE: usize = 64;
+
+/// Hint the CPU to load `lines` cache lines starting at `ptr` into its caches. A no-op on other architectures
+/// than x86_64 and aarch64.
+#[inline]
+fn prefetch(ptr: *const u8, lines: usize) {
+    for line in 0..lines {
+        let address = ptr.wrapping_add(line * PREFETCH_STRIDE);
+        // SAFETY: prefetching is only a hint and never faults, whatever the address.
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch::<_MM_HINT_T0>(address.cast());
         }
-        assert_eq!(pool.pool.available(), 1);
-        assert!(pool.pool.len() > 0);
-        {
-            // Create 10 objects
-            let mut objs = Vec::new();
-            for _ in 0..10 {
-                objs.push(pool.get());
-            }
-            assert_eq!(pool.pool.available(), 0);
-            assert_eq!(pool.pool.len(), 10);
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            std::arch::asm!("prfm pldl1keep, [{}]", in(reg) address, options(nostack, readonly, preserves_flags));
         }
-        assert_eq!(pool.pool.available(), 10);
-        assert_eq!(pool.pool.len(), 10);
-        pool.pool.clear();
-        assert_eq!(pool.pool.available(), 0);
-        assert_eq!(pool.pool.len(), 0);
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        let _ = address;
+    }
+}
 
-        let mut obj = pool.get();
-        obj.ref_mut_item().value = 20;
-        assert_eq!(obj.ref_item().value, 20);
-        let item = obj.extract();
-        assert_eq!(obj.item, std::ptr::null_mut());
-        assert!(item != std::ptr::null_mut());
-        unsafe{ assert!((*item).value == 20); }
+/// Number of idle objects an ObjectPool keeps track of without allocating.
+pub(crate) const INLINE_IDLE: usize = 8;
+
+/// List of idle objects of an ObjectPool.
+type IdleList<T> = InlineDeque<NonNull<T>, INLINE_IDLE>;
+
+/// Number of objects an ObjectPool tracks without allocating.
+pub(crate) const INLINE_ITEMS: usize = 8;
+
+/// Set of the objects an ObjectPool tracks in the current generation.
+type ItemSet<T> = InlineSet<NonNull<T>, INLINE_ITEMS>;
+
+/// Raw variant of ObjectPool that allows manual get and release of items.
+///
+/// Aliasing invariants:
+/// - Every object lives in an `Entry` inside a `Slab` allocated from the global allocator, so the pool
+///   only ever holds raw `NonNull` pointers and never a `Box` that could be retagged (and thereby
+///   invalidate outstanding pointers) when the vectors move or reallocate. Entries never move within
+///   their slab, and each keeps its slab alive until it is freed.
+/// - `items` tracks every allocation of the current generation. Clearing or dropping the pool only
+///   frees available objects: objects that are still handed out are left to their holder, who
+///   frees them with `ObjectPool::destroy`.
+/// - `available` holds the objects that are not handed out. No reference into an object is
+///   created by the pool except while it is exclusively owned by the pool (on reserve and release).
+/// - An object appears in `available` at most once, so two holders never share one object.
+///   The `in_use` flag of its entry is set exactly while it is handed out. The pool only accesses
+///   the flag through the raw entry pointer, never through a reference that covers the value.
+pub struct ObjectPool<T> {
+    /// Objects of the current generation. The first `INLINE_ITEMS` are kept inline, so tracking the objects of
+    /// a small pool needs no allocation.
+    items: ItemSet<T>,
+    /// Idle objects, from the least to the most recently released. The first `INLINE_IDLE` are kept inline,
+    /// so the list of a small pool needs no allocation.
+    available: IdleList<T>,
+    generation: u64,
+    high_water_mark: usize,
+    factory: Factory<T>,
+    reset: fn(&mut T),
+    /// Runs on every released item instead of `reset`.
+    custom_reset: Option<Hook<T>>,
+    /// Runs after `reset` or `custom_reset` on every released item.
+    extra_reset: Option<Hook<T>>,
+    /// Runs last on every released item; the item is discarded if it fails.
+    try_reset: Option<TryResetHook<T>>,
+    /// Whether a discarded item is replaced by a new one.
+    replace_discarded: bool,
+    reset_timing: ResetTiming,
+    reuse_order: ReuseOrder,
+    growth: Box<dyn GrowthPolicy>,
+    /// Set by `freeze`: the pool no longer creates objects.
+    frozen: bool,
+    size_hint: fn(&T) -> usize,
+    /// Sum of the size hints of the tracked objects.
+    bytes: usize,
+    /// Number of entries in each newly allocated slab, or None for the default of the entry size.
+    chunk_size: Option<usize>,
+    /// Minimum alignment of the entries in newly allocated slabs.
+    align: usize,
+    /// Number of cache lines of an object prefetched when it is handed out.
+    prefetch_lines: usize,
+    /// Slab whose slots are handed out to new objects, with the number of slots already handed out.
+    slab: Option<(NonNull<Slab>, usize)>,
+    allocator: SlabAllocator,
+    max_bytes: usize,
+    /// Set by `without_reset`: released items are pushed back without running any reset.
+    skip_reset: bool,
+    /// Set by `with_zeroize`: selects the bytes of a released item that are wiped before it is reset.
+    secret: Option<fn(&mut T) -> &mut [u8]>,
+    hooks: Hooks<T>,
+    /// Health check run on acquire, and on release if `validate_on_release` is set.
+    validate: Option<fn(&T) -> bool>,
+    validate_on_release: bool,
+    /// Objects handed out this many times are destroyed on release instead of being reused.
+    max_uses: Option<u64>,
+    /// Objects older than this are retired on acquire and by `maintain()`.
+    max_age: Option<Duration>,
+    max_size: usize,
+    exhausted: ExhaustedBehavior,
+    /// Released objects are destroyed instead of kept when this many objects are already idle.
+    max_idle: usize,
+    /// `maintain()` creates objects until this many are idle.
+    min_idle: usize,
+    /// `maintain()` evicts objects idle for longer than this, keeping `min_idle` of them.
+    idle_timeout: Option<Duration>,
+    leak_sink: Option<LeakSink>
+}
+
+/// Lifecycle hooks of an ObjectPool.
+struct Hooks<T> {
+    on_create: Option<Hook<T>>,
+    on_acquire: Option<Hook<T>>,
+    on_release: Option<Hook<T>>,
+    on_destroy: Option<Hook<T>>
+}
+
+/// How an ObjectPool creates its objects.
+enum Factory<T> {
+    /// `Poolable::new`.
+    Poolable(fn() -> T),
+    /// A closure given to `ObjectPool::with_factory`.
+    Closure(Box<dyn FnMut() -> T + Send>)
+}
+
+impl<T> Factory<T> {
+    fn create(&mut self) -> T {
+        match self {
+            Factory::Poolable(new) => new(),
+            Factory::Closure(factory) => factory()
+        }
+    }
+}
+
+// SAFETY: the pool owns its items, and every callback it stores is `Send`.
+unsafe impl<T: Send> Send for ObjectPool<T> {}
+
+impl<T: Poolable> Default for ObjectPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> ObjectPool<T> {
+    /// Create a new ObjectPool whose objects are created with `Poolable::new` and reset with `Poolable::reset`.
+    pub fn new() -> ObjectPool<T> {
+        ObjectPool::create(Factory::Poolable(T::new), T::reset).with_size_hint(T::size_hint)
+    }
+
+    /// Create a new ObjectPool with `capacity` objects constructed up front.
+    #[track_caller]
+    pub fn with_capacity(capacity: usize) -> ObjectPool<T> {
+        let mut pool = ObjectPool::new();
+        pool.reserve(capacity);
+        pool
+    }
+
+    /// Move up to `count` idle objects into a new pool, e.g. to hand part of a prewarmed pool to another thread.
+    /// The new pool has default settings, like one created with `ObjectPool::new`.
+    pub fn split(&mut self, count: usize) -> ObjectPool<T> {
+        let mut pool = ObjectPool::new();
+        pool.adopt_idle(self.detach_idle(count));
+        pool
+    }
+}
+
+impl<T: TryPoolable> ObjectPool<T>
+where
+    T::Error: fmt::Debug
+{
+    /// Create a new ObjectPool whose objects are created with `TryPoolable::try_new` and reset with
+    /// `TryPoolable::reset`. Use `try_get` to handle construction failures: `get` panics on them.
+    pub fn new_fallible() -> ObjectPool<T> {
+        fn create<T: TryPoolable>() -> T
+        where
+            T::Error: fmt::Debug
+        {
+            T::try_new().expect("failed to create pool item")
+        }
+        ObjectPool::create(Factory::Poolable(create::<T>), T::reset)
     }
-}
\ No newline at end of file
+}
+
+impl<T> ObjectPool<T> {
+    fn create(factory: Factory<T>, reset: fn(&mut T)) -> ObjectPool<T> {
+        ObjectPool {
+            items: InlineSet::new(),
+            available: InlineDeque::new(),
+            generation: 0,
+            high_water_mark: 0,
+            factory,
+            reset,
+            custom_reset: None,
+            extra_reset: None,
+            try_reset: None,
+            replace_discarded: false,
+            reset_timing: ResetTiming::OnRelease,
+            reuse_order: ReuseOrder::Lifo,
+            growth: Box::new(GrowByOne),
+            frozen: false,
+            size_hint: |_| mem::size_of::<T>(),
+            bytes: 0,
+            chunk_size: None,
+            align: 1,
+            prefetch_lines: 0,
+            slab: None,
+            allocator: SlabAllocator::default(),
+            max_bytes: usize::MAX,
+            skip_reset: false,
+            secret: None,
+            hooks: Hooks {
+                on_create: None,
+                on_acquire: None,
+                on_release: None,
+                on_destroy: None
+            },
+            validate: None,
+            validate_on_release: false,
+            max_uses: None,
+            max_age: None,
+            max_size: usize::MAX,
+            exhausted: ExhaustedBehavior::Grow,
+            max_idle: usize::MAX,
+            min_idle: 0,
+            idle_timeout: None,
+            leak_sink: None
+        }
+    }
+
+    /// Create a new ObjectPool whose objects are created by `factory`, which can capture context such as a config.
+    /// This works for any type, including types that do not implement Poolable; released objects are not reset.
+    pub fn with_factory<F: FnMut() -> T + Send + 'static>(factory: F) -> ObjectPool<T> {
+        ObjectPool::create(Factory::Closure(Box::new(factory)), |_| {})
+    }
+
+    /// Create a new ObjectPool whose objects are created with `PoolableWith::new_with` and reset with
+    /// `PoolableWith::reset_with`, both given `params`.
+    pub fn with_params(params: T::Params) -> ObjectPool<T>
+    where
+        T: PoolableWith,
+        T::Params: Send + Sync + 'static
+    {
+        let params = Arc::new(params);
+        let reset_params = Arc::clone(&params);
+        ObjectPool::with_factory(move || T::new_with(&params)).with_reset(move |item| item.reset_with(&reset_params))
+    }
+
+    /// Reset released objects with `reset` instead of `Poolable::reset`.
+    pub fn with_reset<F: FnMut(&mut T) + Send + 'static>(mut self, reset: F) -> ObjectPool<T> {
+        self.custom_reset = Some(Box::new(reset));
+        self.skip_reset = false;
+        self
+    }
+
+    /// Never reset released objects, for types whose state is fully overwritten on use.
+    /// Releasing then only pushes the object onto the free list. Setting any reset afterwards turns resetting back on.
+    pub fn without_reset(mut self) -> ObjectPool<T> {
+        self.skip_reset = true;
+        self
+    }
+
+    /// Run `reset` on released objects after `Poolable::reset`, or after the reset given to `with_reset`,
+    /// for cleanup specific to this pool.
+    pub fn with_extra_reset<F: FnMut(&mut T) + Send + 'static>(mut self, reset: F) -> ObjectPool<T> {
+        self.extra_reset = Some(Box::new(reset));
+        self.skip_reset = false;
+        self
+    }
+
+    /// Run `try_reset` on released objects after every other reset. An object whose `try_reset` fails is
+    /// destroyed instead of being recycled, so objects in an unrecoverable state never get handed out again.
+    pub fn with_try_reset<E, F: FnMut(&mut T) -> Result<(), E> + Send + 'static>(mut self, mut try_reset: F) -> ObjectPool<T> {
+        self.try_reset = Some(Box::new(move |item| try_reset(item).is_ok()));
+        self.skip_reset = false;
+        self
+    }
+
+    /// Replace every object discarded by a failed `try_reset` with a new one, keeping the pool size.
+    pub fn with_replace_discarded(mut self) -> ObjectPool<T> {
+        self.replace_discarded = true;
+        self
+    }
+
+    /// Choose whether released objects are reset on release or when they are acquired again.
+    pub fn with_reset_timing(mut self, timing: ResetTiming) -> ObjectPool<T> {
+        self.reset_timing = timing;
+        self
+    }
+
+    /// Check objects with `Validate::is_valid` before handing them out.
+    /// Invalid objects are destroyed and transparently replaced by fresh ones.
+    pub fn with_validation(mut self) -> ObjectPool<T>
+    where
+        T: Validate
+    {
+        self.validate = Some(T::is_valid);
+        self
+    }
+
+    /// Also check objects with `Validate::is_valid` when they are released, destroying invalid ones right away.
+    pub fn with_release_validation(mut self) -> ObjectPool<T>
+    where
+        T: Validate
+    {
+        self.validate_on_release = true;
+        self.with_validation()
+    }
+
+    /// Destroy objects on release once they have been handed out `max_uses` times, so state that drifts
+    /// over many uses is flushed. Retired objects are replaced lazily, or right away with `with_replace_discarded`.
+    pub fn with_max_uses(mut self, max_uses: u64) -> ObjectPool<T> {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    /// Retire objects older than `max_age`, e.g. sessions whose credentials need periodic rotation.
+    /// Expired objects are destroyed when they would be handed out or by `maintain()`, and recreated lazily.
+    pub fn with_max_age(mut self, max_age: Duration) -> ObjectPool<T> {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Hold at most `max_size` objects, applying `behavior` when every one of them is in use.
+    /// `reserve` stops at the maximum size, while `adopt` may exceed it.
+    pub fn with_max_size(mut self, max_size: usize, behavior: ExhaustedBehavior) -> ObjectPool<T> {
+        self.max_size = max_size;
+        self.exhausted = behavior;
+        self
+    }
+
+    /// Choose which idle object is handed out next. The default is `ReuseOrder::Lifo`.
+    pub fn with_reuse_order(mut self, order: ReuseOrder) -> ObjectPool<T> {
+        self.reuse_order = order;
+        self
+    }
+
+    /// Choose how many objects are created when `get()` finds no idle object. The default is `GrowByOne`.
+    /// Objects created beyond the one handed out become idle, and the maximum size is never exceeded.
+    pub fn with_growth_policy<P: GrowthPolicy + 'static>(mut self, policy: P) -> ObjectPool<T> {
+        self.growth = Box::new(policy);
+        self
+    }
+
+    /// Keep at most `max_idle` objects idle: an object released while the idle list is full is destroyed.
+    /// This bounds the memory held between bursts without limiting how many objects can be in use.
+    pub fn with_max_idle(mut self, max_idle: usize) -> ObjectPool<T> {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Let `maintain()` keep at least `min_idle` objects idle, creating them ahead of demand so requests after a
+    /// quiet period do not pay for construction.
+    pub fn with_min_idle(mut self, min_idle: usize) -> ObjectPool<T> {
+        self.min_idle = min_idle;
+        self
+    }
+
+    /// Let `maintain()` evict objects that have been idle for longer than `timeout`.
+    /// Eviction never goes below the `min_idle` floor.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> ObjectPool<T> {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Cap the pool by the total size hint of its objects instead of their number, for objects whose sizes vary
+    /// widely such as buffers. A miss only creates an object while the total is below `max_bytes`, and a released
+    /// object is destroyed instead of kept while the total is above it. Sizes are measured on creation and release.
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> ObjectPool<T> {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Allocate objects in slabs of `chunk_size`, instead of the default of up to 64 objects per page.
+    /// A slab is only freed once all of its objects are, so larger slabs mean fewer allocations and better locality,
+    /// but shrinking the pool returns less memory. A chunk size of 1 allocates every object on its own.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> ObjectPool<T> {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    /// Prefetch the first `lines` cache lines of every object handed out, at most as many as the object covers,
+    /// so that the first accesses of the caller do not miss the cache on objects that have gone cold while idle.
+    /// The default of 0 prefetches nothing.
+    pub fn with_prefetch(mut self, lines: usize) -> ObjectPool<T> {
+        self.prefetch_lines = lines.min(mem::size_of::<T>().div_ceil(PREFETCH_STRIDE));
+        self
+    }
+
+    /// Pad and align every new object to its own cache line, so threads working on different objects do not
+    /// slow each other down by writing to the same line. Costs memory for small objects, which are otherwise
+    /// packed next to each other. Objects created before keep their place.
+    pub fn with_cache_line_padding(self) -> ObjectPool<T> {
+        self.with_alignment(CACHE_LINE)
+    }
+
+    /// Align every new object to at least `align` bytes, e.g. 64 for SIMD buffers or 4096 for page aligned
+    /// O_DIRECT and DMA buffers. Objects are padded to a multiple of the alignment, so by default a slab holds
+    /// as many as fit in a page, but at least one. Objects created before keep their place.
+    ///
+    /// # Panics
+    /// Panics if `align` is not a power of two.
+    pub fn with_alignment(mut self, align: usize) -> ObjectPool<T> {
+        assert!(align.is_power_of_two(), "alignment {} is not a power of two", align);
+        self.retire_slab();
+        self.align = self.align.max(align);
+        self
+    }
+
+    /// Allocate the slabs holding new objects from `allocator`, e.g. a jemalloc arena, a DMA-capable allocator or
+    /// a tracking allocator. Objects created before keep the memory they came from, and every slab is freed by the
+    /// allocator it came from. Requires the `allocator_api` feature and a nightly compiler.
+    #[cfg(feature = "allocator_api")]
+    pub fn with_allocator<A: Allocator + Send + Sync + 'static>(mut self, allocator: A) -> ObjectPool<T> {
+        self.retire_slab();
+        self.allocator.custom = Some(Arc::new(allocator));
+        #[cfg(target_os = "linux")]
+        {
+            self.allocator.huge_pages = None;
+        }
+        self
+    }
+
+    /// Back the slabs holding new objects with huge pages, which cuts TLB misses for pools of large buffers.
+    /// Every slab takes up whole huge pages of 2 MiB, so by default a slab holds as many objects as fit in one.
+    /// Replaces an allocator set with `with_allocator`. Objects created before keep the memory they came from.
+    #[cfg(target_os = "linux")]
+    pub fn with_huge_pages(mut self, huge_pages: HugePages) -> ObjectPool<T> {
+        self.retire_slab();
+        self.allocator.huge_pages = Some(huge_pages);
+        #[cfg(feature = "allocator_api")]
+        {
+            self.allocator.custom = None;
+        }
+        self
+    }
+
+    /// Give the memory of every destroyed object back to the kernel with `madvise(MADV_DONTNEED)`, so shrinking
+    /// or evicting idle objects lowers the resident set size even while other objects keep their slab alive.
+    /// Only the pages an object covers entirely are released, so this pays off for objects of a page or more,
+    /// such as large inline buffers. The memory must come from private anonymous mappings, as it does from
+    /// the global allocator and with huge pages. Objects created before keep their behavior.
+    #[cfg(target_os = "linux")]
+    pub fn with_decommit(mut self) -> ObjectPool<T> {
+        self.retire_slab();
+        self.allocator.decommit = true;
+        self
+    }
+
+    /// Keep new objects in memory that is locked with `mlock`, so it is never swapped out, and excluded from core
+    /// dumps, for pools holding key material or session tokens. The memory of an object is overwritten with zeros
+    /// when it is destroyed or moved out of the pool. Memory on the heap owned by an object, such as the buffer
+    /// of a `Vec`, is not covered, and idle objects keep their contents unless `with_zeroize` wipes their secret.
+    /// Objects created before keep the memory they came from.
+    ///
+    /// Locked memory counts against `RLIMIT_MEMLOCK`; creating an object fails like an allocation failure once
+    /// the limit is reached.
+    #[cfg(target_os = "linux")]
+    pub fn with_secure_memory(mut self) -> ObjectPool<T> {
+        self.retire_slab();
+        self.allocator.secure = true;
+        self
+    }
+
+    /// Overwrite the bytes that `secret` selects with zeros whenever an object is released, right after the
+    /// `on_release` hook and before it is reset or goes back to the idle objects. Unlike stores in a reset, the
+    /// volatile writes are never optimized away, so secrets such as keys do not linger in idle objects.
+    /// The selected bytes may also live on the heap, like the contents of a `Vec<u8>`.
+    pub fn with_zeroize(mut self, secret: fn(&mut T) -> &mut [u8]) -> ObjectPool<T> {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Wipe the bytes of a released item selected by `with_zeroize`.
+    ///
+    /// # Safety
+    /// `ptr` must belong to the pool and must not be used by anyone else.
+    unsafe fn zeroize(&mut self, mut ptr: NonNull<T>) {
+        if let Some(secret) = self.secret {
+            let bytes = secret(unsafe { ptr.as_mut() });
+            unsafe {
+                wipe(bytes.as_mut_ptr(), bytes.len());
+            }
+        }
+    }
+
+    /// Measure objects with `size_hint` instead of `Poolable::size_hint`, e.g. for pools created with a factory.
+    pub fn with_size_hint(mut self, size_hint: fn(&T) -> usize) -> ObjectPool<T> {
+        self.size_hint = size_hint;
+        self
+    }
+
+    /// Stop creating objects, e.g. after a warm-up phase on a path that must never allocate.
+    /// From now on a miss fails with `PoolError::Frozen`, and `reserve`, `maintain` and replacements of discarded
+    /// objects create nothing. Objects still go back to the pool and are destroyed as configured.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// Let the pool create objects again after `freeze`.
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// Check whether the pool is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Check whether a new object may be created.
+    fn has_room(&self) -> bool {
+        self.can_grow(self.growth_limit())
+    }
+
+    /// Get the number of objects the pool may grow to on a miss.
+    fn growth_limit(&self) -> usize {
+        match self.exhausted {
+            ExhaustedBehavior::Grow => usize::MAX,
+            _ => self.max_size
+        }
+    }
+
+    /// Check whether the growth policy would create an object without holding more than `max_size` objects.
+    pub(crate) fn can_grow(&self, max_size: usize) -> bool {
+        !self.frozen && self.bytes < self.max_bytes && self.items.len() < max_size && self.growth.grow_by(self.items.len()) > 0
+    }
+
+    /// Create as many objects as the growth policy decides, without holding more than `max_size` objects.
+    /// Returns one of them and makes the rest available, or None if the pool does not grow.
+    #[track_caller]
+    fn grow(&mut self, max_size: usize) -> Option<NonNull<T>> {
+        let room = max_size.saturating_sub(self.items.len());
+        let count = self.growth.grow_by(self.items.len()).min(room);
+        if count == 0 || self.frozen || self.bytes >= self.max_bytes {
+            return None;
+        }
+        self.items.reserve(count);
+        self.available.reserve(count - 1);
+        let ptr = self.allocate();
+        for _ in 1..count {
+            if self.bytes >= self.max_bytes {
+                break;
+            }
+            let extra = self.allocate();
+            self.available.push_back(extra);
+        }
+        Some(ptr)
+    }
+
+    /// Run `hook` on every newly created object, e.g. to warm caches inside it.
+    pub fn with_on_create<F: FnMut(&mut T) + Send + 'static>(mut self, hook: F) -> ObjectPool<T> {
+        self.hooks.on_create = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` on every object right before it is handed out.
+    pub fn with_on_acquire<F: FnMut(&mut T) + Send + 'static>(mut self, hook: F) -> ObjectPool<T> {
+        self.hooks.on_acquire = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` on every released object before it is reset, e.g. to scrub sensitive fields.
+    pub fn with_on_release<F: FnMut(&mut T) + Send + 'static>(mut self, hook: F) -> ObjectPool<T> {
+        self.hooks.on_release = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` on every object the pool drops, whether it is cleared, discarded, or dropped with the pool.
+    /// Guards that outlived a `clear()` run it when they free their object; objects outliving the pool itself do not.
+    pub fn with_on_destroy<F: FnMut(&mut T) + Send + 'static>(mut self, hook: F) -> ObjectPool<T> {
+        self.hooks.on_destroy = Some(Box::new(hook));
+        self
+    }
+
+    /// Run the hook chosen by `select` on an item owned by the pool.
+    /// If the hook panics, the item is removed from the pool and dropped before the panic continues.
+    ///
+    /// # Safety
+    /// `ptr` must belong to the pool and must not be in the available list or be used by anyone else.
+    unsafe fn run_hook(&mut self, select: fn(&mut Hooks<T>) -> &mut Option<Hook<T>>, mut ptr: NonNull<T>) {
+        let Some(hook) = select(&mut self.hooks) else {
+            return;
+        };
+        let item = unsafe { ptr.as_mut() };
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| hook(item))) {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Drop an item that is owned by the pool alone and no longer tracked, running `on_destroy` first.
+    ///
+    /// # Safety
+    /// `ptr` must not be in `items` or `available`, and must not be used by anyone else.
+    unsafe fn discard(&mut self, mut ptr: NonNull<T>) {
+        let result = match &mut self.hooks.on_destroy {
+            Some(hook) => panic::catch_unwind(AssertUnwindSafe(|| hook(unsafe { ptr.as_mut() }))),
+            None => Ok(())
+        };
+        unsafe {
+            ObjectPool::destroy(ptr.as_ptr());
+        }
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Allocate a new object owned by the pool.
+    #[track_caller]
+    fn allocate(&mut self) -> NonNull<T> {
+        let value = self.factory.create();
+        self.insert_created(value)
+    }
+
+    /// Create an object like `allocate`, but return `ReserveError::Alloc` instead of aborting if its entry
+    /// cannot be allocated.
+    #[track_caller]
+    fn try_allocate(&mut self) -> Result<NonNull<T>, ReserveError> {
+        let mut value = self.factory.create();
+        if let Some(hook) = &mut self.hooks.on_create {
+            hook(&mut value);
+        }
+        self.try_insert(value).map_err(ReserveError::Alloc)
+    }
+
+    /// Run `on_create` on an object the pool just created and move it into the pool.
+    #[track_caller]
+    fn insert_created(&mut self, mut value: T) -> NonNull<T> {
+        if let Some(hook) = &mut self.hooks.on_create {
+            hook(&mut value);
+        }
+        self.insert(value)
+    }
+
+    /// Move an object into the pool without making it available.
+    #[track_caller]
+    fn insert(&mut self, value: T) -> NonNull<T> {
+        self.try_insert(value).unwrap_or_else(|layout| alloc::handle_alloc_error(layout))
+    }
+
+    /// Stop handing out slots of the current slab, so the next object starts a new one.
+    fn retire_slab(&mut self) {
+        if let Some((slab, _)) = self.slab.take() {
+            // SAFETY: the pool owns one reference to its current slab.
+            unsafe {
+                Slab::release(slab);
+            }
+        }
+    }
+
+    /// Move an object into the pool like `insert`, or return the slab layout that failed to allocate.
+    #[track_caller]
+    fn try_insert(&mut self, value: T) -> Result<NonNull<T>, Layout> {
+        let (slab, used) = match self.slab {
+            Some(current) => current,
+            None => {
+                let capacity = self.chunk_size.unwrap_or_else(|| Slab::default_capacity::<T>(self.align, &self.allocator));
+                (Slab::allocate::<T>(capacity, self.align, &self.allocator)?, 0)
+            }
+        };
+        // SAFETY: the pool holds a reference to its current slab and hands out each slot once.
+        let entry = unsafe { Slab::claim::<T>(slab, used) };
+        unsafe {
+            entry.as_ptr().write(Entry::new(value, slab));
+        }
+        self.slab = Some((slab, used + 1));
+        if used + 1 == unsafe { slab.as_ref() }.capacity {
+            self.retire_slab();
+        }
+        let ptr = entry.cast::<T>();
+        self.track(ptr);
+        self.measure(ptr);
+        Ok(ptr)
+    }
+
+    /// Start tracking an item as part of the current generation.
+    fn track(&mut self, ptr: NonNull<T>) {
+        self.items.insert(ptr);
+        // SAFETY: the entry is owned by the pool.
+        self.bytes += unsafe { (*Entry::of(ptr)).bytes };
+    }
+
+    /// Stop tracking an item, e.g. before it is destroyed or taken out of the pool.
+    fn untrack(&mut self, ptr: NonNull<T>) {
+        if self.items.remove(&ptr) {
+            // SAFETY: the entry is still live, and the caller destroys or hands it on afterwards.
+            self.bytes -= unsafe { (*Entry::of(ptr)).bytes };
+        }
+    }
+
+    /// Update the recorded size of a tracked item that the pool owns exclusively.
+    fn measure(&mut self, ptr: NonNull<T>) {
+        let entry = Entry::of(ptr);
+        // SAFETY: the item is not handed out, so nobody else accesses it.
+        unsafe {
+            let bytes = (self.size_hint)(&(*entry).value);
+            self.bytes = self.bytes - (*entry).bytes + bytes;
+            (*entry).bytes = bytes;
+        }
+    }
+
+    /// Reserve a number of items in the pool.
+    /// The bookkeeping is grown once up front, so it does not reallocate while the items are created.
+    #[track_caller]
+    pub fn reserve(&mut self, count: usize) {
+        if self.frozen {
+            return;
+        }
+        let count = count.min(self.max_size.saturating_sub(self.items.len()));
+        self.items.reserve(count);
+        self.available.reserve(count);
+        for _ in 0..count {
+            let ptr = self.allocate();
+            self.available.push_back(ptr);
+        }
+    }
+
+    /// Reserve a number of items like `reserve`, but return an error instead of aborting if memory runs out,
+    /// e.g. to start up degraded under memory pressure.
+    #[track_caller]
+    pub fn try_reserve(&mut self, count: usize) -> Result<(), ReserveError> {
+        if self.frozen {
+            return Ok(());
+        }
+        let count = count.min(self.max_size.saturating_sub(self.items.len()));
+        self.items.try_reserve(count)?;
+        self.available.try_reserve(count)?;
+        for _ in 0..count {
+            let ptr = self.try_allocate()?;
+            self.available.push_back(ptr);
+        }
+        Ok(())
+    }
+
+    /// Take ownership of an object constructed outside the pool, e.g. from deserialized state, and make it available.
+    /// The object is moved into an allocation of the pool. It is taken as is: `on_create` does not run and it is not reset.
+    // Taking a Box is the point: callers hand over objects they already allocated.
+    #[allow(clippy::boxed_local)]
+    #[track_caller]
+    pub fn adopt(&mut self, item: Box<T>) {
+        self.adopt_value(*item);
+    }
+
+    /// Take ownership of an object constructed outside the pool and make it available, like `adopt`.
+    #[track_caller]
+    pub fn adopt_value(&mut self, value: T) {
+        let ptr = self.insert(value);
+        self.available.push_back(ptr);
+    }
+
+    /// Get an item from the pool.
+    /// The caller location is recorded for leak reports.
+    ///
+    /// Panics if no item is available and the pool cannot grow, because it is at its maximum size or its growth
+    /// policy does not grow; use `checked_get` to handle that.
+    #[inline]
+    #[track_caller]
+    pub fn get(&mut self) -> *mut T {
+        match self.checked_get() {
+            Ok(item) => item,
+            Err(PoolError::Frozen) => panic!("pool is frozen: all {} items are in use", self.len()),
+            Err(_) => panic!("pool is exhausted: all {} items are in use", self.len())
+        }
+    }
+
+    /// Get an item from the pool, or `PoolError::Exhausted` if every item is in use and the pool cannot grow.
+    #[inline]
+    #[track_caller]
+    pub fn checked_get(&mut self) -> Result<*mut T, PoolError> {
+        match self.get_within(self.growth_limit()) {
+            Some(item) => Ok(item),
+            None if self.frozen => Err(PoolError::Frozen),
+            None => Err(PoolError::Exhausted)
+        }
+    }
+
+    /// Get an item from the pool, growing it if no item is available but never past `max_size` items.
+    #[inline]
+    #[track_caller]
+    pub(crate) fn get_within(&mut self, max_size: usize) -> Option<*mut T> {
+        let ptr = match self.pop_available() {
+            Some(ptr) => ptr,
+            None => self.grow(max_size)?
+        };
+        Some(self.hand_out(ptr))
+    }
+
+    /// Get `n` items like `get_within`, or None without handing out any if the pool cannot provide all of them.
+    /// Nothing is handed out before all `n` items are secured, so a failed attempt runs no hooks and leaves the
+    /// use counts and the high-water mark alone. The idle items it took go back in their previous order.
+    #[track_caller]
+    pub(crate) fn get_n_within(&mut self, n: usize, max_size: usize) -> Option<Vec<*mut T>> {
+        let mut secured = Vec::with_capacity(n);
+        while secured.len() < n {
+            match self.pop_available().or_else(|| self.grow(max_size)) {
+                Some(ptr) => secured.push(ptr),
+                None => {
+                    for ptr in secured.into_iter().rev() {
+                        match self.reuse_order {
+                            ReuseOrder::Lifo => self.available.push_back(ptr),
+                            ReuseOrder::Fifo => self.available.push_front(ptr)
+                        }
+                    }
+                    return None;
+                }
+            }
+        }
+        Some(secured.into_iter().map(|ptr| self.hand_out(ptr)).collect())
+    }
+
+    /// Get an item from the pool, creating it with `TryPoolable::try_new` if none is available.
+    /// Construction failures are returned as `PoolError::Create`.
+    #[track_caller]
+    pub fn try_get(&mut self) -> Result<*mut T, PoolError<T::Error>>
+    where
+        T: TryPoolable
+    {
+        self.try_get_with(T::try_new)
+    }
+
+    /// Get an item from the pool, creating it with `create` if none is available.
+    /// Construction failures are returned as `PoolError::Create`. Only that one item is created, whatever
+    /// the growth policy, but a policy that does not grow still makes this return `PoolError::Exhausted`.
+    #[track_caller]
+    pub fn try_get_with<E, F: FnOnce() -> Result<T, E>>(&mut self, create: F) -> Result<*mut T, PoolError<E>> {
+        let ptr = match self.pop_available() {
+            Some(ptr) => ptr,
+            None if self.frozen => return Err(PoolError::Frozen),
+            None if self.has_room() => self.insert_created(create().map_err(PoolError::Create)?),
+            None => return Err(PoolError::Exhausted)
+        };
+        Ok(self.hand_out(ptr))
+    }
+
+    /// Detach up to `count` idle items from the pool so they can be moved to another pool with `adopt_idle`.
+    pub(crate) fn detach_idle(&mut self, count: usize) -> Vec<NonNull<T>> {
+        let items = self.available.split_back(count);
+        for &item in &items {
+            self.untrack(item);
+        }
+        items
+    }
+
+    /// Take ownership of idle items detached from another pool.
+    pub(crate) fn adopt_idle(&mut self, items: Vec<NonNull<T>>) {
+        for item in items {
+            self.track(item);
+            self.available.push_back(item);
+        }
+    }
+
+    /// Move every idle object of `other` into this pool, returning how many were moved.
+    /// Objects `other` has handed out stay with it.
+    pub fn merge(&mut self, other: &mut ObjectPool<T>) -> usize {
+        let items = other.detach_idle(other.available());
+        let count = items.len();
+        self.adopt_idle(items);
+        count
+    }
+
+    /// Get an idle item from the pool, or None if no item is idle. Unlike `get`, this never allocates,
+    /// so creating an item is always an explicit call.
+    #[inline]
+    #[track_caller]
+    pub fn try_get_idle(&mut self) -> Option<*mut T> {
+        let ptr = self.pop_available()?;
+        Some(self.hand_out(ptr))
+    }
+
+    /// Hand out the next idle item like `try_get_idle`, but without checking that one is idle, its maximum age
+    /// or its validity, for hot paths that have made sure the pool holds enough items, e.g. with `reserve`.
+    /// An item whose reset was deferred to acquisition is still reset, and if that discards it, this falls back
+    /// to `get`.
+    ///
+    /// # Safety
+    /// At least one item must be idle.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn get_unchecked(&mut self) -> *mut T {
+        let ptr = match self.reuse_order {
+            ReuseOrder::Lifo => self.available.pop_back(),
+            ReuseOrder::Fifo => self.available.pop_front()
+        };
+        // SAFETY: the caller guarantees that an item is idle.
+        let ptr = unsafe { ptr.unwrap_unchecked() };
+        // SAFETY: the entry is owned by the pool and the flag is outside the value.
+        if unsafe { mem::take(&mut (*Entry::of(ptr)).dirty) } {
+            match unsafe { self.reset_or_discard(ptr) } {
+                Ok(true) => {}
+                Ok(false) => return self.get(),
+                Err(payload) => panic::resume_unwind(payload)
+            }
+        }
+        self.hand_out(ptr)
+    }
+
+    /// Pop an available item, first resetting it if its reset was deferred to acquisition.
+    /// Items that are discarded by their reset or fail validation are skipped.
+    /// A panic in `reset()` continues after the item is dropped.
+    fn pop_available(&mut self) -> Option<NonNull<T>> {
+        let now = self.max_age.map(|_| Instant::now());
+        loop {
+            let ptr = match self.reuse_order {
+                ReuseOrder::Lifo => self.available.pop_back(),
+                ReuseOrder::Fifo => self.available.pop_front()
+            }?;
+            if now.is_some_and(|now| self.is_expired(ptr, now)) {
+                self.untrack(ptr);
+                unsafe {
+                    self.discard(ptr);
+                }
+                continue;
+            }
+            // SAFETY: the entry is owned by the pool and the flag is outside the value.
+            let dirty = unsafe { mem::take(&mut (*Entry::of(ptr)).dirty) };
+            if dirty {
+                match unsafe { self.reset_or_discard(ptr) } {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(payload) => panic::resume_unwind(payload)
+                }
+            }
+            if unsafe { self.is_valid(ptr) } {
+                return Some(ptr);
+            }
+        }
+    }
+
+    /// Check whether an item is older than the maximum age at `now`.
+    fn is_expired(&self, ptr: NonNull<T>, now: Instant) -> bool {
+        // SAFETY: entries tracked by the pool are live, and `created_at` is never written after creation.
+        let created_at = unsafe { (*Entry::of(ptr)).created_at };
+        self.max_age.is_some_and(|max_age| now.duration_since(created_at) >= max_age)
+    }
+
+    /// Retire idle objects older than the maximum age set with `with_max_age` or failing validation, evict objects
+    /// idle for longer than the idle timeout down to `min_idle`, then create objects until `min_idle` are idle,
+    /// as far as the maximum size allows.
+    /// Returns the number of objects retired or evicted.
+    #[track_caller]
+    pub fn maintain(&mut self) -> usize {
+        let retired = self.retire_expired();
+        self.fill_idle(self.max_size);
+        retired
+    }
+
+    /// Retire idle objects older than the maximum age or failing validation, and evict objects past the
+    /// idle timeout, returning how many were destroyed.
+    pub(crate) fn retire_expired(&mut self) -> usize {
+        if self.max_age.is_none() && self.idle_timeout.is_none() && self.validate.is_none() {
+            return 0;
+        }
+        let now = Instant::now();
+        let mut evictable = self.available.len().saturating_sub(self.min_idle);
+        let (expired, idle): (IdleList<T>, IdleList<T>) = mem::take(&mut self.available).into_iter().partition(|&ptr| {
+            // SAFETY: available objects are owned by the pool alone.
+            let invalid = self.validate.is_some_and(|validate| !validate(unsafe { ptr.as_ref() }));
+            if invalid || self.is_expired(ptr, now) {
+                return true;
+            }
+            let timed_out = evictable > 0 && self.is_timed_out(ptr, now);
+            evictable -= usize::from(timed_out);
+            timed_out
+        });
+        self.available = idle;
+        for ptr in expired.iter() {
+            self.untrack(ptr);
+            // SAFETY: available objects are owned by the pool alone.
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        expired.len()
+    }
+
+    /// Check whether an idle item has been idle for longer than the idle timeout at `now`.
+    fn is_timed_out(&self, ptr: NonNull<T>, now: Instant) -> bool {
+        // SAFETY: entries tracked by the pool are live, and `idle_since` is only written with the pool borrowed mutably.
+        let idle_since = unsafe { (*Entry::of(ptr)).idle_since };
+        self.idle_timeout.is_some_and(|timeout| now.duration_since(idle_since) >= timeout)
+    }
+
+    /// Create objects until `min_idle` are idle, without growing the pool past `max_size` objects.
+    /// Returns the number of objects created.
+    #[track_caller]
+    pub(crate) fn fill_idle(&mut self, max_size: usize) -> usize {
+        let room = if self.frozen { 0 } else { max_size.min(self.max_size).saturating_sub(self.items.len()) };
+        let missing = self.min_idle.saturating_sub(self.available.len()).min(room);
+        for _ in 0..missing {
+            let ptr = self.allocate();
+            self.available.push_back(ptr);
+        }
+        missing
+    }
+
+    /// Check an item with the configured validation, destroying it if it is invalid.
+    ///
+    /// # Safety
+    /// Same as `reset_or_discard`.
+    unsafe fn is_valid(&mut self, ptr: NonNull<T>) -> bool {
+        let valid = self.validate.is_none_or(|validate| validate(unsafe { ptr.as_ref() }));
+        if !valid {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        valid
+    }
+
+    /// Run `on_acquire` on an item and mark it as handed out.
+    #[inline]
+    #[track_caller]
+    fn hand_out(&mut self, ptr: NonNull<T>) -> *mut T {
+        prefetch(ptr.as_ptr().cast(), self.prefetch_lines);
+        // SAFETY: the entry is owned by the pool and the flag is outside the value.
+        unsafe {
+            self.run_hook(|hooks| &mut hooks.on_acquire, ptr);
+            let entry = Entry::of(ptr);
+            (*entry).in_use = true;
+            (*entry).uses += 1;
+            (*entry).acquired_at = Location::caller();
+        }
+        self.high_water_mark = self.high_water_mark.max(self.in_use());
+        ptr.as_ptr()
+    }
+
+    /// Release an item back to the pool.
+    ///
+    /// Panics if the item is released twice. Debug builds also panic if the item does not belong to the pool.
+    /// If `reset()` panics, the item is dropped and removed from the pool before the panic continues.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on this pool since the last `clear()`.
+    #[inline]
+    pub unsafe fn release(&mut self, item: *mut T) {
+        let ptr = NonNull::new(item).expect("released a null pointer to the pool");
+        debug_assert!(self.items.contains(&ptr), "released an item that does not belong to the pool");
+        let entry = Entry::of(ptr);
+        unsafe {
+            assert!((*entry).in_use, "released an item that is not in use");
+            (*entry).in_use = false;
+            (*entry).idle_since = Instant::now();
+            self.run_hook(|hooks| &mut hooks.on_release, ptr);
+            self.zeroize(ptr);
+        }
+        if self.max_bytes != usize::MAX {
+            self.measure(ptr);
+        }
+        if self.available.len() >= self.max_idle || self.bytes > self.max_bytes {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+            return;
+        }
+        if self.max_uses.is_some_and(|max_uses| unsafe { (*entry).uses } >= max_uses) {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+            self.replace();
+            return;
+        }
+        if self.validate_on_release && !unsafe { self.is_valid(ptr) } {
+            self.replace();
+            return;
+        }
+        if self.skip_reset {
+            self.available.push_back(ptr);
+            return;
+        }
+        if self.reset_timing == ResetTiming::OnAcquire {
+            unsafe {
+                (*entry).dirty = true;
+            }
+            self.available.push_back(ptr);
+            return;
+        }
+        match unsafe { self.reset_or_discard(ptr) } {
+            Ok(true) => self.available.push_back(ptr),
+            Ok(false) => self.replace(),
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+
+    /// Make an item available again without any of the checks of `release` and without resetting it, for hot
+    /// paths whose items are always left in their reset state. The `on_release` hook and `with_zeroize` still
+    /// run, but validation and the limits on idle items, sizes and uses are not applied.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on this pool since the last `clear()` and not released since.
+    #[inline]
+    pub unsafe fn release_unchecked(&mut self, item: *mut T) {
+        // SAFETY: the caller guarantees that the item is a handed out item of this pool.
+        let ptr = unsafe { NonNull::new_unchecked(item) };
+        let entry = Entry::of(ptr);
+        unsafe {
+            (*entry).in_use = false;
+            // Reading the clock is the most expensive part of a release, and only the idle timeout needs it.
+            if self.idle_timeout.is_some() {
+                (*entry).idle_since = Instant::now();
+            }
+            self.run_hook(|hooks| &mut hooks.on_release, ptr);
+            self.zeroize(ptr);
+        }
+        self.available.push_back(ptr);
+    }
+
+    /// Replace an item discarded by a failed `try_reset`, validation or use limit if the pool is configured to.
+    fn replace(&mut self) {
+        if self.replace_discarded && !self.frozen && self.available.len() < self.max_idle {
+            let ptr = self.allocate();
+            self.available.push_back(ptr);
+        }
+    }
+
+    /// Reset an item owned by the pool, returning whether it can be reused.
+    /// If `reset()` panics or `try_reset` fails, the item is removed from the pool and dropped.
+    /// A panic is returned as the error.
+    ///
+    /// # Safety
+    /// `ptr` must belong to the pool and must not be in the available list or be used by anyone else.
+    unsafe fn reset_or_discard(&mut self, mut ptr: NonNull<T>) -> thread::Result<bool> {
+        let (reset, custom, extra) = (self.reset, &mut self.custom_reset, &mut self.extra_reset);
+        let try_reset = &mut self.try_reset;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let item = unsafe { ptr.as_mut() };
+            match custom {
+                Some(custom) => custom(item),
+                None => reset(item)
+            }
+            if let Some(extra) = extra {
+                extra(item);
+            }
+            try_reset.as_mut().is_none_or(|try_reset| try_reset(item))
+        }));
+        if !matches!(result, Ok(true)) {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        result
+    }
+
+    /// Release an item back to the pool after checking that it is currently handed out by this pool.
+    pub fn try_release(&mut self, item: *mut T) -> Result<(), PoolError> {
+        let ptr = self.check_in_use(item)?;
+        // SAFETY: the item belongs to the current generation and is handed out.
+        unsafe {
+            self.release(ptr.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Remove a handed out item from the pool and return it by value.
+    /// Panics if the item is not in use.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on this pool since the last `clear()`,
+    /// and must not be used afterwards.
+    pub unsafe fn take(&mut self, item: *mut T) -> T {
+        let ptr = NonNull::new(item).expect("took a null pointer from the pool");
+        debug_assert!(self.items.contains(&ptr), "took an item that does not belong to the pool");
+        unsafe {
+            assert!((*Entry::of(ptr)).in_use, "took an item that is not in use");
+        }
+        self.untrack(ptr);
+        unsafe {
+            ObjectPool::unbox(item)
+        }
+    }
+
+    /// Remove an item handed out during `generation` from the pool and return it by value.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on this pool during `generation` and not released since.
+    pub(crate) unsafe fn take_from(&mut self, item: *mut T, generation: u64) -> T {
+        if generation == self.generation {
+            unsafe {
+                self.take(item)
+            }
+        } else {
+            unsafe {
+                ObjectPool::unbox(item)
+            }
+        }
+    }
+
+    /// Move an object that is no longer tracked by its pool out of its allocation.
+    ///
+    /// # Safety
+    /// Same as `destroy`.
+    unsafe fn unbox(item: *mut T) -> T {
+        unsafe {
+            Entry::into_value(item.cast::<Entry<T>>())
+        }
+    }
+
+    /// Check that `item` is currently handed out by this pool.
+    fn check_in_use(&self, item: *mut T) -> Result<NonNull<T>, PoolError> {
+        let ptr = NonNull::new(item).ok_or(PoolError::ForeignPointer)?;
+        if !self.items.contains(&ptr) {
+            return Err(PoolError::ForeignPointer);
+        }
+        // SAFETY: the item belongs to the pool, so its entry is alive.
+        if unsafe { !(*Entry::of(ptr)).in_use } {
+            return Err(PoolError::NotInUse);
+        }
+        Ok(ptr)
+    }
+
+    /// Release an item handed out during `generation`.
+    /// Items from an earlier generation no longer belong to the pool and are freed instead.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on this pool during `generation` and not released since.
+    pub(crate) unsafe fn release_from(&mut self, item: *mut T, generation: u64) {
+        if generation == self.generation {
+            unsafe {
+                self.release(item);
+            }
+        } else {
+            unsafe {
+                self.discard(NonNull::new_unchecked(item));
+            }
+        }
+    }
+
+    /// Get the reset that `release` runs when resetting is all it does besides making the item available,
+    /// so a cache in front of the pool may reset items itself and `restore_from` them later.
+    /// Returns None if releasing also runs hooks, validates, applies limits or defers the reset.
+    pub(crate) fn plain_reset(&self) -> Option<fn(&mut T)> {
+        let plain = self.custom_reset.is_none() && self.extra_reset.is_none() && self.try_reset.is_none()
+            && self.hooks.on_release.is_none() && self.secret.is_none() && !self.validate_on_release && self.max_uses.is_none() && self.max_idle == usize::MAX
+            && self.max_bytes == usize::MAX && self.reset_timing == ResetTiming::OnRelease;
+        match (plain, self.skip_reset) {
+            (false, _) => None,
+            (true, true) => Some(|_| {}),
+            (true, false) => Some(self.reset)
+        }
+    }
+
+    /// Return an item handed out during `generation` that the caller has already reset.
+    /// Items from an earlier generation no longer belong to the pool and are freed instead.
+    ///
+    /// # Safety
+    /// Same as `release_from`.
+    pub(crate) unsafe fn restore_from(&mut self, item: *mut T, generation: u64) {
+        if generation != self.generation {
+            unsafe {
+                self.discard(NonNull::new_unchecked(item));
+            }
+            return;
+        }
+        let ptr = NonNull::new(item).expect("released a null pointer to the pool");
+        debug_assert!(self.items.contains(&ptr), "released an item that does not belong to the pool");
+        let entry = Entry::of(ptr);
+        unsafe {
+            assert!((*entry).in_use, "released an item that is not in use");
+            (*entry).in_use = false;
+            (*entry).idle_since = Instant::now();
+            self.run_hook(|hooks| &mut hooks.on_release, ptr);
+            self.zeroize(ptr);
+        }
+        self.available.push_back(ptr);
+    }
+
+    /// Clear the pool completely and start a new generation.
+    /// Items that are still handed out are no longer tracked by the pool;
+    /// guards acquired before the clear free their item when dropped.
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.items.clear();
+        self.bytes = 0;
+        for ptr in mem::take(&mut self.available) {
+            // SAFETY: available objects are owned by the pool alone.
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+    }
+
+    /// Free an object that is no longer tracked by its pool.
+    ///
+    /// # Safety
+    /// `item` must have been returned by `get()` on a pool that has since been cleared or dropped,
+    /// and must not be used afterwards.
+    pub(crate) unsafe fn destroy(item: *mut T) {
+        unsafe {
+            Entry::free(item.cast::<Entry<T>>());
+        }
+    }
+
+    /// Clear the pool completely, but only if no items are handed out.
+    pub fn try_clear(&mut self) -> Result<(), PoolError> {
+        match self.in_use() {
+            0 => {
+                self.clear();
+                Ok(())
+            }
+            count => Err(PoolError::InUse(count))
+        }
+    }
+
+    /// Free every available item, leaving the handed out items untouched.
+    /// Returns the number of items freed.
+    pub fn clear_idle(&mut self) -> usize {
+        let idle: Vec<NonNull<T>> = mem::take(&mut self.available).into_iter().collect();
+        for &ptr in &idle {
+            self.untrack(ptr);
+        }
+        for &ptr in idle.iter() {
+            // SAFETY: available objects are owned by the pool alone.
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        idle.len()
+    }
+
+    /// Free every idle item for which `keep` returns false, leaving handed out items untouched.
+    /// Returns the number of items freed.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) -> usize {
+        // SAFETY: available objects are owned by the pool alone.
+        let (kept, dropped): (IdleList<T>, IdleList<T>) =
+            mem::take(&mut self.available).into_iter().partition(|ptr| keep(unsafe { ptr.as_ref() }));
+        self.available = kept;
+        for ptr in dropped.iter() {
+            self.untrack(ptr);
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        dropped.len()
+    }
+
+    /// Free idle items until at most `idle` are left, and release the memory the pool no longer needs for
+    /// its bookkeeping. The least recently released items are freed first.
+    /// Returns the number of items freed.
+    pub fn shrink_to(&mut self, idle: usize) -> usize {
+        let excess = self.available.len().saturating_sub(idle);
+        let freed = self.available.drain_front(excess);
+        for &ptr in &freed {
+            self.untrack(ptr);
+            // SAFETY: available objects are owned by the pool alone.
+            unsafe {
+                self.discard(ptr);
+            }
+        }
+        self.available.shrink_to_fit();
+        self.items.shrink_to_fit();
+        freed.len()
+    }
+
+    /// Free every idle item and release the memory the pool no longer needs, like `shrink_to(0)`.
+    /// Returns the number of items freed.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        self.shrink_to(0)
+    }
+
+    /// Release all items back to the pool.
+    /// Items whose `reset()` panics are dropped; the first panic continues once every item is handled.
+    ///
+    /// # Safety
+    /// No pointer previously returned by `get()` may be used after this call.
+    pub unsafe fn release_all(&mut self) {
+        self.available.clear();
+        let items: Vec<NonNull<T>> = self.items.iter().collect();
+        let mut first_panic = None;
+        for ptr in items {
+            let released = unsafe {
+                (*Entry::of(ptr)).dirty = false;
+                mem::take(&mut (*Entry::of(ptr)).in_use)
+            };
+            if released {
+                unsafe {
+                    (*Entry::of(ptr)).idle_since = Instant::now();
+                }
+                let hook = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+                    self.run_hook(|hooks| &mut hooks.on_release, ptr);
+                    self.zeroize(ptr);
+                }));
+                if let Err(payload) = hook {
+                    first_panic.get_or_insert(payload);
+                    continue;
+                }
+            }
+            match unsafe { self.reset_or_discard(ptr) } {
+                Ok(true) => self.available.push_back(ptr),
+                Ok(false) => self.replace(),
+                Err(payload) => {
+                    first_panic.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = first_panic {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.available.len()
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.items.len() - self.available.len()
+    }
+
+    /// Get the largest number of items that were handed out at the same time.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+
+    /// Get the total size hint of the items in the pool, as last measured.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Set the sink that receives a LeakReport if the pool is dropped while items are in use.
+    pub fn set_leak_sink<F: Fn(&LeakReport) + Send + 'static>(&mut self, sink: F) {
+        self.leak_sink = Some(Box::new(sink));
+    }
+
+    /// Build a report of the items that are currently handed out.
+    fn leak_report(&self) -> LeakReport {
+        let sites: Vec<_> = self.items.iter()
+            .map(Entry::of)
+            // SAFETY: entries in `items` are alive; only the header fields are read.
+            .filter(|entry| unsafe { (**entry).in_use })
+            .map(|entry| unsafe { (*entry).acquired_at })
+            .collect();
+        LeakReport {
+            leaked: sites.len(),
+            sites
+        }
+    }
+
+    /// Get the current generation of the pool, which is advanced by every `clear()`.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+/// Adds pre-built objects to the pool with `adopt_value`.
+impl<T> Extend<T> for ObjectPool<T> {
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.adopt_value(value);
+        }
+    }
+}
+
+/// Seeds a pool that creates further objects with `Poolable::new`.
+impl<T: Poolable> FromIterator<T> for ObjectPool<T> {
+    #[track_caller]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ObjectPool<T> {
+        let mut pool = ObjectPool::new();
+        pool.extend(iter);
+        pool
+    }
+}
+
+impl<T: Poolable + Clone> Clone for ObjectPool<T> {
+    /// Clone the idle objects into a new, independent pool, e.g. to give every tenant a copy of a warmed template.
+    /// Limits and plain function settings carry over. Closures, such as hooks, a factory closure, the growth policy
+    /// and the leak sink, do not: the clone creates objects with `Poolable::new` and grows by one.
+    fn clone(&self) -> ObjectPool<T> {
+        let factory = match self.factory {
+            Factory::Poolable(new) => Factory::Poolable(new),
+            Factory::Closure(_) => Factory::Poolable(T::new)
+        };
+        let mut pool = ObjectPool::create(factory, self.reset);
+        pool.replace_discarded = self.replace_discarded;
+        pool.reset_timing = self.reset_timing;
+        pool.reuse_order = self.reuse_order;
+        pool.size_hint = self.size_hint;
+        pool.max_bytes = self.max_bytes;
+        pool.skip_reset = self.skip_reset;
+        pool.secret = self.secret;
+        pool.validate = self.validate;
+        pool.validate_on_release = self.validate_on_release;
+        pool.max_uses = self.max_uses;
+        pool.max_age = self.max_age;
+        pool.max_size = self.max_size;
+        pool.exhausted = self.exhausted;
+        pool.max_idle = self.max_idle;
+        pool.min_idle = self.min_idle;
+        pool.idle_timeout = self.idle_timeout;
+        pool.chunk_size = self.chunk_size;
+        pool.align = self.align;
+        pool.prefetch_lines = self.prefetch_lines;
+        pool.allocator = self.allocator.clone();
+        pool.available.reserve(self.available.len());
+        for ptr in self.available.iter() {
+            // SAFETY: idle objects are owned by the pool, and nothing else references them.
+            let (value, dirty) = unsafe { (ptr.as_ref().clone(), (*Entry::of(ptr)).dirty) };
+            let copy = pool.insert(value);
+            unsafe {
+                (*Entry::of(copy)).dirty = dirty;
+            }
+            pool.available.push_back(copy);
+        }
+        pool.frozen = self.frozen;
+        pool
+    }
+}
+
+impl<T> Drop for ObjectPool<T> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.leak_sink.as_ref() {
+            let report = self.leak_report();
+            if report.leaked > 0 {
+                sink(&report);
+            }
+        }
+        self.clear();
+        self.retire_slab();
+    }
+}
+
+/// Item taken out of a PoolBox, remembering the pool generation it was handed out in.
+/// Pass it to `PoolBox::from_token` to regain automatic release; dropping the token leaks the item.
+#[must_use = "dropping a PoolToken leaks its item"]
+pub struct PoolToken<T> {
+    item: NonNull<T>,
+    generation: u64
+}
+
+impl<T> PoolToken<T> {
+    /// Get a raw pointer to the item.
+    pub fn as_ptr(&self) -> *mut T {
+        self.item.as_ptr()
+    }
+}
+
+/// PoolBox is a wrapper around a pool item that automatically releases the item back to the pool when dropped.
+/// The guard borrows the pool for `'a`, so the pool cannot be moved or dropped while the guard is alive.
+pub struct PoolBox<'a, T> {
+    item: *mut T,
+    generation: u64,
+    pool: &'a RefCell<ObjectPool<T>>
+}
+
+impl<'a, T> PoolBox<'a, T> {
+    /// Create a new PoolBox that wraps an item from the pool.
+    #[inline]
+    #[track_caller]
+    pub fn new(pool: &'a AutoReturnObjectPool<T>) -> PoolBox<'a, T> {
+        let mut raw = pool.pool.borrow_mut();
+        let item = raw.get();
+        PoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &pool.pool
+        }
+    }
+
+    /// Re-wrap an item extracted from a PoolBox of `pool`.
+    ///
+    /// # Safety
+    /// `item` must have been extracted from a PoolBox of `pool` since the last `clear()`,
+    /// and must not be released or re-wrapped again.
+    pub unsafe fn from_raw(pool: &'a AutoReturnObjectPool<T>, item: *mut T) -> PoolBox<'a, T> {
+        PoolBox {
+            item,
+            generation: pool.pool.borrow().generation(),
+            pool: &pool.pool
+        }
+    }
+
+    /// Re-wrap an item taken out with `into_token`.
+    /// Fails if the item does not belong to `pool` or was handed out before the pool was cleared.
+    pub fn from_token(pool: &'a AutoReturnObjectPool<T>, token: PoolToken<T>) -> Result<PoolBox<'a, T>, PoolError> {
+        let raw = pool.pool.borrow();
+        if token.generation != raw.generation() {
+            return Err(PoolError::StaleHandle);
+        }
+        raw.check_in_use(token.item.as_ptr())?;
+        Ok(PoolBox {
+            item: token.item.as_ptr(),
+            generation: token.generation,
+            pool: &pool.pool
+        })
+    }
+
+    /// Get a reference to the item.
+    /// Panics if the item was extracted.
+    #[inline]
+    pub fn ref_item(&self) -> &T {
+        assert!(!self.item.is_null(), "item was extracted from the PoolBox");
+        unsafe {
+            &*self.item
+        }
+    }
+
+    /// Get a mutable reference to the item.
+    /// Panics if the item was extracted.
+    #[inline]
+    pub fn ref_mut_item(&mut self) -> &mut T {
+        assert!(!self.item.is_null(), "item was extracted from the PoolBox");
+        unsafe {
+            &mut *self.item
+        }
+    }
+
+    /// Extract the item from the PoolBox without releasing it back to the pool.
+    /// The raw pool will be responsible for releasing the item.
+    pub fn extract(&mut self) -> *mut T {
+        let item = self.item;
+        self.item = std::ptr::null_mut();
+        item
+    }
+
+    /// Remove the item from the pool entirely and return it by value.
+    /// Panics if the item was extracted.
+    pub fn into_inner(mut self) -> T {
+        let item = self.extract();
+        assert!(!item.is_null(), "item was extracted from the PoolBox");
+        unsafe {
+            self.pool.borrow_mut().take_from(item, self.generation)
+        }
+    }
+
+    /// Get a Debug view that includes the pool and slot information of the guard.
+    pub fn debug_info(&self) -> PoolBoxDebug<'_, 'a, T> {
+        PoolBoxDebug(self)
+    }
+
+    /// Turn the PoolBox into a guard over a part of the item, which still releases the whole item when dropped.
+    /// Panics if the item was extracted.
+    pub fn map<U, F>(mut self, f: F) -> PoolGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U
+    {
+        let value = NonNull::from(f(self.ref_mut_item()));
+        self.into_guard(value)
+    }
+
+    /// Like `map`, but the projection may fail, in which case the PoolBox is returned unchanged.
+    /// Panics if the item was extracted.
+    pub fn try_map<U, F>(mut self, f: F) -> Result<PoolGuard<'a, U>, PoolBox<'a, T>>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        match f(self.ref_mut_item()).map(NonNull::from) {
+            Some(value) => Ok(self.into_guard(value)),
+            None => Err(self)
+        }
+    }
+
+    /// Hand the release duty of the PoolBox over to a PoolGuard over `value`.
+    fn into_guard<U>(mut self, value: NonNull<U>) -> PoolGuard<'a, U> {
+        let pool: *const RefCell<ObjectPool<T>> = self.pool;
+        PoolGuard {
+            value,
+            item: self.extract().cast(),
+            generation: self.generation,
+            pool: pool.cast(),
+            release: release_erased::<T>,
+            _marker: PhantomData
+        }
+    }
+
+    /// Release the item back to the pool now instead of at the end of the scope.
+    pub fn release(self) {
+        drop(self);
+    }
+
+    /// Release the item without resetting it, for hot paths that always leave their items in the reset state.
+    /// The checks of a normal release still apply, but validation and the limits on idle items are skipped,
+    /// like `ObjectPool::release_unchecked`.
+    #[inline]
+    pub fn release_without_reset(mut self) {
+        let item = self.extract();
+        if !item.is_null() {
+            // SAFETY: the item was handed out by the pool during `generation` and is released only here.
+            unsafe {
+                self.pool.borrow_mut().restore_from(item, self.generation);
+            }
+        }
+    }
+
+    /// Take the item out of the PoolBox as a token that can be re-wrapped with `from_token`.
+    /// Panics if the item was already extracted.
+    pub fn into_token(mut self) -> PoolToken<T> {
+        let item = NonNull::new(self.extract()).expect("item was extracted from the PoolBox");
+        PoolToken {
+            item,
+            generation: self.generation
+        }
+    }
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.ref_item()
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.ref_mut_item()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.item.is_null() {
+            return f.write_str("<extracted>");
+        }
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.item.is_null() {
+            return f.write_str("<extracted>");
+        }
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for PoolBox<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for PoolBox<'_, T> {}
+
+impl<T: PartialOrd> PartialOrd for PoolBox<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord> Ord for PoolBox<'_, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash> Hash for PoolBox<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+/// Debug view of a PoolBox that also shows where the item lives and which pool generation it belongs to.
+pub struct PoolBoxDebug<'b, 'a, T>(&'b PoolBox<'a, T>);
+
+impl<T: fmt::Debug> fmt::Debug for PoolBoxDebug<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = self.0;
+        let pool_generation = guard.pool.borrow().generation();
+        f.debug_struct("PoolBox")
+            .field("item", guard)
+            .field("address", &guard.item)
+            .field("generation", &guard.generation)
+            .field("stale", &(guard.generation != pool_generation))
+            .finish()
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.item.is_null() {
+            return;
+        }
+        unsafe {
+            self.pool.borrow_mut().release_from(self.item, self.generation);
+        }
+    }
+}
+
+/// Release an item through pointers whose item type has been erased.
+///
+/// # Safety
+/// `pool` must point to a live `RefCell<ObjectPool<T>>` and `item` must be an item of it handed out during `generation`.
+unsafe fn release_erased<T>(pool: *const (), item: *mut (), generation: u64) {
+    unsafe {
+        let pool = &*pool.cast::<RefCell<ObjectPool<T>>>();
+        pool.borrow_mut().release_from(item.cast(), generation);
+    }
+}
+
+/// Guard over a part of a pooled item, created with `PoolBox::map`.
+/// The guard is not generic over the item type; it releases the whole item when dropped.
+pub struct PoolGuard<'a, U> {
+    value: NonNull<U>,
+    item: *mut (),
+    generation: u64,
+    pool: *const (),
+    release: unsafe fn(*const (), *mut (), u64),
+    _marker: PhantomData<(&'a (), &'a mut U)>
+}
+
+impl<'a, U> PoolGuard<'a, U> {
+    /// Narrow the guard further to a part of the current value.
+    pub fn map<V, F>(mut self, f: F) -> PoolGuard<'a, V>
+    where
+        F: FnOnce(&mut U) -> &mut V
+    {
+        let value = NonNull::from(f(&mut *self));
+        let guard = PoolGuard {
+            value,
+            item: self.item,
+            generation: self.generation,
+            pool: self.pool,
+            release: self.release,
+            _marker: PhantomData
+        };
+        mem::forget(self);
+        guard
+    }
+}
+
+impl<U> Deref for PoolGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        unsafe {
+            self.value.as_ref()
+        }
+    }
+}
+
+impl<U> DerefMut for PoolGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe {
+            self.value.as_mut()
+        }
+    }
+}
+
+impl<U> Drop for PoolGuard<'_, U> {
+    fn drop(&mut self) {
+        unsafe {
+            (self.release)(self.pool, self.item, self.generation);
+        }
+    }
+}
+
+/// Handle passed to the closure of `AutoReturnObjectPool::scope`.
+/// Everything acquired through it is released when the closure ends, even on early return or panic.
+pub struct PoolScope<'a, T> {
+    pool: &'a AutoReturnObjectPool<T>,
+    guards: RefCell<Vec<PoolBox<'a, T>>>
+}
+
+impl<T> PoolScope<'_, T> {
+    /// Get an item from the pool that stays handed out until the scope ends.
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub fn get(&self) -> &mut T {
+        let guard = self.pool.get();
+        let item = guard.item;
+        self.guards.borrow_mut().push(guard);
+        // Every call hands out a different item, and the guard keeping it alive is only dropped with the scope.
+        unsafe {
+            &mut *item
+        }
+    }
+
+    /// Get the number of items acquired through the scope.
+    pub fn len(&self) -> usize {
+        self.guards.borrow().len()
+    }
+
+    /// Check whether nothing was acquired through the scope yet.
+    pub fn is_empty(&self) -> bool {
+        self.guards.borrow().is_empty()
+    }
+}
+
+/// Variant of ObjectPool that hands out PoolBox guards which release their item when dropped.
+/// The free list lives behind a `RefCell`, so any number of guards can be outstanding at once.
+pub struct AutoReturnObjectPool<T> {
+    pool: RefCell<ObjectPool<T>>
+}
+
+impl<T: Poolable + Clone> Clone for AutoReturnObjectPool<T> {
+    /// Clone the idle items into a new, independent pool, like `ObjectPool::clone`.
+    fn clone(&self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.borrow().clone())
+    }
+}
+
+impl<T: Poolable> Default for AutoReturnObjectPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for AutoReturnObjectPool<T> {
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.pool.get_mut().extend(iter);
+    }
+}
+
+impl<T: Poolable> FromIterator<T> for AutoReturnObjectPool<T> {
+    #[track_caller]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(iter.into_iter().collect())
+    }
+}
+
+impl<T: Poolable> AutoReturnObjectPool<T> {
+    /// Create a new AutoReturnObjectPool.
+    pub fn new() -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool {
+            pool: RefCell::new(ObjectPool::new())
+        }
+    }
+
+    /// Create a new AutoReturnObjectPool with `capacity` objects constructed up front.
+    #[track_caller]
+    pub fn with_capacity(capacity: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(ObjectPool::with_capacity(capacity))
+    }
+
+    /// Move up to `count` idle items into a new pool, like `ObjectPool::split`.
+    pub fn split(&self, count: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.borrow_mut().split(count))
+    }
+}
+
+impl<T> AutoReturnObjectPool<T> {
+    /// Wrap a raw pool, such as one created with `ObjectPool::with_factory`.
+    pub fn from_raw(raw: ObjectPool<T>) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool {
+            pool: RefCell::new(raw)
+        }
+    }
+
+    /// Create a new AutoReturnObjectPool whose objects are created with `TryPoolable::try_new`, like
+    /// `ObjectPool::new_fallible`.
+    pub fn new_fallible() -> AutoReturnObjectPool<T>
+    where
+        T: TryPoolable,
+        T::Error: fmt::Debug
+    {
+        AutoReturnObjectPool::from_raw(ObjectPool::new_fallible())
+    }
+
+    /// Create a new AutoReturnObjectPool whose objects are created by `factory`, like `ObjectPool::with_factory`.
+    pub fn with_factory<F: FnMut() -> T + Send + 'static>(factory: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool {
+            pool: RefCell::new(ObjectPool::with_factory(factory))
+        }
+    }
+
+    /// Create a new AutoReturnObjectPool whose objects are created and reset with `params`, like
+    /// `ObjectPool::with_params`.
+    pub fn with_params(params: T::Params) -> AutoReturnObjectPool<T>
+    where
+        T: PoolableWith,
+        T::Params: Send + Sync + 'static
+    {
+        AutoReturnObjectPool::from_raw(ObjectPool::with_params(params))
+    }
+
+    /// Reset released objects with `reset` instead of `Poolable::reset`, like `ObjectPool::with_reset`.
+    pub fn with_reset<F: FnMut(&mut T) + Send + 'static>(self, reset: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool {
+            pool: RefCell::new(self.pool.into_inner().with_reset(reset))
+        }
+    }
+
+    /// Discard released objects whose `try_reset` fails, like `ObjectPool::with_try_reset`.
+    pub fn with_try_reset<E, F: FnMut(&mut T) -> Result<(), E> + Send + 'static>(self, try_reset: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_try_reset(try_reset))
+    }
+
+    /// Replace objects discarded by a failed `try_reset`, like `ObjectPool::with_replace_discarded`.
+    pub fn with_replace_discarded(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_replace_discarded())
+    }
+
+    /// Never reset released objects, like `ObjectPool::without_reset`.
+    pub fn without_reset(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().without_reset())
+    }
+
+    /// Check objects with `Validate::is_valid` before handing them out, like `ObjectPool::with_validation`.
+    pub fn with_validation(self) -> AutoReturnObjectPool<T>
+    where
+        T: Validate
+    {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_validation())
+    }
+
+    /// Also validate objects on release, like `ObjectPool::with_release_validation`.
+    pub fn with_release_validation(self) -> AutoReturnObjectPool<T>
+    where
+        T: Validate
+    {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_release_validation())
+    }
+
+    /// Destroy objects on release once they have been handed out `max_uses` times, like `ObjectPool::with_max_uses`.
+    pub fn with_max_uses(self, max_uses: u64) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_max_uses(max_uses))
+    }
+
+    /// Hold at most `max_size` objects, like `ObjectPool::with_max_size`.
+    pub fn with_max_size(self, max_size: usize, behavior: ExhaustedBehavior) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_max_size(max_size, behavior))
+    }
+
+    /// Choose how many objects are created on a miss, like `ObjectPool::with_growth_policy`.
+    pub fn with_growth_policy<P: GrowthPolicy + 'static>(self, policy: P) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_growth_policy(policy))
+    }
+
+    /// Choose which idle object is handed out next, like `ObjectPool::with_reuse_order`.
+    pub fn with_reuse_order(self, order: ReuseOrder) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_reuse_order(order))
+    }
+
+    /// Keep at most `max_idle` objects idle, like `ObjectPool::with_max_idle`.
+    pub fn with_max_idle(self, max_idle: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_max_idle(max_idle))
+    }
+
+    /// Let `maintain()` keep at least `min_idle` objects idle, like `ObjectPool::with_min_idle`.
+    pub fn with_min_idle(self, min_idle: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_min_idle(min_idle))
+    }
+
+    /// Let `maintain()` evict objects idle for longer than `timeout`, like `ObjectPool::with_idle_timeout`.
+    pub fn with_idle_timeout(self, timeout: Duration) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_idle_timeout(timeout))
+    }
+
+    /// Retire objects older than `max_age`, like `ObjectPool::with_max_age`.
+    pub fn with_max_age(self, max_age: Duration) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_max_age(max_age))
+    }
+
+    /// Run `hook` on every newly created object, like `ObjectPool::with_on_create`.
+    pub fn with_on_create<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_on_create(hook))
+    }
+
+    /// Run `hook` on every object before it is handed out, like `ObjectPool::with_on_acquire`.
+    pub fn with_on_acquire<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_on_acquire(hook))
+    }
+
+    /// Run `hook` on every released object before it is reset, like `ObjectPool::with_on_release`.
+    pub fn with_on_release<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_on_release(hook))
+    }
+
+    /// Run `hook` on every object the pool drops, like `ObjectPool::with_on_destroy`.
+    pub fn with_on_destroy<F: FnMut(&mut T) + Send + 'static>(self, hook: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_on_destroy(hook))
+    }
+
+    /// Choose when released objects are reset, like `ObjectPool::with_reset_timing`.
+    pub fn with_reset_timing(self, timing: ResetTiming) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_reset_timing(timing))
+    }
+
+    /// Run `reset` on released objects after `Poolable::reset`, like `ObjectPool::with_extra_reset`.
+    pub fn with_extra_reset<F: FnMut(&mut T) + Send + 'static>(self, reset: F) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool {
+            pool: RefCell::new(self.pool.into_inner().with_extra_reset(reset))
+        }
+    }
+
+    /// Get a PoolBox from the pool.
+    /// The returned guard borrows the pool until it is dropped.
+    #[inline]
+    #[track_caller]
+    pub fn get(&self) -> PoolBox<'_, T> {
+        PoolBox::new(self)
+    }
+
+    /// Get a PoolBox with the next idle item without checking that one is idle, like `ObjectPool::get_unchecked`.
+    ///
+    /// # Safety
+    /// At least one item must be idle.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn get_unchecked(&self) -> PoolBox<'_, T> {
+        let mut raw = self.pool.borrow_mut();
+        // SAFETY: the caller guarantees that an item is idle.
+        let item = unsafe { raw.get_unchecked() };
+        PoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &self.pool
+        }
+    }
+
+    /// Run `f` with a PoolScope and release everything acquired through it, reset, when `f` returns or panics.
+    /// Items are borrowed as plain references, so per-frame code needs no guard bindings.
+    pub fn scope<R, F: FnOnce(&PoolScope<'_, T>) -> R>(&self, f: F) -> R {
+        let scope = PoolScope {
+            pool: self,
+            guards: RefCell::new(Vec::new())
+        };
+        f(&scope)
+    }
+
+    /// Get `N` PoolBoxes from the pool as an array, such as the buffers of a triple buffer, without allocating
+    /// a Vec for the guards.
+    #[track_caller]
+    pub fn get_array<const N: usize>(&self) -> [PoolBox<'_, T>; N] {
+        std::array::from_fn(|_| self.get())
+    }
+
+    /// Get a PoolBox from the pool, or `PoolError::Exhausted` if the pool is at its maximum size and cannot grow.
+    #[track_caller]
+    pub fn checked_get(&self) -> Result<PoolBox<'_, T>, PoolError> {
+        let mut raw = self.pool.borrow_mut();
+        let item = raw.checked_get()?;
+        Ok(PoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &self.pool
+        })
+    }
+
+    /// Get a PoolBox with an idle item, or None if no item is idle. This never allocates.
+    #[track_caller]
+    pub fn try_get_idle(&self) -> Option<PoolBox<'_, T>> {
+        let mut raw = self.pool.borrow_mut();
+        let item = raw.try_get_idle()?;
+        Some(PoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &self.pool
+        })
+    }
+
+    /// Get a PoolBox from the pool and run `init` on its item before returning it.
+    /// If `init` panics, the item goes back to the pool, so a half initialized item never escapes.
+    #[track_caller]
+    pub fn get_with<F: FnOnce(&mut T)>(&self, init: F) -> PoolBox<'_, T> {
+        let mut obj = self.get();
+        init(&mut obj);
+        obj
+    }
+
+    /// Get a PoolBox from the pool, creating its item with `TryPoolable::try_new` if none is available.
+    #[track_caller]
+    pub fn try_get(&self) -> Result<PoolBox<'_, T>, PoolError<T::Error>>
+    where
+        T: TryPoolable
+    {
+        self.try_get_with(T::try_new)
+    }
+
+    /// Get a PoolBox from the pool, creating its item with `create` if none is available.
+    #[track_caller]
+    pub fn try_get_with<E, F: FnOnce() -> Result<T, E>>(&self, create: F) -> Result<PoolBox<'_, T>, PoolError<E>> {
+        let mut raw = self.pool.borrow_mut();
+        let item = raw.try_get_with(create)?;
+        Ok(PoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &self.pool
+        })
+    }
+
+    /// Get the underlying raw pool.
+    /// Requires that no guards are outstanding.
+    pub fn raw_pool(&mut self) -> &mut ObjectPool<T> {
+        self.pool.get_mut()
+    }
+
+    /// Reserve a number of items in the pool.
+    #[track_caller]
+    pub fn reserve(&self, count: usize) {
+        self.pool.borrow_mut().reserve(count);
+    }
+
+    /// Reserve a number of items, returning an error if memory runs out, like `ObjectPool::try_reserve`.
+    #[track_caller]
+    pub fn try_reserve(&self, count: usize) -> Result<(), ReserveError> {
+        self.pool.borrow_mut().try_reserve(count)
+    }
+
+    /// Take ownership of an object constructed outside the pool and make it available, like `ObjectPool::adopt`.
+    #[track_caller]
+    pub fn adopt(&self, item: Box<T>) {
+        self.pool.borrow_mut().adopt(item);
+    }
+
+    /// Take ownership of an object constructed outside the pool, like `ObjectPool::adopt_value`.
+    #[track_caller]
+    pub fn adopt_value(&self, value: T) {
+        self.pool.borrow_mut().adopt_value(value);
+    }
+
+    /// Set the sink that receives a LeakReport if the pool is dropped while items are in use.
+    pub fn set_leak_sink<F: Fn(&LeakReport) + Send + 'static>(&self, sink: F) {
+        self.pool.borrow_mut().set_leak_sink(sink);
+    }
+
+    /// Clear the pool completely.
+    /// Outstanding guards keep their item and free it when dropped.
+    pub fn clear(&self) {
+        self.pool.borrow_mut().clear();
+    }
+
+    /// Clear the pool completely, but only if no guards are outstanding.
+    pub fn try_clear(&self) -> Result<(), PoolError> {
+        self.pool.borrow_mut().try_clear()
+    }
+
+    /// Free every available item, leaving outstanding guards untouched.
+    /// Returns the number of items freed.
+    pub fn clear_idle(&self) -> usize {
+        self.pool.borrow_mut().clear_idle()
+    }
+
+    /// Cap the pool by the total size hint of its objects, like `ObjectPool::with_max_bytes`.
+    pub fn with_max_bytes(self, max_bytes: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_max_bytes(max_bytes))
+    }
+
+    /// Allocate objects in slabs of `chunk_size`, like `ObjectPool::with_chunk_size`.
+    pub fn with_chunk_size(self, chunk_size: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_chunk_size(chunk_size))
+    }
+
+    /// Prefetch handed out objects, like `ObjectPool::with_prefetch`.
+    pub fn with_prefetch(self, lines: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_prefetch(lines))
+    }
+
+    /// Give every new object its own cache line, like `ObjectPool::with_cache_line_padding`.
+    pub fn with_cache_line_padding(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_cache_line_padding())
+    }
+
+    /// Align every new object to at least `align` bytes, like `ObjectPool::with_alignment`.
+    pub fn with_alignment(self, align: usize) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_alignment(align))
+    }
+
+    /// Back new objects with huge pages, like `ObjectPool::with_huge_pages`.
+    #[cfg(target_os = "linux")]
+    pub fn with_huge_pages(self, huge_pages: HugePages) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_huge_pages(huge_pages))
+    }
+
+    /// Give the memory of destroyed objects back to the kernel, like `ObjectPool::with_decommit`.
+    #[cfg(target_os = "linux")]
+    pub fn with_decommit(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_decommit())
+    }
+
+    /// Keep new objects in locked memory and wipe them when destroyed, like `ObjectPool::with_secure_memory`.
+    #[cfg(target_os = "linux")]
+    pub fn with_secure_memory(self) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_secure_memory())
+    }
+
+    /// Wipe the bytes that `secret` selects whenever an object is released, like `ObjectPool::with_zeroize`.
+    pub fn with_zeroize(self, secret: fn(&mut T) -> &mut [u8]) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_zeroize(secret))
+    }
+
+    /// Allocate new objects from `allocator`, like `ObjectPool::with_allocator`.
+    #[cfg(feature = "allocator_api")]
+    pub fn with_allocator<A: Allocator + Send + Sync + 'static>(self, allocator: A) -> AutoReturnObjectPool<T> {
+        AutoReturnObjectPool::from_raw(self.pool.into_inner().with_allocator(allocator))
+    }
+
+    /// Stop creating objects, like `ObjectPool::freeze`.
+    pub fn freeze(&self) {
+        self.pool.borrow_mut().freeze();
+    }
+
+    /// Let the pool create objects again, like `ObjectPool::unfreeze`.
+    pub fn unfreeze(&self) {
+        self.pool.borrow_mut().unfreeze();
+    }
+
+    /// Check whether the pool is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.pool.borrow().is_frozen()
+    }
+
+    /// Free every idle item for which `keep` returns false, like `ObjectPool::retain`.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, keep: F) -> usize {
+        self.pool.borrow_mut().retain(keep)
+    }
+
+    /// Move every idle item of `other` into this pool, like `ObjectPool::merge`.
+    pub fn merge(&self, other: &AutoReturnObjectPool<T>) -> usize {
+        if std::ptr::eq(self, other) {
+            return 0;
+        }
+        self.pool.borrow_mut().merge(&mut other.pool.borrow_mut())
+    }
+
+    /// Free idle items until at most `idle` are left, like `ObjectPool::shrink_to`.
+    pub fn shrink_to(&self, idle: usize) -> usize {
+        self.pool.borrow_mut().shrink_to(idle)
+    }
+
+    /// Free every idle item and release the memory the pool no longer needs, like `ObjectPool::shrink_to_fit`.
+    pub fn shrink_to_fit(&self) -> usize {
+        self.pool.borrow_mut().shrink_to_fit()
+    }
+
+    /// Retire expired and timed out idle objects and refill up to `min_idle`, like `ObjectPool::maintain`.
+    #[track_caller]
+    pub fn maintain(&self) -> usize {
+        self.pool.borrow_mut().maintain()
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.pool.borrow().len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.pool.borrow().is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.pool.borrow().available()
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.pool.borrow().in_use()
+    }
+
+    /// Get the largest number of items that were handed out at the same time.
+    pub fn high_water_mark(&self) -> usize {
+        self.pool.borrow().high_water_mark()
+    }
+
+    /// Get the total size hint of the items in the pool, as last measured.
+    pub fn bytes(&self) -> usize {
+        self.pool.borrow().bytes()
+    }
+}
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::object_pool::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_object_pool() {
+        let mut pool = AutoReturnObjectPool::<TestObject>::new();
+        {
+            let mut obj = pool.get();
+            obj.ref_mut_item().value = 10;
+            assert_eq!(obj.ref_item().value, 10);
+            assert_eq!(pool.len(), 1);
+        }
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.is_empty());
+        {
+            // Create 10 objects
+            let mut objs = Vec::new();
+            for _ in 0..10 {
+                objs.push(pool.get());
+            }
+            assert_eq!(pool.available(), 0);
+            assert_eq!(pool.len(), 10);
+        }
+        assert_eq!(pool.available(), 10);
+        assert_eq!(pool.len(), 10);
+        assert_eq!(pool.high_water_mark(), 10);
+        pool.clear();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(pool.len(), 0);
+
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 20;
+        assert_eq!(obj.ref_item().value, 20);
+        let item = obj.extract();
+        assert!(!item.is_null());
+        drop(obj);
+        assert_eq!(pool.available(), 0);
+        unsafe{ assert!((*item).value == 20); }
+        unsafe { pool.raw_pool().release(item); }
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_multiple_guards_through_shared_ref() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut a = pool.get();
+        let mut b = pool.get();
+        a.ref_mut_item().value = 1;
+        b.ref_mut_item().value = 2;
+        assert_eq!(a.ref_item().value + b.ref_item().value, 3);
+        assert_eq!(pool.available(), 0);
+        drop(a);
+        assert_eq!(pool.available(), 1);
+        drop(b);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn test_clear_with_outstanding_guards() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        pool.reserve(2);
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 3;
+        pool.clear();
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.available(), 0);
+
+        // The stale guard still owns a live object but no longer returns it to the pool.
+        assert_eq!(obj.ref_item().value, 3);
+        drop(obj);
+        assert_eq!(pool.available(), 0);
+        let obj = pool.get();
+        assert_eq!(obj.ref_item().value, 0);
+        drop(obj);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_try_clear() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        pool.reserve(3);
+        let obj = pool.get();
+        assert_eq!(pool.in_use(), 1);
+        assert_eq!(pool.try_clear(), Err(PoolError::InUse(1)));
+        assert_eq!(pool.len(), 3);
+
+        assert_eq!(pool.clear_idle(), 2);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 0);
+        drop(obj);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.try_clear(), Ok(()));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_try_release() {
+        let mut pool = ObjectPool::<TestObject>::new();
+        let mut other = ObjectPool::<TestObject>::new();
+        let item = pool.get();
+        let foreign = other.get();
+        let mut local = TestObject::new();
+        assert_eq!(pool.try_release(foreign), Err(PoolError::ForeignPointer));
+        assert_eq!(pool.try_release(&mut local), Err(PoolError::ForeignPointer));
+        assert_eq!(pool.try_release(std::ptr::null_mut()), Err(PoolError::ForeignPointer));
+        assert_eq!(pool.try_release(item), Ok(()));
+        assert_eq!(pool.try_release(item), Err(PoolError::NotInUse));
+        assert_eq!(pool.available(), 1);
+        assert_eq!(other.try_release(foreign), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "released an item that is not in use")]
+    fn test_double_release_panics() {
+        let mut pool = ObjectPool::<TestObject>::new();
+        let item = pool.get();
+        unsafe {
+            pool.release(item);
+            pool.release(item);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "released an item that does not belong to the pool")]
+    fn test_foreign_release_panics() {
+        let mut pool = ObjectPool::<TestObject>::new();
+        let mut other = ObjectPool::<TestObject>::new();
+        let item = other.get();
+        unsafe {
+            pool.release(item);
+        }
+    }
+
+    #[test]
+    fn test_leak_report() {
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let mut pool = ObjectPool::<TestObject>::new();
+        pool.set_leak_sink(move |report| sink.lock().unwrap().push(report.clone()));
+        let item = pool.get();
+        let line = line!();
+        let leaked = pool.get();
+        unsafe { pool.release(item); }
+        drop(pool);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].leaked, 1);
+        assert_eq!(reports[0].sites[0].line(), line + 1);
+        assert!(reports[0].to_string().contains(file!()));
+        unsafe { ObjectPool::<TestObject>::destroy(leaked); }
+    }
+
+    struct PanickyObject {
+        pub poisoned: bool
+    }
+
+    impl Poolable for PanickyObject {
+        fn new() -> PanickyObject {
+            PanickyObject {
+                poisoned: false
+            }
+        }
+
+        fn reset(&mut self) {
+            if self.poisoned {
+                panic!("reset failed");
+            }
+        }
+    }
+
+    #[test]
+    fn test_panicking_reset_discards_item() {
+        let mut pool = ObjectPool::<PanickyObject>::new();
+        pool.reserve(1);
+        let poisoned = pool.get();
+        let healthy = pool.get();
+        unsafe { (*poisoned).poisoned = true; }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { pool.release(poisoned) }));
+        assert!(result.is_err());
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 0);
+
+        let other = pool.get();
+        unsafe { (*healthy).poisoned = true; }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { pool.release_all() }));
+        assert!(result.is_err());
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.get(), other);
+    }
+
+    #[test]
+    fn test_unchecked_fast_paths() {
+        let mut pool = ObjectPool::<TestObject>::new();
+        pool.reserve(2);
+        unsafe {
+            let a = pool.get_unchecked();
+            let b = pool.get_unchecked();
+            assert!(a != b);
+            (*a).value = 4;
+            // Released as is: the next holder sees the value, since no reset ran.
+            pool.release_unchecked(a);
+            pool.release(b);
+            assert_eq!((pool.available(), pool.in_use()), (2, 0));
+            let b = pool.get_unchecked();
+            let a = pool.get_unchecked();
+            assert_eq!(((*a).value, (*b).value), (4, 0));
+            pool.release(a);
+            pool.release(b);
+        }
+
+        let pool = AutoReturnObjectPool::<TestObject>::with_capacity(1);
+        let mut obj = unsafe { pool.get_unchecked() };
+        obj.value = 2;
+        obj.release_without_reset();
+        assert_eq!(pool.get().value, 2);
+        assert_eq!(pool.get().value, 0);
+    }
+
+    #[test]
+    fn test_release_all() {
+        let mut pool = ObjectPool::<TestObject>::new();
+        pool.reserve(2);
+        let a = pool.get();
+        let b = pool.get();
+        unsafe {
+            (*a).value = 1;
+            (*b).value = 2;
+            pool.release(a);
+            pool.release_all();
+        }
+        assert_eq!(pool.available(), 2);
+        let a = pool.get();
+        let b = pool.get();
+        assert!(a != b);
+        unsafe { assert_eq!((*a).value + (*b).value, 0); }
+    }
+
+    #[test]
+    fn test_rewrap_extracted_items() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 4;
+        let item = obj.extract();
+        drop(obj);
+        assert_eq!(pool.in_use(), 1);
+        let obj = unsafe { PoolBox::from_raw(&pool, item) };
+        assert_eq!(obj.ref_item().value, 4);
+        drop(obj);
+        assert_eq!(pool.available(), 1);
+
+        let token = pool.get().into_token();
+        let other = AutoReturnObjectPool::<TestObject>::new();
+        let _other_obj = other.get();
+        let token_ptr = token.as_ptr();
+        assert!(matches!(PoolBox::from_token(&other, token), Err(PoolError::ForeignPointer)));
+        let obj = unsafe { PoolBox::from_raw(&pool, token_ptr) };
+        let token = obj.into_token();
+        let obj = PoolBox::from_token(&pool, token).unwrap();
+        drop(obj);
+        assert_eq!(pool.available(), 1);
+
+        let token = pool.get().into_token();
+        let stale = token.as_ptr();
+        pool.clear();
+        assert!(matches!(PoolBox::from_token(&pool, token), Err(PoolError::StaleHandle)));
+        unsafe { ObjectPool::destroy(stale); }
+    }
+
+    #[test]
+    fn test_deref() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.value += 2;
+        assert_eq!(obj.value, 2);
+        fn read(object: &TestObject) -> i32 {
+            object.value
+        }
+        assert_eq!(read(&obj), 2);
+    }
+
+    #[test]
+    fn test_debug_and_display() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.value = 9;
+        assert_eq!(format!("{:?}", obj), "TestObject { value: 9 }");
+        let info = format!("{:?}", obj.debug_info());
+        assert!(info.starts_with("PoolBox { item: TestObject { value: 9 }, address: 0x"));
+        assert!(info.ends_with("generation: 0, stale: false }"));
+        pool.clear();
+        assert!(format!("{:?}", obj.debug_info()).ends_with("stale: true }"));
+
+        let mut strings = AutoReturnObjectPool::<Label>::new();
+        let mut label = strings.get();
+        label.0.push_str("pooled");
+        assert_eq!(label.to_string(), "pooled");
+        let item = label.extract();
+        assert_eq!(format!("{} {:?}", label, label), "<extracted> <extracted>");
+        drop(label);
+        unsafe { strings.raw_pool().release(item); }
+    }
+
+    #[derive(Debug)]
+    struct Label(String);
+
+    impl Poolable for Label {
+        fn new() -> Label {
+            Label(String::new())
+        }
+
+        fn reset(&mut self) {
+            self.0.clear();
+        }
+    }
+
+    impl std::fmt::Display for Label {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    struct Key(u32);
+
+    impl Poolable for Key {
+        fn new() -> Key {
+            Key(0)
+        }
+
+        fn reset(&mut self) {
+            self.0 = 0;
+        }
+    }
+
+    #[test]
+    // The guard's pool reference has interior mutability, but it is not part of the hash.
+    #[allow(clippy::mutable_key_type)]
+    fn test_comparisons() {
+        let pool = AutoReturnObjectPool::<Key>::new();
+        let mut keys: Vec<_> = [3, 1, 2, 1].iter().map(|value| {
+            let mut key = pool.get();
+            key.0 = *value;
+            key
+        }).collect();
+        keys.sort();
+        assert_eq!(keys.iter().map(|key| key.0).collect::<Vec<_>>(), vec![1, 1, 2, 3]);
+        assert!(keys[0] == keys[1]);
+        assert!(keys[1] < keys[2]);
+        let set: std::collections::HashSet<_> = keys.into_iter().collect();
+        assert_eq!(set.len(), 3);
+    }
+
+    struct Frame {
+        header: TestObject,
+        payload: Vec<u8>
+    }
+
+    impl Poolable for Frame {
+        fn new() -> Frame {
+            Frame {
+                header: TestObject::new(),
+                payload: Vec::new()
+            }
+        }
+
+        fn reset(&mut self) {
+            self.header.reset();
+            self.payload.clear();
+        }
+    }
+
+    #[test]
+    fn test_map() {
+        let pool = AutoReturnObjectPool::<Frame>::new();
+        let mut payload = pool.get().map(|frame| &mut frame.payload);
+        payload.push(1);
+        assert_eq!(pool.in_use(), 1);
+        drop(payload);
+        assert_eq!(pool.available(), 1);
+
+        let mut value = pool.get().map(|frame| &mut frame.header).map(|header| &mut header.value);
+        *value = 3;
+        drop(value);
+        assert_eq!(pool.available(), 1);
+
+        let frame = pool.get().try_map(|frame| frame.payload.first_mut());
+        let frame = frame.err().unwrap();
+        assert!(frame.payload.is_empty());
+        drop(frame);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_early_release() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 6;
+        obj.release();
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.get().ref_item().value, 0);
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        pool.reserve(1);
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 8;
+        let owned: TestObject = obj.into_inner();
+        assert_eq!(owned.value, 8);
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.in_use(), 0);
+
+        let obj = pool.get();
+        pool.clear();
+        assert_eq!(obj.into_inner().value, 0);
+    }
+
+    #[test]
+    fn test_pool_box_borrows_pool() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.ref_mut_item().value = 5;
+        drop(obj);
+        // The guard has been dropped, so the pool can be moved again.
+        assert_eq!(pool.available(), 1);
+        let moved = pool;
+        assert_eq!(moved.len(), 1);
+    }
+
+    #[test]
+    fn test_with_factory() {
+        // Vec does not implement Poolable, and the factory captures its capacity.
+        let capacity = 64;
+        let pool = AutoReturnObjectPool::with_factory(move || Vec::<u8>::with_capacity(capacity));
+        let mut buffer = pool.get();
+        assert!(buffer.capacity() >= capacity);
+        buffer.push(1);
+        drop(buffer);
+        assert_eq!(pool.len(), 1);
+        // Without a reset, the buffer comes back as it was released.
+        let reused = pool.get();
+        assert_eq!(*reused, vec![1]);
+        let fresh = pool.get();
+        assert!(fresh.is_empty() && fresh.capacity() >= capacity);
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_with_reset() {
+        // Keeps the capacity of released buffers.
+        let keep = AutoReturnObjectPool::with_factory(Vec::<u8>::new).with_reset(Vec::clear);
+        keep.get().extend_from_slice(&[1; 100]);
+        let buffer = keep.get();
+        assert!(buffer.is_empty() && buffer.capacity() >= 100);
+        drop(buffer);
+
+        // Supplements Poolable::reset, which runs first.
+        let shrink = AutoReturnObjectPool::<TestObject>::new().with_extra_reset(|obj| obj.value -= 1);
+        shrink.get().value = 10;
+        assert_eq!(shrink.get().value, -1);
+
+        // Both together: the replacement reset runs first, then the extra one, in either order of setting them.
+        let both = AutoReturnObjectPool::<TestObject>::new()
+            .with_extra_reset(|obj| obj.value *= 2)
+            .with_reset(|obj| obj.value += 1);
+        both.get().value = 10;
+        assert_eq!(both.get().value, 22);
+    }
+
+    struct Buffer {
+        pub data: Vec<u8>,
+        pub tenant: u32
+    }
+
+    struct BufferParams {
+        pub size: usize,
+        pub tenant: u32
+    }
+
+    impl PoolableWith for Buffer {
+        type Params = BufferParams;
+
+        fn new_with(params: &BufferParams) -> Buffer {
+            Buffer {
+                data: vec![0; params.size],
+                tenant: params.tenant
+            }
+        }
+
+        fn reset_with(&mut self, params: &BufferParams) {
+            self.data.clear();
+            self.data.resize(params.size, 0);
+            self.tenant = params.tenant;
+        }
+    }
+
+    #[test]
+    fn test_with_params() {
+        let pool = AutoReturnObjectPool::<Buffer>::with_params(BufferParams {
+            size: 8,
+            tenant: 3
+        });
+        let mut buffer = pool.get();
+        assert_eq!((buffer.data.len(), buffer.tenant), (8, 3));
+        buffer.data.truncate(2);
+        buffer.tenant = 0;
+        drop(buffer);
+        let buffer = pool.get();
+        assert_eq!((buffer.data.len(), buffer.tenant), (8, 3));
+        drop(buffer);
+
+        // Poolable types take `()` as their parameters.
+        let plain = AutoReturnObjectPool::<TestObject>::with_params(());
+        plain.get().value = 5;
+        assert_eq!(plain.get().value, 0);
+    }
+
+    struct Session {
+        pub id: u32
+    }
+
+    impl TryPoolable for Session {
+        type Error = String;
+
+        fn try_new() -> Result<Session, String> {
+            thread_local! {
+                static ATTEMPTS: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+            }
+            let attempt = ATTEMPTS.with(|attempts| attempts.replace(attempts.get() + 1));
+            if attempt % 2 == 1 {
+                return Err(format!("handshake {} failed", attempt));
+            }
+            Ok(Session {
+                id: attempt
+            })
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn test_try_get() {
+        let pool = AutoReturnObjectPool::<Session>::new_fallible();
+        let session = pool.try_get().unwrap();
+        assert_eq!(session.id, 0);
+        let Err(error) = pool.try_get() else {
+            panic!("the second handshake should fail");
+        };
+        assert_eq!(error, PoolError::Create("handshake 1 failed".to_string()));
+        assert_eq!(error.to_string(), "failed to create pool item: handshake 1 failed");
+        assert_eq!(pool.len(), 1);
+        drop(session);
+        // Idle items are reused without calling the factory.
+        assert_eq!(pool.try_get().unwrap().id, 0);
+
+        let files = AutoReturnObjectPool::with_factory(Vec::<u8>::new);
+        let error = files.try_get_with(|| Err::<Vec<u8>, _>(std::io::Error::other("no file"))).unwrap_err();
+        assert!(std::error::Error::source(&error).is_some());
+        assert!(files.try_get_with(|| Ok::<_, std::io::Error>(vec![1])).is_ok());
+        assert_eq!(files.len(), 1);
+    }
+
+    struct Codec {
+        pub corrupted: bool
+    }
+
+    impl Codec {
+        fn try_reset(&mut self) -> Result<(), &'static str> {
+            if self.corrupted {
+                return Err("unrecoverable codec state");
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_with_try_reset() {
+        let codec = || Codec {
+            corrupted: false
+        };
+        let pool = AutoReturnObjectPool::with_factory(codec).with_try_reset(Codec::try_reset);
+        pool.reserve(2);
+        pool.get().corrupted = true;
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 1);
+        assert!(!pool.get().corrupted);
+
+        let replacing = AutoReturnObjectPool::with_factory(codec).with_try_reset(Codec::try_reset).with_replace_discarded();
+        replacing.reserve(2);
+        replacing.get().corrupted = true;
+        assert_eq!(replacing.len(), 2);
+        assert_eq!(replacing.available(), 2);
+        let a = replacing.get();
+        let b = replacing.get();
+        assert!(!a.corrupted && !b.corrupted);
+    }
+
+    #[derive(Default)]
+    struct Scratch {
+        pub words: Vec<String>
+    }
+
+    impl Clear for Scratch {
+        fn clear(&mut self) {
+            self.words.clear();
+        }
+    }
+
+    #[test]
+    fn test_default_and_clear_are_poolable() {
+        let pool = AutoReturnObjectPool::<Scratch>::new();
+        pool.get().words.push("hello".to_string());
+        let scratch = pool.get();
+        assert!(scratch.words.is_empty() && scratch.words.capacity() > 0);
+    }
+
+    #[cfg(feature = "collections")]
+    #[test]
+    fn test_collections_are_poolable() {
+        let pool = AutoReturnObjectPool::<Vec<u8>>::new();
+        pool.get().extend_from_slice(&[1; 32]);
+        let buffer = pool.get();
+        assert!(buffer.is_empty() && buffer.capacity() >= 32);
+
+        let names = AutoReturnObjectPool::<std::collections::HashMap<String, u32>>::new();
+        names.get().insert("a".to_string(), 1);
+        let map = names.get();
+        assert!(map.is_empty() && map.capacity() >= 1);
+    }
+
+    #[test]
+    fn test_reset_on_acquire() {
+        let resets = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&resets);
+        let pool = AutoReturnObjectPool::<TestObject>::new()
+            .with_extra_reset(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_reset_timing(ResetTiming::OnAcquire);
+        pool.get().value = 4;
+        assert_eq!(resets.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.get().value, 0);
+        assert_eq!(resets.load(Ordering::Relaxed), 1);
+
+        // A deferred try_reset failure discards the item when it would be handed out.
+        let codec = || Codec {
+            corrupted: false
+        };
+        let lazy = AutoReturnObjectPool::with_factory(codec).with_try_reset(Codec::try_reset).with_reset_timing(ResetTiming::OnAcquire);
+        lazy.get().corrupted = true;
+        assert_eq!(lazy.len(), 1);
+        assert!(!lazy.get().corrupted);
+        assert_eq!(lazy.len(), 1);
+    }
+
+    #[test]
+    fn test_without_reset() {
+        let frames = AutoReturnObjectPool::<TestObject>::new().without_reset();
+        frames.get().value = 9;
+        assert_eq!(frames.get().value, 9);
+
+        let reset_again = AutoReturnObjectPool::<TestObject>::new().without_reset().with_extra_reset(|obj| obj.value += 1);
+        reset_again.get().value = 9;
+        assert_eq!(reset_again.get().value, 1);
+    }
+
+    #[test]
+    fn test_get_with() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let obj = pool.get_with(|obj| obj.value = 12);
+        assert_eq!(obj.value, 12);
+        drop(obj);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.get_with(|obj| {
+                obj.value = 5;
+                panic!("configuration failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.get().value, 0);
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = |event: &'static str| {
+            let events = Arc::clone(&events);
+            move |obj: &mut TestObject| events.lock().unwrap().push((event, obj.value))
+        };
+        let pool = AutoReturnObjectPool::<TestObject>::new()
+            .with_on_create(log("create"))
+            .with_on_acquire(log("acquire"))
+            .with_on_release(log("release"))
+            .with_on_destroy(log("destroy"));
+        pool.get().value = 3;
+        pool.get().value = 4;
+        pool.clear();
+        assert_eq!(*events.lock().unwrap(), [
+            ("create", 0),
+            ("acquire", 0),
+            ("release", 3),
+            ("acquire", 0),
+            ("release", 4),
+            ("destroy", 0)
+        ]);
+    }
+
+    #[test]
+    fn test_on_destroy_after_clear() {
+        let destroyed = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&destroyed);
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_on_destroy(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let held = pool.get();
+        drop(pool.get());
+        pool.clear();
+        assert_eq!(destroyed.load(Ordering::Relaxed), 1);
+        drop(held);
+        assert_eq!(destroyed.load(Ordering::Relaxed), 2);
+        assert_eq!(pool.len(), 0);
+    }
+
+    struct Connection {
+        pub alive: bool
+    }
+
+    impl Poolable for Connection {
+        fn new() -> Connection {
+            Connection {
+                alive: true
+            }
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    impl Validate for Connection {
+        fn is_valid(&self) -> bool {
+            self.alive
+        }
+    }
+
+    #[test]
+    fn test_with_validation() {
+        let pool = AutoReturnObjectPool::<Connection>::new().with_validation();
+        pool.reserve(1);
+        pool.get().alive = false;
+        assert_eq!(pool.available(), 1);
+        // The dead connection is replaced when it would be handed out.
+        assert!(pool.get().alive);
+        assert_eq!(pool.len(), 1);
+
+        let eager = AutoReturnObjectPool::<Connection>::new().with_release_validation();
+        eager.get().alive = false;
+        assert_eq!(eager.len(), 0);
+        eager.get().alive = true;
+        assert_eq!(eager.available(), 1);
+    }
+
+    #[test]
+    fn test_with_max_uses() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&created);
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_max_uses(2).with_on_create(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        for _ in 0..5 {
+            drop(pool.get());
+        }
+        // Every object is retired after its second use.
+        assert_eq!(created.load(Ordering::Relaxed), 3);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_with_max_age() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_max_age(Duration::from_millis(100));
+        pool.reserve(2);
+        assert_eq!(pool.maintain(), 0);
+        thread::sleep(Duration::from_millis(150));
+        let fresh = pool.get();
+        // Both expired objects were retired instead of being handed out.
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 0);
+        drop(fresh);
+        assert_eq!(pool.maintain(), 0);
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(pool.maintain(), 1);
+        assert_eq!(pool.len(), 0);
+    }
+
+    #[test]
+    fn test_adopt() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        pool.adopt(Box::new(TestObject {
+            value: 7
+        }));
+        pool.adopt_value(TestObject {
+            value: 8
+        });
+        assert_eq!((pool.len(), pool.available()), (2, 2));
+        let a = pool.get();
+        let b = pool.get();
+        assert_eq!(a.value + b.value, 15);
+        drop((a, b));
+        assert_eq!(pool.get().value, 0);
+    }
+
+    #[test]
+    fn test_seed_from_iterator() {
+        let mut pool: AutoReturnObjectPool<TestObject> = (1..=3).map(|value| TestObject {
+            value
+        }).collect();
+        assert_eq!(pool.available(), 3);
+        pool.extend([TestObject {
+            value: 4
+        }]);
+        let seeded: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        assert_eq!(seeded.iter().map(|obj| obj.value).sum::<i32>(), 10);
+        assert_eq!(pool.len(), 4);
+    }
+
+    #[test]
+    fn test_with_max_size() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_max_size(2, ExhaustedBehavior::Fail);
+        pool.reserve(5);
+        assert_eq!(pool.len(), 2);
+        let held = (pool.get(), pool.get());
+        assert_eq!(pool.checked_get().err(), Some(PoolError::Exhausted));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| pool.get()));
+        assert!(result.is_err());
+        drop(held);
+        assert!(pool.checked_get().is_ok());
+
+        let growing = AutoReturnObjectPool::<TestObject>::new().with_max_size(1, ExhaustedBehavior::Grow);
+        let held = (growing.get(), growing.get());
+        assert_eq!(growing.len(), 2);
+        drop(held);
+    }
+
+    #[test]
+    fn test_try_get_idle() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        assert!(pool.try_get_idle().is_none());
+        assert_eq!(pool.len(), 0);
+        pool.reserve(1);
+        let mut obj = pool.try_get_idle().unwrap();
+        obj.value = 5;
+        assert!(pool.try_get_idle().is_none());
+        drop(obj);
+        assert_eq!(pool.try_get_idle().unwrap().value, 0);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_scope() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let sum = pool.scope(|s| {
+            let a = s.get();
+            let b = s.get();
+            a.value = 2;
+            b.value = 3;
+            assert_eq!((s.len(), pool.in_use()), (2, 2));
+            a.value + b.value
+        });
+        assert_eq!(sum, 5);
+        assert_eq!((pool.in_use(), pool.available()), (0, 2));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.get().value = 7;
+                panic!("frame failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(pool.in_use(), 0);
+        assert!(pool.get_array::<2>().iter().all(|obj| obj.value == 0));
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let pool = AutoReturnObjectPool::<TestObject>::with_capacity(5);
+        let held = pool.get();
+        let worker = pool.split(3);
+        assert_eq!((pool.len(), pool.available()), (2, 1));
+        assert_eq!((worker.len(), worker.available()), (3, 3));
+        assert_eq!(pool.split(5).len(), 1);
+        worker.get().value = 4;
+        assert_eq!(pool.merge(&worker), 3);
+        assert_eq!(pool.merge(&pool), 0);
+        assert_eq!((worker.len(), pool.available()), (0, 3));
+        drop(held);
+        assert_eq!((pool.len(), pool.available()), (4, 4));
+        assert!(pool.get_array::<4>().iter().all(|obj| obj.value == 0));
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut template = ObjectPool::<TestObject>::new().with_max_size(4, ExhaustedBehavior::Fail);
+        template.adopt_value(TestObject {
+            value: 1
+        });
+        template.adopt_value(TestObject {
+            value: 2
+        });
+        let held = template.get();
+        let tenant = AutoReturnObjectPool::from_raw(template.clone());
+        assert_eq!((tenant.len(), tenant.available()), (1, 1));
+        let mut copy = tenant.get();
+        assert_eq!(copy.value, 1);
+        copy.value = 3;
+        drop(copy);
+        // The clone is independent: its objects and limits are its own.
+        let original = template.try_get_idle().unwrap();
+        assert_eq!(unsafe { (*original).value }, 1);
+        let all = tenant.get_array::<4>();
+        assert!(tenant.checked_get().is_err());
+        drop(all);
+        unsafe {
+            template.release(original);
+            template.release(held);
+        }
+    }
+
+    #[test]
+    fn test_try_reserve() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        assert_eq!(pool.try_reserve(3), Ok(()));
+        assert_eq!((pool.len(), pool.available()), (3, 3));
+        // The bookkeeping cannot grow this far, so nothing is created and nothing aborts.
+        assert!(matches!(pool.try_reserve(usize::MAX), Err(ReserveError::Bookkeeping(_))));
+        assert_eq!(pool.len(), 3);
+        let capped = AutoReturnObjectPool::from_raw(ObjectPool::<TestObject>::new().with_max_size(2, ExhaustedBehavior::Fail));
+        assert_eq!(capped.try_reserve(usize::MAX), Ok(()));
+        assert_eq!(capped.len(), 2);
+    }
+
+    #[test]
+    fn test_slab_storage() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_chunk_size(4);
+        pool.reserve(4);
+        let mut addresses: Vec<usize> = pool.get_array::<4>().iter().map(|obj| obj.ref_item() as *const TestObject as usize).collect();
+        addresses.sort_unstable();
+        // The first four objects share one slab, evenly spaced.
+        let stride = addresses[1] - addresses[0];
+        assert!(addresses.windows(2).all(|pair| pair[1] - pair[0] == stride));
+        pool.reserve(2);
+        let worker = pool.split(5);
+        drop(pool);
+        // Objects keep their slab alive after moving to another pool and outliving the first one.
+        let mut obj = worker.get();
+        obj.value = 3;
+        drop(obj);
+        assert_eq!(worker.shrink_to(1), 4);
+        assert_eq!(worker.get().value, 0);
+    }
+
+    #[test]
+    fn test_with_prefetch() {
+        // Asking for more lines than the object covers only prefetches the object itself.
+        let pool = AutoReturnObjectPool::with_factory(|| [0u64; 40]).with_reset(|buffer| buffer.fill(0)).with_prefetch(16);
+        pool.get()[39] = 1;
+        assert_eq!(pool.get()[39], 0);
+    }
+
+    #[test]
+    fn test_with_cache_line_padding() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_cache_line_padding();
+        pool.reserve(3);
+        let mut addresses: Vec<usize> = pool.get_array::<3>().iter().map(|obj| obj.ref_item() as *const TestObject as usize).collect();
+        addresses.sort_unstable();
+        // Every object starts its own cache line, and no two share one.
+        assert!(addresses.iter().all(|address| address.is_multiple_of(CACHE_LINE)));
+        assert!(addresses.windows(2).all(|pair| pair[1] - pair[0] >= CACHE_LINE));
+    }
+
+    #[test]
+    fn test_with_alignment() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_alignment(4096).with_alignment(64);
+        pool.reserve(2);
+        let pair = pool.get_array::<2>();
+        // The larger alignment wins, and the page aligned objects go to separate slabs.
+        assert!(pair.iter().all(|obj| (obj.ref_item() as *const TestObject as usize).is_multiple_of(4096)));
+    }
+
+    #[test]
+    #[should_panic(expected = "not a power of two")]
+    fn test_with_alignment_rejects_invalid() {
+        let _ = ObjectPool::<TestObject>::new().with_alignment(48);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore = "Miri cannot run mmap")]
+    fn test_with_huge_pages() {
+        for huge_pages in [HugePages::Transparent, HugePages::Explicit] {
+            let pool = AutoReturnObjectPool::<TestObject>::new().with_huge_pages(huge_pages);
+            pool.reserve(2);
+            let mut pair = pool.get_array::<2>();
+            pair[1].value = 1;
+            let addresses = pair.each_ref().map(|obj| obj.ref_item() as *const TestObject as usize);
+            // Both objects share one slab, which starts a huge page.
+            let huge_page = crate::pages::HUGE_PAGE_SIZE;
+            assert_eq!(addresses[0] / huge_page, addresses[1] / huge_page);
+            drop(pair);
+            assert_eq!(pool.get().value, 0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore = "Miri cannot run madvise")]
+    fn test_with_decommit() {
+        struct Buffer([u8; 10000]);
+
+        impl Poolable for Buffer {
+            fn new() -> Buffer {
+                Buffer([0; 10000])
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        let pool = AutoReturnObjectPool::<Buffer>::new().with_decommit().with_chunk_size(4);
+        pool.reserve(4);
+        let mut buffers = Vec::new();
+        for (value, mut buffer) in (1..).zip(pool.get_array::<4>()) {
+            buffer.0.fill(value);
+            buffers.push(buffer.0.as_ptr());
+        }
+        assert_eq!(pool.shrink_to(1), 3);
+        // The pages of the destroyed neighbours are gone, but the object left in the slab is untouched.
+        let kept = pool.get();
+        let value = kept.0[0];
+        assert!(value > 0 && kept.0.iter().all(|&byte| byte == value));
+        let page_size = crate::pages::page_size();
+        for destroyed in buffers.into_iter().filter(|&buffer| buffer != kept.0.as_ptr()) {
+            let start = destroyed.align_offset(page_size);
+            let whole = (10000 - start) / page_size * page_size;
+            assert!(whole > 0);
+            // SAFETY: the kept object holds the slab, so the decommitted pages are still mapped and read as zeros.
+            assert!((start..start + whole).all(|offset| unsafe { std::ptr::read_volatile(destroyed.add(offset)) } == 0));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore = "Miri cannot run mlock")]
+    fn test_with_secure_memory() {
+        // No other test locks memory, so the locked size of the process only changes here.
+        fn locked_kb() -> usize {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            let line = status.lines().find(|line| line.starts_with("VmLck:")).unwrap();
+            line.split_whitespace().nth(1).unwrap().parse().unwrap()
+        }
+
+        let before = locked_kb();
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_secure_memory();
+        pool.get().value = 7;
+        assert!(locked_kb() > before);
+        assert_eq!(pool.get().value, 0);
+        drop(pool);
+        assert_eq!(locked_kb(), before);
+    }
+
+    #[test]
+    fn test_with_zeroize() {
+        struct Session {
+            key: [u8; 16]
+        }
+
+        impl Poolable for Session {
+            fn new() -> Session {
+                Session {
+                    key: [0; 16]
+                }
+            }
+
+            // The key is left alone, so only the zeroize clears it.
+            fn reset(&mut self) {}
+        }
+
+        let seen = Arc::new(AtomicUsize::new(0));
+        let sum = Arc::clone(&seen);
+        let pool = AutoReturnObjectPool::<Session>::new()
+            .with_zeroize(|session| &mut session.key)
+            .with_on_release(move |session| {
+                sum.store(session.key.iter().map(|&byte| byte as usize).sum(), Ordering::Relaxed);
+            });
+        pool.get().key = [0xAA; 16];
+        // The hook still sees the key, but the idle object no longer holds it.
+        assert_eq!(seen.load(Ordering::Relaxed), 0xAA * 16);
+        assert_eq!(pool.get().key, [0; 16]);
+    }
+
+    #[test]
+    #[cfg(feature = "allocator_api")]
+    fn test_with_allocator() {
+        use std::alloc::{AllocError, Allocator, Global, Layout};
+        use std::ptr::NonNull;
+
+        struct CountingAllocator(Arc<AtomicUsize>);
+
+        unsafe impl Allocator for CountingAllocator {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+                unsafe {
+                    Global.deallocate(ptr, layout);
+                }
+            }
+        }
+
+        let slabs = Arc::new(AtomicUsize::new(0));
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_chunk_size(2).with_allocator(CountingAllocator(slabs.clone()));
+        pool.reserve(3);
+        assert_eq!(slabs.load(Ordering::SeqCst), 2);
+        let worker = pool.split(2);
+        drop(pool);
+        // The slabs are freed by their allocator once their last object is gone, wherever it ended up.
+        assert_eq!(slabs.load(Ordering::SeqCst), 2);
+        drop(worker);
+        assert_eq!(slabs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_get_array() {
+        let pool = AutoReturnObjectPool::<TestObject>::new();
+        let [mut front, back, spare] = pool.get_array::<3>();
+        front.value = 1;
+        assert_eq!((back.value, spare.value), (0, 0));
+        assert_eq!(pool.in_use(), 3);
+        drop((front, back, spare));
+        assert!(pool.get_array::<3>().iter().all(|obj| obj.value == 0));
+        assert_eq!((pool.len(), pool.available()), (3, 3));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let pool = AutoReturnObjectPool::<TestObject>::with_capacity(3);
+        assert_eq!((pool.len(), pool.available()), (3, 3));
+        let held: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        assert_eq!(pool.len(), 3);
+        drop(held);
+        assert_eq!(ObjectPool::<TestObject>::with_capacity(0).len(), 0);
+    }
+
+    #[test]
+    fn test_shrink_to() {
+        let pool = AutoReturnObjectPool::<TestObject>::with_capacity(5);
+        let held = pool.get();
+        assert_eq!(pool.shrink_to(2), 2);
+        assert_eq!((pool.len(), pool.available()), (3, 2));
+        assert_eq!(pool.shrink_to(4), 0);
+        assert_eq!(pool.shrink_to_fit(), 2);
+        assert_eq!((pool.len(), pool.in_use()), (1, 1));
+        drop(held);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_available_list_spills_past_inline() {
+        let mut pool = ObjectPool::<TestObject>::new().with_reuse_order(ReuseOrder::Fifo);
+        let items: Vec<_> = (0..INLINE_IDLE + 4).map(|_| pool.get()).collect();
+        for &item in &items {
+            unsafe {
+                pool.release(item);
+            }
+        }
+        assert_eq!(pool.shrink_to(INLINE_IDLE - 1), 5);
+        // The list moved back inline and kept the most recently released items in order.
+        let reused: Vec<_> = (0..INLINE_IDLE - 1).map(|_| pool.get()).collect();
+        assert_eq!(reused, items[5..]);
+        for item in reused {
+            unsafe {
+                pool.release(item);
+            }
+        }
+        assert_eq!(pool.available(), INLINE_IDLE - 1);
+    }
+
+    #[test]
+    fn test_max_idle() {
+        let destroyed = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&destroyed);
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_max_idle(1).with_on_destroy(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let burst: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        assert_eq!(pool.in_use(), 3);
+        drop(burst);
+        assert_eq!((pool.len(), pool.available()), (1, 1));
+        assert_eq!(destroyed.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_min_idle() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_min_idle(2).with_max_size(3, ExhaustedBehavior::Fail);
+        assert_eq!(pool.maintain(), 0);
+        assert_eq!(pool.available(), 2);
+        let held = (pool.get(), pool.get());
+        pool.maintain();
+        // Only one more object fits under the maximum size.
+        assert_eq!((pool.len(), pool.available()), (3, 1));
+        drop(held);
+        pool.maintain();
+        assert_eq!(pool.len(), 3);
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_idle_timeout(Duration::from_millis(100)).with_min_idle(1);
+        let burst: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        drop(burst);
+        assert_eq!(pool.maintain(), 0);
+        thread::sleep(Duration::from_millis(150));
+        let recent = pool.get();
+        drop(recent);
+        assert_eq!(pool.maintain(), 2);
+        assert_eq!(pool.available(), 1);
+        thread::sleep(Duration::from_millis(150));
+        // The last object has timed out too, but the `min_idle` floor keeps it.
+        assert_eq!(pool.maintain(), 0);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_retain() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().without_reset();
+        let mut objs: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        for (value, obj) in objs.iter_mut().enumerate() {
+            obj.value = value as i32;
+        }
+        let held = objs.pop().unwrap();
+        drop(objs);
+        assert_eq!(pool.retain(|obj| obj.value % 2 == 0), 1);
+        assert_eq!((pool.len(), pool.available()), (3, 2));
+        assert_eq!(held.value, 3);
+    }
+
+    #[test]
+    fn test_reuse_order() {
+        for (order, expected) in [(ReuseOrder::Lifo, [2, 1, 0]), (ReuseOrder::Fifo, [0, 1, 2])] {
+            let pool = AutoReturnObjectPool::<TestObject>::new().without_reset().with_reuse_order(order);
+            let mut objs: Vec<_> = (0..3).map(|_| pool.get()).collect();
+            for (value, obj) in objs.iter_mut().enumerate() {
+                obj.value = value as i32;
+            }
+            for obj in objs {
+                drop(obj);
+            }
+            let reused: Vec<_> = (0..3).map(|_| pool.get()).collect();
+            assert_eq!(reused.iter().map(|obj| obj.value).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_growth_policy() {
+        let pool = AutoReturnObjectPool::<TestObject>::new().with_growth_policy(GrowByChunk(4));
+        let first = pool.get();
+        assert_eq!((pool.len(), pool.available()), (4, 3));
+        drop(first);
+
+        let doubling = AutoReturnObjectPool::<TestObject>::new().with_growth_policy(Doubling).with_max_size(6, ExhaustedBehavior::Fail);
+        let held: Vec<_> = (0..4).map(|_| doubling.get()).collect();
+        assert_eq!(doubling.len(), 4);
+        let fifth = doubling.get();
+        // Doubling would create four more, but the maximum size leaves room for two.
+        assert_eq!((doubling.len(), doubling.available()), (6, 1));
+        drop((held, fifth));
+
+        let fixed = AutoReturnObjectPool::<TestObject>::new().with_growth_policy(NoGrowth);
+        assert_eq!(fixed.checked_get().err(), Some(PoolError::Exhausted));
+        fixed.reserve(1);
+        assert!(fixed.checked_get().is_ok());
+    }
+
+    #[test]
+    fn test_freeze() {
+        let pool = AutoReturnObjectPool::<TestObject>::with_capacity(2);
+        pool.freeze();
+        let held = (pool.get(), pool.get());
+        assert_eq!(pool.checked_get().err(), Some(PoolError::Frozen));
+        assert!(matches!(pool.try_get_with(|| Ok::<_, ()>(TestObject::new())), Err(PoolError::Frozen)));
+        pool.reserve(1);
+        assert_eq!(pool.len(), 2);
+        drop(held);
+        assert!(pool.checked_get().is_ok());
+        pool.unfreeze();
+        let held: Vec<_> = (0..3).map(|_| pool.get()).collect();
+        assert_eq!(held.len(), 3);
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        struct Buffer(Vec<u8>);
+
+        impl Poolable for Buffer {
+            fn new() -> Buffer {
+                Buffer(Vec::with_capacity(1024))
+            }
+
+            fn reset(&mut self) {
+                self.0.clear();
+            }
+
+            fn size_hint(&self) -> usize {
+                self.0.capacity()
+            }
+        }
+
+        let pool = AutoReturnObjectPool::<Buffer>::new().with_max_bytes(4096);
+        let mut held: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        assert_eq!(pool.bytes(), 4096);
+        assert!(pool.checked_get().is_err());
+        // A buffer that grew while in use pushes the pool over its budget, so it is destroyed on release.
+        held[0].0.reserve(8192);
+        held.remove(0);
+        assert_eq!((pool.len(), pool.bytes()), (3, 3072));
+        drop(held);
+        assert_eq!(pool.available(), 3);
+        assert!(pool.checked_get().is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+use std::alloc::Layout;
+use std::ffi::{c_int, c_long, c_void};
+use std::ptr::{self, NonNull};
+use std::sync::OnceLock;
+
+/// Size of a huge page, the default on x86_64 and aarch64 Linux.
+pub(crate) const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+const PROT_READ: c_int = 0x1;
+const PROT_WRITE: c_int = 0x2;
+const MAP_PRIVATE: c_int = 0x2;
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+const MAP_ANONYMOUS: c_int = 0x20;
+#[cfg(any(target_arch = "mips", target_arch = "mips64"))]
+const MAP_ANONYMOUS: c_int = 0x800;
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+const MAP_HUGETLB: c_int = 0x40000;
+#[cfg(any(target_arch = "mips", target_arch = "mips64"))]
+const MAP_HUGETLB: c_int = 0x80000;
+const MADV_DONTNEED: c_int = 4;
+const MADV_HUGEPAGE: c_int = 14;
+const MADV_DONTDUMP: c_int = 16;
+const MADV_DODUMP: c_int = 17;
+const _SC_PAGESIZE: c_int = 30;
+
+extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: c_long) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    fn sysconf(name: c_int) -> c_long;
+    fn mlock(addr: *const c_void, len: usize) -> c_int;
+    fn munlock(addr: *const c_void, len: usize) -> c_int;
+}
+
+/// Get the base page size of the system.
+pub(crate) fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    // SAFETY: sysconf has no preconditions.
+    *PAGE_SIZE.get_or_init(|| usize::try_from(unsafe { sysconf(_SC_PAGESIZE) }).unwrap_or(usize::MAX))
+}
+
+/// Get the number of bytes mapped for `layout`, a whole number of huge pages.
+fn mapped_len(layout: Layout) -> usize {
+    layout.size().next_multiple_of(HUGE_PAGE_SIZE)
+}
+
+/// Map `len` bytes of anonymous memory with extra mmap `flags`.
+fn map(len: usize, flags: c_int) -> Option<NonNull<u8>> {
+    // SAFETY: an anonymous mapping at an address of the kernel's choice does not touch existing memory.
+    let memory = unsafe { mmap(ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | flags, -1, 0) };
+    if memory as isize == -1 {
+        return None;
+    }
+    NonNull::new(memory.cast())
+}
+
+/// Map `len` bytes aligned to `align` and ask the kernel to back them with transparent huge pages.
+/// The mapping is made larger than needed and trimmed, since mmap only aligns to the base page size.
+fn map_transparent(len: usize, align: usize) -> Option<NonNull<u8>> {
+    let memory = map(len + align, 0)?;
+    let head = memory.as_ptr().align_offset(align);
+    // SAFETY: the unmapped head and tail lie within the mapping and are never used.
+    unsafe {
+        let start = memory.as_ptr().add(head);
+        if head > 0 {
+            munmap(memory.as_ptr().cast(), head);
+        }
+        if align - head > 0 {
+            munmap(start.add(len).cast(), align - head);
+        }
+        // The advice is a hint; without THP support the memory is simply backed by base pages.
+        madvise(start.cast(), len, MADV_HUGEPAGE);
+        Some(NonNull::new_unchecked(start))
+    }
+}
+
+/// Allocate memory for `layout` from huge pages, explicitly reserved ones if `explicit` is set and any are free,
+/// transparent ones otherwise.
+pub(crate) fn allocate(layout: Layout, explicit: bool) -> Option<NonNull<u8>> {
+    let len = mapped_len(layout);
+    // Explicit huge page mappings are aligned to the huge page size.
+    let reserved = if explicit && layout.align() <= HUGE_PAGE_SIZE { map(len, MAP_HUGETLB) } else { None };
+    reserved.or_else(|| map_transparent(len, layout.align().max(HUGE_PAGE_SIZE)))
+}
+
+/// Unmap memory returned by `allocate`.
+///
+/// # Safety
+/// `memory` must have been returned by `allocate` for `layout` and must not be used afterwards.
+pub(crate) unsafe fn deallocate(memory: NonNull<u8>, layout: Layout) {
+    unsafe {
+        munmap(memory.as_ptr().cast(), mapped_len(layout));
+    }
+}
+
+/// Give the pages that lie entirely within `len` bytes at `memory` back to the kernel. The memory stays mapped,
+/// and reading it afterwards yields zeros; parts of pages at either end are left alone.
+///
+/// # Safety
+/// The memory must be mapped, private, and must not hold anything that is still used.
+pub(crate) unsafe fn decommit(memory: *mut u8, len: usize) {
+    let page_size = page_size();
+    let start = memory.align_offset(page_size);
+    if page_size == usize::MAX || start >= len {
+        return;
+    }
+    let whole = (len - start) / page_size * page_size;
+    if whole > 0 {
+        unsafe {
+            madvise(memory.add(start).cast(), whole, MADV_DONTNEED);
+        }
+    }
+}
+
+/// Round `layout` out to whole pages, or None if the page size is unknown or the size overflows.
+pub(crate) fn page_layout(layout: Layout) -> Option<Layout> {
+    let page_size = page_size();
+    let size = layout.size().checked_next_multiple_of(page_size)?;
+    Layout::from_size_align(size, layout.align().max(page_size)).ok()
+}
+
+/// Lock `len` bytes at `memory` into memory and exclude them from core dumps. Returns false if locking failed,
+/// e.g. because of `RLIMIT_MEMLOCK`.
+///
+/// # Safety
+/// The memory must be mapped, and its pages must not be locked or unlocked by anyone else.
+pub(crate) unsafe fn lock(memory: *mut u8, len: usize) -> bool {
+    unsafe {
+        if mlock(memory.cast(), len) != 0 {
+            return false;
+        }
+        // Only a hint: memory of a mapping that cannot be excluded is simply dumped.
+        madvise(memory.cast(), len, MADV_DONTDUMP);
+    }
+    true
+}
+
+/// Unlock memory locked with `lock` and include it in core dumps again, since the allocator may reuse it.
+///
+/// # Safety
+/// Same as `lock`.
+pub(crate) unsafe fn unlock(memory: *mut u8, len: usize) {
+    unsafe {
+        madvise(memory.cast(), len, MADV_DODUMP);
+        munlock(memory.cast(), len);
+    }
+}
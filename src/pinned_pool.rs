@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::pin::Pin;
+
+use crate::object_pool::object_pool::{ObjectPool, Poolable};
+
+/// Variant of AutoReturnObjectPool whose guards hand out pinned references.
+///
+/// Every object lives in an entry of a slab shared with other objects. Entries never move within
+/// their slab, and a slab outlives its last entry, so an object keeps its address from creation
+/// until it is dropped. The pool never moves an object out of its entry either: there is no extract
+/// or into_inner for pinned guards. This upholds the `Pin` contract, so self-referential types can
+/// be pooled.
+/// `Poolable::reset` is called through `&mut T` on a pinned object, just like `Drop::drop`,
+/// so implementations for `!Unpin` types must not move out of `self`.
+pub struct PinnedPool<T: Poolable> {
+    pool: RefCell<ObjectPool<T>>
+}
+
+impl<T: Poolable> Default for PinnedPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> PinnedPool<T> {
+    /// Create a new PinnedPool.
+    pub fn new() -> PinnedPool<T> {
+        PinnedPool {
+            pool: RefCell::new(ObjectPool::new())
+        }
+    }
+
+    /// Get a PinnedPoolBox from the pool.
+    #[track_caller]
+    pub fn get(&self) -> PinnedPoolBox<'_, T> {
+        let mut raw = self.pool.borrow_mut();
+        let item = raw.get();
+        PinnedPoolBox {
+            item,
+            generation: raw.generation(),
+            pool: &self.pool
+        }
+    }
+
+    /// Reserve a number of items in the pool.
+    #[track_caller]
+    pub fn reserve(&self, count: usize) {
+        self.pool.borrow_mut().reserve(count);
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.pool.borrow().len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.pool.borrow().is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.pool.borrow().available()
+    }
+}
+
+/// Guard handed out by a PinnedPool. The item stays pinned until it is dropped.
+pub struct PinnedPoolBox<'a, T: Poolable> {
+    item: *mut T,
+    generation: u64,
+    pool: &'a RefCell<ObjectPool<T>>
+}
+
+impl<T: Poolable> PinnedPoolBox<'_, T> {
+    /// Get a pinned reference to the item.
+    pub fn as_ref(&self) -> Pin<&T> {
+        // SAFETY: the item never moves while it is owned by a pool or guard.
+        unsafe {
+            Pin::new_unchecked(&*self.item)
+        }
+    }
+
+    /// Get a pinned mutable reference to the item.
+    pub fn as_mut(&mut self) -> Pin<&mut T> {
+        // SAFETY: the item never moves while it is owned by a pool or guard.
+        unsafe {
+            Pin::new_unchecked(&mut *self.item)
+        }
+    }
+
+    /// Get the address of the item, which is stable for the item's lifetime.
+    pub fn as_ptr(&self) -> *const T {
+        self.item
+    }
+}
+
+impl<T: Poolable> Drop for PinnedPoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.pool.borrow_mut().release_from(self.item, self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::marker::PhantomPinned;
+    use std::ptr;
+
+    /// Object that points into itself once it has been pinned.
+    struct SelfReferential {
+        value: i32,
+        this: *const i32,
+        _pinned: PhantomPinned
+    }
+
+    impl SelfReferential {
+        fn init(self: Pin<&mut Self>) {
+            unsafe {
+                let this = self.get_unchecked_mut();
+                this.this = &this.value;
+            }
+        }
+
+        fn is_intact(&self) -> bool {
+            self.this.is_null() || ptr::eq(self.this, &self.value)
+        }
+    }
+
+    impl Poolable for SelfReferential {
+        fn new() -> SelfReferential {
+            SelfReferential {
+                value: 0,
+                this: ptr::null(),
+                _pinned: PhantomPinned
+            }
+        }
+
+        fn reset(&mut self) {
+            self.value = 0;
+        }
+    }
+
+    #[test]
+    fn test_pinned_pool() {
+        let pool = PinnedPool::<SelfReferential>::new();
+        let mut obj = pool.get();
+        obj.as_mut().init();
+        let address = obj.as_ptr();
+        drop(obj);
+
+        // Many more objects are created, but the first one stays where it was.
+        let objs: Vec<_> = (0..32).map(|_| pool.get()).collect();
+        assert!(objs.iter().any(|obj| ptr::eq(obj.as_ptr(), address)));
+        assert!(objs.iter().all(|obj| obj.as_ref().is_intact()));
+        drop(objs);
+        assert_eq!(pool.available(), 32);
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::PoisonError;
+
+use crate::concurrent_pool::{ConcurrentObjectPool, ConcurrentPoolBox};
+use crate::object_pool::object_pool::{ObjectPool, PoolError, Poolable};
+use crate::sync::{Condvar, Mutex, MutexGuard};
+
+/// Share of a QuotaPool registered by one consumer.
+struct Share {
+    reserved: usize,
+    quota: usize,
+    held: usize
+}
+
+/// Accounting of a QuotaPool, protected by its mutex.
+struct QuotaState {
+    shares: Vec<Share>,
+    in_use: usize
+}
+
+impl QuotaState {
+    /// Get the number of items that are reserved for consumers but not held by them.
+    fn owed(&self) -> usize {
+        self.shares.iter().map(|share| share.reserved.saturating_sub(share.held)).sum()
+    }
+}
+
+/// Bounded pool shared by several consumers, such as the subsystems of a service.
+/// Every consumer registers with a reservation, the number of items it is guaranteed to get, and a quota,
+/// the number of items it may hold at most. Items beyond the reservations are shared first come, first served,
+/// so one misbehaving consumer can use up its quota but never the reservations of the others.
+pub struct QuotaPool<T: Send> {
+    pool: ConcurrentObjectPool<T>,
+    state: Mutex<QuotaState>,
+    released: Condvar,
+    max_size: usize
+}
+
+impl<T: Poolable + Send> QuotaPool<T> {
+    /// Create a QuotaPool that holds at most `max_size` items.
+    pub fn new(max_size: usize) -> QuotaPool<T> {
+        QuotaPool::from_raw(ObjectPool::new(), max_size)
+    }
+}
+
+impl<T: Send> QuotaPool<T> {
+    /// Wrap a raw pool, such as one created with `ObjectPool::with_factory`, holding at most `max_size` items.
+    pub fn from_raw(raw: ObjectPool<T>, max_size: usize) -> QuotaPool<T> {
+        QuotaPool {
+            pool: ConcurrentObjectPool::from_raw(raw, usize::MAX),
+            state: Mutex::new(QuotaState {
+                shares: Vec::new(),
+                in_use: 0
+            }),
+            released: Condvar::new(),
+            max_size
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QuotaState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a consumer that is guaranteed `reserved` items and may hold at most `quota` items.
+    /// Consumers stay registered for the lifetime of the pool.
+    /// Returns `PoolError::Exhausted` if the reservations would add up to more than the maximum size.
+    pub fn register(&self, reserved: usize, quota: usize) -> Result<Consumer<'_, T>, PoolError> {
+        let mut state = self.lock();
+        let reserved = reserved.min(quota);
+        let total: usize = state.shares.iter().map(|share| share.reserved).sum();
+        if total + reserved > self.max_size {
+            return Err(PoolError::Exhausted);
+        }
+        state.shares.push(Share {
+            reserved,
+            quota,
+            held: 0
+        });
+        Ok(Consumer {
+            pool: self,
+            id: state.shares.len() - 1
+        })
+    }
+
+    /// Check whether a consumer may take another item.
+    /// Within its reservation it always may; beyond it the item must not be owed to another consumer.
+    fn may_take(&self, state: &QuotaState, id: usize) -> bool {
+        let share = &state.shares[id];
+        share.held < share.quota && (share.held < share.reserved || state.in_use + state.owed() < self.max_size)
+    }
+
+    /// Take an item for a consumer whose share has room.
+    /// The share is claimed under the lock, so no other consumer can take it while the item is acquired,
+    /// and the guard gives it back if acquiring the item panics.
+    #[track_caller]
+    fn take(&self, mut state: MutexGuard<'_, QuotaState>, id: usize) -> QuotaPoolBox<'_, T> {
+        state.shares[id].held += 1;
+        state.in_use += 1;
+        drop(state);
+        let mut obj = QuotaPoolBox {
+            obj: None,
+            pool: self,
+            consumer: id
+        };
+        obj.obj = Some(self.pool.get());
+        obj
+    }
+
+    /// Give back the share of a released item and wake the consumers waiting for one.
+    fn give_back(&self, id: usize) {
+        let mut state = self.lock();
+        state.shares[id].held -= 1;
+        state.in_use -= 1;
+        drop(state);
+        self.released.notify_all();
+    }
+
+    /// Get the maximum number of items the pool holds.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Get the number of items held by consumers.
+    pub fn in_use(&self) -> usize {
+        self.lock().in_use
+    }
+}
+
+/// Consumer registered with `QuotaPool::register`. Items are acquired through it, so they count against its share.
+pub struct Consumer<'a, T: Send> {
+    pool: &'a QuotaPool<T>,
+    id: usize
+}
+
+impl<'a, T: Send> Consumer<'a, T> {
+    /// Get an item, waiting until the consumer's share allows one.
+    /// Within its reservation this only waits for items held beyond their reservations by others.
+    #[track_caller]
+    pub fn get(&self) -> QuotaPoolBox<'a, T> {
+        let mut state = self.pool.lock();
+        while !self.pool.may_take(&state, self.id) {
+            state = self.pool.released.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        self.pool.take(state, self.id)
+    }
+
+    /// Get an item if the consumer's share allows one right now.
+    #[track_caller]
+    pub fn try_get(&self) -> Option<QuotaPoolBox<'a, T>> {
+        let state = self.pool.lock();
+        if !self.pool.may_take(&state, self.id) {
+            return None;
+        }
+        Some(self.pool.take(state, self.id))
+    }
+
+    /// Get the number of items the consumer holds.
+    pub fn held(&self) -> usize {
+        self.pool.lock().shares[self.id].held
+    }
+}
+
+/// Guard handed out by a QuotaPool. Its item counts against the share of the consumer that acquired it
+/// until the guard is dropped.
+pub struct QuotaPoolBox<'a, T: Send> {
+    /// Always Some once the guard is handed out, until it is dropped.
+    obj: Option<ConcurrentPoolBox<'a, T>>,
+    pool: &'a QuotaPool<T>,
+    consumer: usize
+}
+
+impl<T: Send> Deref for QuotaPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.obj.as_ref().unwrap()
+    }
+}
+
+impl<T: Send> DerefMut for QuotaPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.obj.as_mut().unwrap()
+    }
+}
+
+impl<T: Send + fmt::Debug> fmt::Debug for QuotaPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Send> Drop for QuotaPoolBox<'_, T> {
+    fn drop(&mut self) {
+        // Return the item before its share, so the consumer woken next reuses it instead of creating one.
+        drop(self.obj.take());
+        self.pool.give_back(self.consumer);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_reservations_and_quotas() {
+        let pool = QuotaPool::<TestObject>::new(4);
+        let ingest = pool.register(2, 4).unwrap();
+        let bulk = pool.register(0, 3).unwrap();
+        assert_eq!(pool.register(3, 3).err(), Some(PoolError::Exhausted));
+
+        let mut greedy: Vec<_> = (0..3).map_while(|_| bulk.try_get()).collect();
+        // The bulk consumer got the two shared items but not the two reserved for ingest.
+        assert_eq!(greedy.len(), 2);
+        greedy[0].value = 1;
+        let reserved = (ingest.get(), ingest.get());
+        assert!(ingest.try_get().is_none());
+        assert_eq!((ingest.held(), pool.in_use()), (2, 4));
+        drop(reserved);
+        // The ingest reservation is free again, but still not shared.
+        assert!(bulk.try_get().is_none());
+        greedy.pop();
+        assert_eq!(ingest.try_get().unwrap().value, 0);
+    }
+
+    #[test]
+    fn test_get_waits_for_share() {
+        let pool = QuotaPool::<TestObject>::new(1);
+        let consumer = pool.register(0, 1).unwrap();
+        let held = consumer.get();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| consumer.get().value);
+            thread::sleep(Duration::from_millis(10));
+            drop(held);
+            assert_eq!(waiter.join().unwrap(), 0);
+        });
+        assert_eq!(pool.in_use(), 0);
+    }
+
+    #[test]
+    fn test_share_given_back_when_create_panics() {
+        static FAIL: AtomicBool = AtomicBool::new(true);
+        let raw = ObjectPool::with_factory(|| {
+            assert!(!FAIL.swap(false, Ordering::Relaxed), "create failed");
+            TestObject::new()
+        });
+        let pool = QuotaPool::from_raw(raw, 1);
+        let consumer = pool.register(1, 1).unwrap();
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| consumer.get())).is_err());
+        assert_eq!((consumer.held(), pool.in_use()), (0, 0));
+        assert_eq!(consumer.get().value, 0);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+use crate::concurrent_pool::ConcurrentObjectPool;
+
+/// A pool whose counters can be read through the registry.
+pub trait InspectPool: Send + Sync {
+    /// Get the current counters of the pool.
+    fn counters(&self) -> PoolCounters;
+}
+
+/// Counters of a single pool at the time it was inspected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolCounters {
+    /// Number of items owned by the pool.
+    pub size: usize,
+    /// Number of idle items.
+    pub available: usize,
+    /// Number of items currently handed out.
+    pub in_use: usize,
+    /// Largest number of items handed out at the same time.
+    pub high_water_mark: usize
+}
+
+/// Counters of a registered pool, together with the name it was registered under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Name the pool was registered under.
+    pub name: String,
+    /// Counters of the pool.
+    pub counters: PoolCounters
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counters = &self.counters;
+        write!(
+            f,
+            "{}: {} items, {} available, {} in use, high water mark {}",
+            self.name, counters.size, counters.available, counters.in_use, counters.high_water_mark
+        )
+    }
+}
+
+impl<T: Send> InspectPool for ConcurrentObjectPool<T> {
+    fn counters(&self) -> PoolCounters {
+        PoolCounters {
+            size: self.len(),
+            available: self.available(),
+            in_use: self.in_use(),
+            high_water_mark: self.high_water_mark()
+        }
+    }
+}
+
+/// A registered pool. Shared pools are held weakly so the registry never keeps them alive.
+enum Registered {
+    Static(&'static dyn InspectPool),
+    Shared(Weak<dyn InspectPool>)
+}
+
+impl Registered {
+    fn counters(&self) -> Option<PoolCounters> {
+        match self {
+            Registered::Static(pool) => Some(pool.counters()),
+            Registered::Shared(pool) => pool.upgrade().map(|pool| pool.counters())
+        }
+    }
+}
+
+static REGISTRY: Mutex<Vec<(String, Registered)>> = Mutex::new(Vec::new());
+
+/// Lock the registry, ignoring poisoning since every update leaves it consistent.
+fn lock() -> MutexGuard<'static, Vec<(String, Registered)>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Add a pool to the registry, replacing any pool registered under the same name.
+fn insert(name: String, pool: Registered) {
+    let mut registry = lock();
+    registry.retain(|(registered, _)| *registered != name);
+    registry.push((name, pool));
+}
+
+/// Register a pool under `name`. The registry does not keep the pool alive;
+/// it disappears from `inspect()` once the last `Arc` is dropped.
+pub fn register<P: InspectPool + 'static>(name: impl Into<String>, pool: &Arc<P>) {
+    let pool: Arc<dyn InspectPool> = pool.clone();
+    insert(name.into(), Registered::Shared(Arc::downgrade(&pool)));
+}
+
+/// Register a pool that lives for the rest of the process, such as one declared with `static_pool!`.
+pub fn register_static(name: impl Into<String>, pool: &'static dyn InspectPool) {
+    insert(name.into(), Registered::Static(pool));
+}
+
+/// Remove the pool registered under `name`. Returns false if no pool was registered under it.
+pub fn unregister(name: &str) -> bool {
+    let mut registry = lock();
+    let before = registry.len();
+    registry.retain(|(registered, _)| registered != name);
+    registry.len() != before
+}
+
+/// Get the counters of every live registered pool, in registration order.
+/// Pools that have been dropped are removed from the registry.
+pub fn inspect() -> Vec<PoolStats> {
+    let mut registry = lock();
+    let mut stats = Vec::with_capacity(registry.len());
+    registry.retain(|(name, pool)| match pool.counters() {
+        Some(counters) => {
+            stats.push(PoolStats { name: name.clone(), counters });
+            true
+        }
+        None => false
+    });
+    stats
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::test_support::TestObject;
+
+    fn find(name: &str) -> Option<PoolStats> {
+        inspect().into_iter().find(|stats| stats.name == name)
+    }
+
+    #[test]
+    fn test_registry() {
+        let pool = Arc::new(ConcurrentObjectPool::<TestObject>::new());
+        register("registry::test", &pool);
+        {
+            let mut first = pool.get();
+            first.value = 1;
+            let _second = pool.get();
+            assert_eq!(find("registry::test").unwrap().counters, PoolCounters {
+                size: 2,
+                available: 0,
+                in_use: 2,
+                high_water_mark: 2
+            });
+        }
+        let stats = find("registry::test").unwrap();
+        assert_eq!(stats.counters.available, 2);
+        assert_eq!(stats.counters.high_water_mark, 2);
+        assert_eq!(stats.to_string(), "registry::test: 2 items, 2 available, 0 in use, high water mark 2");
+
+        drop(pool);
+        assert!(find("registry::test").is_none());
+        assert!(!unregister("registry::test"));
+    }
+
+    #[test]
+    fn test_register_static() {
+        crate::static_pool!(POOL: TestObject);
+        register_static("registry::static", &*POOL);
+        let _obj = POOL.get();
+        assert_eq!(find("registry::static").unwrap().counters.in_use, 1);
+        assert!(unregister("registry::static"));
+        assert!(find("registry::static").is_none());
+    }
+}
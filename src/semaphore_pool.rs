@@ -0,0 +1,336 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::PoisonError;
+use std::task::{Context, Poll, Waker};
+
+use crate::async_pool::AsyncPoolable;
+use crate::sync::{Mutex, MutexGuard};
+
+/// State of a Semaphore, protected by its mutex.
+struct SemaphoreState {
+    permits: usize,
+    /// Tasks waiting for a permit in arrival order, each with its ticket and the waker of its last poll.
+    waiters: VecDeque<(u64, Waker)>,
+    /// Tickets that were granted a permit but have not been polled yet.
+    granted: HashSet<u64>,
+    next_ticket: u64
+}
+
+/// Fair async semaphore. A released permit is handed to the longest waiting task, and only that task is woken.
+pub struct Semaphore {
+    state: Mutex<SemaphoreState>
+}
+
+impl Semaphore {
+    /// Create a semaphore with `permits` permits.
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(SemaphoreState {
+                permits,
+                waiters: VecDeque::new(),
+                granted: HashSet::new(),
+                next_ticket: 0
+            })
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SemaphoreState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for a permit.
+    pub fn acquire(&self) -> AcquirePermit<'_> {
+        AcquirePermit {
+            semaphore: self,
+            ticket: None
+        }
+    }
+
+    /// Take a permit if one is free and no task is waiting.
+    pub fn try_acquire(&self) -> Option<Permit<'_>> {
+        let mut state = self.lock();
+        if state.permits == 0 || !state.waiters.is_empty() {
+            return None;
+        }
+        state.permits -= 1;
+        Some(Permit { semaphore: self })
+    }
+
+    /// Hand a permit to the longest waiting task, or make it free if no task is waiting.
+    fn release(&self) {
+        let mut state = self.lock();
+        match state.waiters.pop_front() {
+            Some((ticket, waker)) => {
+                state.granted.insert(ticket);
+                drop(state);
+                waker.wake();
+            }
+            None => state.permits += 1
+        }
+    }
+
+    /// Get the number of free permits.
+    pub fn available_permits(&self) -> usize {
+        self.lock().permits
+    }
+
+    /// Get the number of tasks waiting for a permit.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiters.len()
+    }
+}
+
+/// Future returned by `Semaphore::acquire`.
+/// It is cancellation safe: dropping it gives back a permit it was granted but has not picked up.
+#[must_use = "futures do nothing unless polled"]
+pub struct AcquirePermit<'a> {
+    semaphore: &'a Semaphore,
+    ticket: Option<u64>
+}
+
+impl<'a> Future for AcquirePermit<'a> {
+    type Output = Permit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit<'a>> {
+        let semaphore = self.semaphore;
+        let mut state = semaphore.lock();
+        let Some(ticket) = self.ticket else {
+            if state.permits > 0 && state.waiters.is_empty() {
+                state.permits -= 1;
+                return Poll::Ready(Permit { semaphore });
+            }
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiters.push_back((ticket, cx.waker().clone()));
+            self.ticket = Some(ticket);
+            return Poll::Pending;
+        };
+        if state.granted.remove(&ticket) {
+            self.ticket = None;
+            return Poll::Ready(Permit { semaphore });
+        }
+        if let Some((_, waker)) = state.waiters.iter_mut().find(|(waiting, _)| *waiting == ticket) {
+            waker.clone_from(cx.waker());
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for AcquirePermit<'_> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut state = self.semaphore.lock();
+        state.waiters.retain(|(waiting, _)| *waiting != ticket);
+        if state.granted.remove(&ticket) {
+            drop(state);
+            self.semaphore.release();
+        }
+    }
+}
+
+/// Permit of a Semaphore, given back when dropped.
+#[must_use]
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+/// Async pool whose capacity is enforced by a Semaphore. Acquiring first takes a permit and then pops or creates
+/// an object, and the guard carries the permit, so at most `max_size` guards exist at any time.
+/// Releasing a guard returns its object before its permit, and wakes exactly one waiting task.
+pub struct SemaphorePool<T: AsyncPoolable> {
+    permits: Semaphore,
+    /// Idle objects and whether they still have to be reset.
+    idle: Mutex<Vec<(Box<T>, bool)>>,
+    max_size: usize
+}
+
+impl<T: AsyncPoolable> SemaphorePool<T> {
+    /// Create a SemaphorePool with at most `max_size` objects.
+    pub fn new(max_size: usize) -> SemaphorePool<T> {
+        SemaphorePool {
+            permits: Semaphore::new(max_size),
+            idle: Mutex::new(Vec::new()),
+            max_size
+        }
+    }
+
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<(Box<T>, bool)>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Turn a permit into a guard with an idle object, or a new one if none is idle.
+    /// A permit guarantees room: every object that is not idle belongs to another permit holder.
+    async fn fulfil<'a>(&'a self, permit: Permit<'a>) -> SemaphorePoolBox<'a, T> {
+        let idle = self.lock_idle().pop();
+        let (item, dirty) = match idle {
+            Some(idle) => idle,
+            None => (Box::new(T::create().await), false)
+        };
+        let mut obj = SemaphorePoolBox {
+            item: ManuallyDrop::new(item),
+            permit: ManuallyDrop::new(permit),
+            pool: self
+        };
+        if dirty {
+            obj.item.reset().await;
+        }
+        obj
+    }
+
+    /// Acquire an object, waiting for a permit if `max_size` guards are out.
+    pub async fn acquire(&self) -> SemaphorePoolBox<'_, T> {
+        let permit = self.permits.acquire().await;
+        self.fulfil(permit).await
+    }
+
+    /// Acquire an object if a permit is free, without waiting.
+    pub async fn try_acquire(&self) -> Option<SemaphorePoolBox<'_, T>> {
+        let permit = self.permits.try_acquire()?;
+        Some(self.fulfil(permit).await)
+    }
+
+    /// Get the maximum number of objects, which is also the number of permits.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Get the number of free permits.
+    pub fn available_permits(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// Get the number of idle objects.
+    pub fn available(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    /// Get the number of tasks waiting for a permit.
+    pub fn waiting(&self) -> usize {
+        self.permits.waiting()
+    }
+}
+
+/// Guard handed out by a SemaphorePool. It carries the permit it was acquired with.
+/// The object is reset by the next task that acquires it.
+pub struct SemaphorePoolBox<'a, T: AsyncPoolable> {
+    item: ManuallyDrop<Box<T>>,
+    permit: ManuallyDrop<Permit<'a>>,
+    pool: &'a SemaphorePool<T>
+}
+
+impl<T: AsyncPoolable> Deref for SemaphorePoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl<T: AsyncPoolable> DerefMut for SemaphorePoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
+
+impl<T: AsyncPoolable + fmt::Debug> fmt::Debug for SemaphorePoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: AsyncPoolable> Drop for SemaphorePoolBox<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: neither field is used again after being taken here.
+        let (item, permit) = unsafe {
+            (ManuallyDrop::take(&mut self.item), ManuallyDrop::take(&mut self.permit))
+        };
+        // Return the object first, so the task woken by the permit finds it idle.
+        self.pool.lock_idle().push((item, true));
+        drop(permit);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+    use std::thread;
+
+    use crate::executor::block_on;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_semaphore_pool() {
+        let pool = SemaphorePool::<TestObject>::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut held = block_on(pool.acquire());
+        held.value = 3;
+        assert_eq!(pool.available_permits(), 0);
+        assert!(block_on(pool.try_acquire()).is_none());
+
+        let mut first = pin!(pool.acquire());
+        let mut second = pin!(pool.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        drop(held);
+        // Only the first waiter got the permit.
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(obj) = first.as_mut().poll(&mut cx) else {
+            panic!("the first waiter should be served first");
+        };
+        assert_eq!(obj.value, 0);
+        drop(obj);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(pool.available_permits(), 1);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_cancelled_permit_is_passed_on() {
+        let semaphore = Semaphore::new(1);
+        let mut cx = Context::from_waker(Waker::noop());
+        let permit = semaphore.try_acquire().unwrap();
+        let mut first = Box::pin(semaphore.acquire());
+        let mut second = pin!(semaphore.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        drop(permit);
+        drop(first);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(semaphore.waiting(), 0);
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn test_semaphore_pool_threads() {
+        let pool = SemaphorePool::<TestObject>::new(2);
+        thread::scope(|scope| {
+            for worker in 1..=4 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        block_on(async {
+                            let mut obj = pool.acquire().await;
+                            assert_eq!(obj.value, 0);
+                            obj.value = worker;
+                        });
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available_permits(), 2);
+        assert!(pool.available() <= 2);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::object_pool::object_pool::{ObjectPool, Poolable};
+use crate::sync::CachePadded;
+
+/// Source of shard ids; every thread takes the next id the first time it uses a sharded pool.
+static NEXT_SHARD_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SHARD_ID: usize = NEXT_SHARD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Thread safe pool split into independent shards, each a raw pool behind its own `Mutex`.
+/// Threads are spread over the shards round-robin, so threads on different shards never contend.
+/// When the local shard has no available item, it steals a batch of idle items from the fullest sibling before allocating.
+/// Every shard is padded to its own cache line, so locking one shard never slows down threads on its neighbours.
+pub struct ShardedPool<T: Poolable> {
+    shards: Box<[CachePadded<Mutex<ObjectPool<T>>>]>
+}
+
+impl<T: Poolable + Send> Default for ShardedPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable + Send> ShardedPool<T> {
+    /// Create a new ShardedPool with one shard per available CPU.
+    pub fn new() -> ShardedPool<T> {
+        let shards = thread::available_parallelism().map(|count| count.get()).unwrap_or(1);
+        ShardedPool::with_shards(shards)
+    }
+
+    /// Create a new ShardedPool with a given number of shards.
+    pub fn with_shards(shards: usize) -> ShardedPool<T> {
+        assert!(shards > 0, "ShardedPool needs at least one shard");
+        ShardedPool {
+            shards: (0..shards).map(|_| CachePadded::new(Mutex::new(ObjectPool::new()))).collect()
+        }
+    }
+
+    /// Lock the raw pool of a shard, ignoring poisoning like ConcurrentObjectPool does.
+    fn lock(&self, shard: usize) -> MutexGuard<'_, ObjectPool<T>> {
+        self.shards[shard].lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get the shard of the current thread.
+    pub fn local_shard(&self) -> usize {
+        SHARD_ID.with(|id| *id) % self.shards.len()
+    }
+
+    /// Get a ShardedPoolBox from the pool.
+    /// Uses an available item of the local shard. If the local shard has run dry, it steals half of the idle items
+    /// of the fullest sibling shard, and only allocates if every shard is empty.
+    #[track_caller]
+    pub fn get(&self) -> ShardedPoolBox<'_, T> {
+        let local = self.local_shard();
+        if self.lock(local).available() == 0 {
+            self.steal_into(local);
+        }
+        let mut raw = self.lock(local);
+        let item = raw.get();
+        ShardedPoolBox {
+            item,
+            generation: raw.generation(),
+            shard: local,
+            pool: self
+        }
+    }
+
+    /// Move half of the idle items of the fullest sibling shard into `shard`.
+    /// Only one shard is locked at a time, so concurrent steals cannot deadlock.
+    fn steal_into(&self, shard: usize) {
+        let fullest = (0..self.shards.len())
+            .filter(|&sibling| sibling != shard)
+            .map(|sibling| (self.lock(sibling).available(), sibling))
+            .max();
+        let Some((available, sibling)) = fullest else {
+            return;
+        };
+        if available == 0 {
+            return;
+        }
+        let stolen = self.lock(sibling).detach_idle(available.div_ceil(2));
+        self.lock(shard).adopt_idle(stolen);
+    }
+
+    /// Reserve a number of items, spread evenly over the shards.
+    #[track_caller]
+    pub fn reserve(&self, count: usize) {
+        let shards = self.shards.len();
+        for shard in 0..shards {
+            self.lock(shard).reserve(count / shards + usize::from(shard < count % shards));
+        }
+    }
+
+    /// Get the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len()).map(|shard| self.lock(shard).len()).sum()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        (0..self.shards.len()).map(|shard| self.lock(shard).available()).sum()
+    }
+
+    /// Get the number of available items in one shard.
+    pub fn shard_available(&self, shard: usize) -> usize {
+        self.lock(shard).available()
+    }
+}
+
+/// Guard handed out by a ShardedPool. It releases its item to the shard it came from.
+pub struct ShardedPoolBox<'a, T: Poolable + Send> {
+    item: *mut T,
+    generation: u64,
+    shard: usize,
+    pool: &'a ShardedPool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item and releases it through the shard's mutex.
+unsafe impl<T: Poolable + Send> Send for ShardedPoolBox<'_, T> {}
+unsafe impl<T: Poolable + Send + Sync> Sync for ShardedPoolBox<'_, T> {}
+
+impl<T: Poolable + Send> ShardedPoolBox<'_, T> {
+    /// Get the shard the item belongs to.
+    pub fn shard(&self) -> usize {
+        self.shard
+    }
+}
+
+impl<T: Poolable + Send> Deref for ShardedPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send> DerefMut for ShardedPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send + fmt::Debug> fmt::Debug for ShardedPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + Send> Drop for ShardedPoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.pool.lock(self.shard).release_from(self.item, self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_sharded_pool_steals_from_fullest_sibling() {
+        let pool = ShardedPool::<TestObject>::with_shards(3);
+        let local = pool.local_shard();
+        let (near, far) = ((local + 1) % 3, (local + 2) % 3);
+        pool.lock(near).reserve(1);
+        pool.lock(far).reserve(4);
+
+        let mut obj = pool.get();
+        assert_eq!(obj.shard(), local);
+        assert_eq!(pool.shard_available(local), 1);
+        assert_eq!(pool.shard_available(near), 1);
+        assert_eq!(pool.shard_available(far), 2);
+        obj.value = 2;
+        drop(obj);
+        assert_eq!(pool.shard_available(local), 2);
+        assert_eq!(pool.len(), 5);
+
+        // Once every shard is empty, the local shard grows.
+        let objs: Vec<_> = (0..6).map(|_| pool.get()).collect();
+        assert!(objs.iter().all(|obj| obj.shard() == local));
+        assert_eq!(pool.len(), 6);
+        drop(objs);
+        assert_eq!(pool.available(), 6);
+    }
+
+    #[test]
+    fn test_sharded_pool_threads() {
+        let pool = ShardedPool::<TestObject>::with_shards(4);
+        pool.reserve(6);
+        assert_eq!(pool.len(), 6);
+        thread::scope(|scope| {
+            for worker in 0..8 {
+                let pool = &pool;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        let mut obj = pool.get();
+                        assert_eq!(obj.value, 0);
+                        obj.value = worker;
+                    }
+                });
+            }
+        });
+        assert_eq!(pool.available(), pool.len());
+    }
+}
@@ -0,0 +1,493 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+
+use crate::object_pool::object_pool::{LeakReport, ObjectPool, PoolError, Poolable};
+
+/// Clone-able handle to a pool. Every handle and every guard shares ownership of the pool,
+/// so the pool lives until the last of them is dropped.
+/// The pool sits behind an `Arc` and a mutex, so handles and guards can be sent to other threads.
+pub struct SharedPool<T: Poolable> {
+    pool: Arc<Mutex<ObjectPool<T>>>
+}
+
+/// Lock a shared pool.
+/// A panic while the lock was held cannot leave the raw pool inconsistent, so poisoning is ignored.
+fn lock<T: Poolable>(pool: &Mutex<ObjectPool<T>>) -> MutexGuard<'_, ObjectPool<T>> {
+    pool.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<T: Poolable> Clone for SharedPool<T> {
+    fn clone(&self) -> Self {
+        SharedPool {
+            pool: Arc::clone(&self.pool)
+        }
+    }
+}
+
+impl<T: Poolable> Default for SharedPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Poolable> SharedPool<T> {
+    /// Lock the pool.
+    fn lock(&self) -> MutexGuard<'_, ObjectPool<T>> {
+        lock(&self.pool)
+    }
+
+    /// Create a new SharedPool.
+    pub fn new() -> SharedPool<T> {
+        SharedPool {
+            pool: Arc::new(Mutex::new(ObjectPool::new()))
+        }
+    }
+
+    /// Get a SharedPoolGuard from the pool.
+    #[track_caller]
+    pub fn get(&self) -> SharedPoolGuard<T> {
+        let mut raw = self.lock();
+        let item = raw.get();
+        SharedPoolGuard {
+            item,
+            generation: raw.generation(),
+            pool: Arc::clone(&self.pool)
+        }
+    }
+
+    /// Get a SharedPoolGuard from the pool and run `init` on its item before returning it.
+    /// If `init` panics, the item goes back to the pool.
+    #[track_caller]
+    pub fn get_with<F: FnOnce(&mut T)>(&self, init: F) -> SharedPoolGuard<T> {
+        let mut obj = self.get();
+        init(&mut obj);
+        obj
+    }
+
+    /// Get a WeakPoolGuard from the pool.
+    /// Unlike SharedPoolGuard, the guard does not keep the pool alive.
+    #[track_caller]
+    pub fn get_weak(&self) -> WeakPoolGuard<T> {
+        let mut raw = self.lock();
+        let item = raw.get();
+        WeakPoolGuard {
+            item,
+            generation: raw.generation(),
+            pool: Arc::downgrade(&self.pool)
+        }
+    }
+
+    /// Reserve a number of items in the pool.
+    #[track_caller]
+    pub fn reserve(&self, count: usize) {
+        self.lock().reserve(count);
+    }
+
+    /// Set the sink that receives a LeakReport if the pool is dropped while items are in use.
+    pub fn set_leak_sink<F: Fn(&LeakReport) + Send + 'static>(&self, sink: F) {
+        self.lock().set_leak_sink(sink);
+    }
+
+    /// Clear the pool completely.
+    /// Outstanding guards keep their item and free it when dropped.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Clear the pool completely, but only if no guards are outstanding.
+    pub fn try_clear(&self) -> Result<(), PoolError> {
+        self.lock().try_clear()
+    }
+
+    /// Free every available item, leaving outstanding guards untouched.
+    /// Returns the number of items freed.
+    pub fn clear_idle(&self) -> usize {
+        self.lock().clear_idle()
+    }
+
+    /// Free every idle item for which `keep` returns false, like `ObjectPool::retain`.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, keep: F) -> usize {
+        self.lock().retain(keep)
+    }
+
+    /// Free idle items until at most `idle` are left, like `ObjectPool::shrink_to`.
+    pub fn shrink_to(&self, idle: usize) -> usize {
+        self.lock().shrink_to(idle)
+    }
+
+    /// Free every idle item and release the memory the pool no longer needs, like `ObjectPool::shrink_to_fit`.
+    pub fn shrink_to_fit(&self) -> usize {
+        self.lock().shrink_to_fit()
+    }
+
+    /// Get the number of items in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Check whether the pool holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Get the number of available items in the pool.
+    pub fn available(&self) -> usize {
+        self.lock().available()
+    }
+
+    /// Get the number of items that are currently handed out.
+    pub fn in_use(&self) -> usize {
+        self.lock().in_use()
+    }
+}
+
+/// Guard handed out by a SharedPool. It keeps the pool alive and releases its item when dropped.
+pub struct SharedPoolGuard<T: Poolable> {
+    item: *mut T,
+    generation: u64,
+    pool: Arc<Mutex<ObjectPool<T>>>
+}
+
+// SAFETY: the guard has exclusive access to its item, and the pool it releases into is behind a mutex.
+unsafe impl<T: Poolable + Send> Send for SharedPoolGuard<T> {}
+unsafe impl<T: Poolable + Send + Sync> Sync for SharedPoolGuard<T> {}
+
+impl<T: Poolable> SharedPoolGuard<T> {
+    /// Get a reference to the item.
+    pub fn ref_item(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+
+    /// Get a mutable reference to the item.
+    pub fn ref_mut_item(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+
+    /// Get a handle to the pool this guard belongs to.
+    pub fn pool(&self) -> SharedPool<T> {
+        SharedPool {
+            pool: Arc::clone(&self.pool)
+        }
+    }
+
+    /// Turn the guard into a read-only SharedPoolBox that can be cloned.
+    pub fn into_shared(self) -> SharedPoolBox<T> {
+        SharedPoolBox {
+            guard: Arc::new(self)
+        }
+    }
+}
+
+impl<T: Poolable> Deref for SharedPoolGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.ref_item()
+    }
+}
+
+impl<T: Poolable> DerefMut for SharedPoolGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ref_mut_item()
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for SharedPoolGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + fmt::Display> fmt::Display for SharedPoolGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + PartialEq> PartialEq for SharedPoolGuard<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Poolable + Eq> Eq for SharedPoolGuard<T> {}
+
+impl<T: Poolable + PartialOrd> PartialOrd for SharedPoolGuard<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Poolable + Ord> Ord for SharedPoolGuard<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Poolable + Hash> Hash for SharedPoolGuard<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: Poolable> Drop for SharedPoolGuard<T> {
+    fn drop(&mut self) {
+        unsafe {
+            lock(&self.pool).release_from(self.item, self.generation);
+        }
+    }
+}
+
+/// Guard handed out by a SharedPool that only holds a weak reference to the pool.
+/// If the pool is dropped first, dropping the guard frees the item instead of releasing it.
+pub struct WeakPoolGuard<T: Poolable> {
+    item: *mut T,
+    generation: u64,
+    pool: Weak<Mutex<ObjectPool<T>>>
+}
+
+// SAFETY: as for SharedPoolGuard.
+unsafe impl<T: Poolable + Send> Send for WeakPoolGuard<T> {}
+unsafe impl<T: Poolable + Send + Sync> Sync for WeakPoolGuard<T> {}
+
+impl<T: Poolable> WeakPoolGuard<T> {
+    /// Get a reference to the item.
+    pub fn ref_item(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+
+    /// Get a mutable reference to the item.
+    pub fn ref_mut_item(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+
+    /// Get a handle to the pool this guard belongs to, if it is still alive.
+    pub fn pool(&self) -> Option<SharedPool<T>> {
+        self.pool.upgrade().map(|pool| SharedPool { pool })
+    }
+}
+
+impl<T: Poolable> Deref for WeakPoolGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.ref_item()
+    }
+}
+
+impl<T: Poolable> DerefMut for WeakPoolGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ref_mut_item()
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for WeakPoolGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + fmt::Display> fmt::Display for WeakPoolGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + PartialEq> PartialEq for WeakPoolGuard<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Poolable + Eq> Eq for WeakPoolGuard<T> {}
+
+impl<T: Poolable + PartialOrd> PartialOrd for WeakPoolGuard<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Poolable + Ord> Ord for WeakPoolGuard<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Poolable + Hash> Hash for WeakPoolGuard<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: Poolable> Drop for WeakPoolGuard<T> {
+    fn drop(&mut self) {
+        match self.pool.upgrade() {
+            Some(pool) => unsafe {
+                lock(&pool).release_from(self.item, self.generation);
+            },
+            None => unsafe {
+                ObjectPool::destroy(self.item);
+            }
+        }
+    }
+}
+
+/// Reference counted, read-only guard created with `SharedPoolGuard::into_shared`.
+/// Clones share one item, which returns to the pool when the last clone is dropped.
+pub struct SharedPoolBox<T: Poolable> {
+    guard: Arc<SharedPoolGuard<T>>
+}
+
+impl<T: Poolable> SharedPoolBox<T> {
+    /// Get the number of clones sharing the item.
+    pub fn share_count(&self) -> usize {
+        Arc::strong_count(&self.guard)
+    }
+}
+
+impl<T: Poolable> Clone for SharedPoolBox<T> {
+    fn clone(&self) -> Self {
+        SharedPoolBox {
+            guard: Arc::clone(&self.guard)
+        }
+    }
+}
+
+impl<T: Poolable> Deref for SharedPoolBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard.ref_item()
+    }
+}
+
+impl<T: Poolable + fmt::Debug> fmt::Debug for SharedPoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    thread_local! {
+        static DROPPED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn test_shared_pool() {
+        let pool = SharedPool::<TestObject>::new();
+        let other = pool.clone();
+        let mut obj = other.get();
+        obj.ref_mut_item().value = 7;
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.available(), 0);
+
+        // The guard keeps the pool alive after every handle is gone.
+        drop(pool);
+        drop(other);
+        assert_eq!(obj.ref_item().value, 7);
+        let pool = obj.pool();
+        drop(obj);
+        assert_eq!(pool.available(), 1);
+        assert_eq!(pool.get().ref_item().value, 0);
+    }
+
+    #[test]
+    fn test_shared_pool_across_threads() {
+        let pool = SharedPool::<TestObject>::new();
+        let workers: Vec<_> = (0..4).map(|worker| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                let mut obj = pool.get();
+                obj.value = worker;
+                obj
+            })
+        }).collect();
+        let guards: Vec<_> = workers.into_iter().map(|worker| worker.join().unwrap()).collect();
+        drop(pool);
+        // Guards returned from other threads still keep the pool alive.
+        let pool = guards[0].pool();
+        assert_eq!(pool.in_use(), 4);
+        drop(guards);
+        assert_eq!(pool.available(), 4);
+    }
+
+    struct DropCounter;
+
+    impl Poolable for DropCounter {
+        fn new() -> DropCounter {
+            DropCounter
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+        }
+    }
+
+    #[test]
+    fn test_weak_guard() {
+        let pool = SharedPool::<DropCounter>::new();
+        let first = pool.get_weak();
+        let second = pool.get_weak();
+        drop(first);
+        assert_eq!(pool.available(), 1);
+        assert!(second.pool().is_some());
+
+        // Dropping the pool frees the idle object but leaves the leased one to its guard.
+        drop(pool);
+        assert_eq!(DROPPED.with(Cell::get), 1);
+        assert!(second.pool().is_none());
+        drop(second);
+        assert_eq!(DROPPED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn test_shared_pool_box() {
+        let pool = SharedPool::<TestObject>::new();
+        let mut obj = pool.get();
+        obj.value = 11;
+        let shared = obj.into_shared();
+        let readers: Vec<_> = (0..3).map(|_| shared.clone()).collect();
+        assert_eq!(shared.share_count(), 4);
+        assert!(readers.iter().all(|reader| reader.value == 11));
+        drop(shared);
+        assert_eq!(pool.in_use(), 1);
+        drop(readers);
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn test_clear_with_outstanding_guards() {
+        let pool = SharedPool::<TestObject>::new();
+        let mut strong = pool.get();
+        let mut weak = pool.get_weak();
+        strong.value = 1;
+        weak.value = 2;
+        pool.clear();
+        assert_eq!(strong.value + weak.value, 3);
+        drop(strong);
+        drop(weak);
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.available(), 0);
+    }
+}
@@ -0,0 +1,63 @@
+/// Re-exported so `static_pool!` expands to the same `LazyLock` regardless of the caller's imports.
+#[doc(hidden)]
+pub use std::sync::LazyLock;
+
+/// Declare a process-wide ConcurrentObjectPool that is created on first use.
+///
+/// `static_pool!(NAME: Type);` declares an unbounded pool, and
+/// `static_pool!(NAME: Type = expr);` initializes the pool from `expr`, for example
+/// `ConcurrentObjectPool::bounded(8)`. Attributes and a visibility may precede the name.
+#[macro_export]
+macro_rules! static_pool {
+    ($(#[$attr:meta])* $vis:vis $name:ident: $ty:ty) => {
+        $crate::static_pool!($(#[$attr])* $vis $name: $ty = $crate::concurrent_pool::ConcurrentObjectPool::new());
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident: $ty:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis static $name: $crate::static_pool::LazyLock<$crate::concurrent_pool::ConcurrentObjectPool<$ty>> =
+            $crate::static_pool::LazyLock::new(|| $init);
+    };
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::thread;
+
+    use crate::concurrent_pool::ConcurrentObjectPool;
+    use crate::test_support::TestObject;
+
+    static_pool!(UNBOUNDED: TestObject);
+    static_pool!(
+        /// A pool holding at most two objects.
+        BOUNDED: TestObject = ConcurrentObjectPool::bounded(2)
+    );
+
+    fn touch() -> i32 {
+        let mut obj = UNBOUNDED.get();
+        obj.value += 1;
+        obj.value
+    }
+
+    #[test]
+    fn test_static_pool() {
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        assert_eq!(touch(), 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(UNBOUNDED.in_use(), 0);
+        assert!(UNBOUNDED.len() <= 4);
+    }
+
+    #[test]
+    fn test_bounded_static_pool() {
+        assert_eq!(BOUNDED.max_size(), 2);
+        let _first = BOUNDED.get();
+        let _second = BOUNDED.get();
+        assert!(BOUNDED.get_timeout(std::time::Duration::from_millis(10)).is_none());
+    }
+}
@@ -0,0 +1,52 @@
+// Synchronization primitives used by the thread safe pools.
+//
+// Building with `RUSTFLAGS="--cfg loom"` swaps them for the loom versions so the interleavings of the
+// concurrent, lock-free and array queue pools can be model-checked by the `loom_tests` modules.
+// The default build does not pull loom in; to run the models, add it to Cargo.toml with
+//
+//     [target.'cfg(loom)'.dev-dependencies]
+//     loom = "0.7"
+//
+// and run `RUSTFLAGS="--cfg loom" cargo test --release --lib loom`.
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Condvar, Mutex, MutexGuard};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{fence, AtomicU32, AtomicU64, AtomicUsize};
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{fence, AtomicU32, AtomicU64, AtomicUsize};
+
+/// Back off inside a spin loop. Under loom this yields so the model checker can schedule the other threads.
+#[inline]
+pub(crate) fn spin_loop() {
+    #[cfg(loom)]
+    loom::thread::yield_now();
+    #[cfg(not(loom))]
+    std::hint::spin_loop();
+}
+
+/// Pads and aligns a value to the length of a cache line, so that values written by different threads
+/// never share a cache line. x86_64 and aarch64 prefetch cache lines in pairs, so they use 128 bytes.
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
+#[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), repr(align(64)))]
+pub(crate) struct CachePadded<T> {
+    value: T
+}
+
+impl<T> CachePadded<T> {
+    /// Pad a value to a cache line.
+    pub(crate) const fn new(value: T) -> CachePadded<T> {
+        CachePadded { value }
+    }
+}
+
+impl<T> std::ops::Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
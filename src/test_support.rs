@@ -0,0 +1,21 @@
+// Fixtures shared by the unit tests of the pools.
+
+use crate::object_pool::object_pool::Poolable;
+
+/// Object whose `value` tests change while it is handed out; `reset` clears it, so a reused object reads 0.
+#[derive(Debug, Clone)]
+pub(crate) struct TestObject {
+    pub value: i32
+}
+
+impl Poolable for TestObject {
+    fn new() -> TestObject {
+        TestObject {
+            value: 0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+}
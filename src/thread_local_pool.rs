@@ -0,0 +1,331 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use crate::concurrent_pool::ConcurrentObjectPool;
+use crate::object_pool::object_pool::{ObjectPool, Poolable};
+
+/// Source of ids that key the per-thread caches of every ThreadLocalPool.
+static NEXT_POOL_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Per-thread caches of every ThreadLocalPool used on this thread, keyed by pool id.
+    static CACHES: RefCell<HashMap<u64, Box<dyn Cache>>> = RefCell::new(HashMap::new());
+}
+
+/// Cache of one ThreadLocalPool, with its item type erased so the caches of all pools share one map.
+trait Cache {
+    /// Whether the ThreadLocalPool of the cache is dropped, so the cache only waits to be purged.
+    fn is_stale(&self) -> bool;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Reset items cached by one thread for one pool. The items are handed out by the shared pool.
+struct LocalCache<T: Poolable + Send> {
+    items: Vec<(*mut T, u64)>,
+    shared: Weak<ConcurrentObjectPool<T>>,
+    /// Liveness token of the ThreadLocalPool that owns the cache.
+    owner: Weak<()>
+}
+
+impl<T: Poolable + Send + 'static> Cache for LocalCache<T> {
+    fn is_stale(&self) -> bool {
+        self.owner.strong_count() == 0
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl<T: Poolable + Send> LocalCache<T> {
+    /// Return cached items to the shared pool.
+    fn flush(&mut self, count: usize) {
+        let start = self.items.len().saturating_sub(count);
+        match self.shared.upgrade() {
+            Some(shared) => shared.restore(self.items.drain(start..)),
+            // The shared pool is gone and no longer tracks the items.
+            None => {
+                for (item, _) in self.items.drain(start..) {
+                    unsafe {
+                        ObjectPool::destroy(item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Poolable + Send> Drop for LocalCache<T> {
+    fn drop(&mut self) {
+        self.flush(self.items.len());
+    }
+}
+
+/// Thread safe pool that keeps a small cache of reset items per thread in front of a ConcurrentObjectPool.
+/// Acquiring and releasing only touch the shared pool when the local cache is empty or full,
+/// and then move half a cache worth of items under a single lock.
+/// A thread's cache is returned to the shared pool when the thread exits. Caches that other threads still hold
+/// when the pool is dropped are returned the next time those threads create a cache for any ThreadLocalPool.
+pub struct ThreadLocalPool<T: Poolable + Send + 'static> {
+    shared: Arc<ConcurrentObjectPool<T>>,
+    id: u64,
+    cache_size: usize,
+    /// Reset run by the releasing thread before caching an item, or None if releases must go through
+    /// the shared pool to run its hooks, validation and limits.
+    local_reset: Option<fn(&mut T)>,
+    /// Dropped with the pool, marking its caches as stale.
+    alive: Arc<()>
+}
+
+impl<T: Poolable + Send + 'static> ThreadLocalPool<T> {
+    /// Create a new ThreadLocalPool that caches up to `cache_size` items per thread.
+    pub fn new(cache_size: usize) -> ThreadLocalPool<T> {
+        ThreadLocalPool::from_shared(Arc::new(ConcurrentObjectPool::new()), cache_size)
+    }
+
+    /// Create a ThreadLocalPool that caches up to `cache_size` items per thread in front of `shared`,
+    /// such as a bounded pool. Cached items count as in use by the shared pool.
+    /// If releasing into `shared` runs more than a reset, such as hooks or validation, released items
+    /// bypass the caches and go straight back to `shared`.
+    pub fn from_shared(shared: Arc<ConcurrentObjectPool<T>>, cache_size: usize) -> ThreadLocalPool<T> {
+        ThreadLocalPool {
+            local_reset: shared.plain_reset(),
+            shared,
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            cache_size,
+            alive: Arc::new(())
+        }
+    }
+
+    /// Get the shared pool behind the per-thread caches.
+    pub fn shared(&self) -> &ConcurrentObjectPool<T> {
+        &self.shared
+    }
+
+    /// Run `f` on the cache of the current thread, or return None if thread locals are already destroyed.
+    fn with_cache<R>(&self, f: impl FnOnce(&mut LocalCache<T>) -> R) -> Option<R> {
+        CACHES.try_with(|caches| {
+            let mut caches = caches.borrow_mut();
+            if !caches.contains_key(&self.id) {
+                // Return the caches of dropped pools before adding a new one.
+                caches.retain(|_, cache| !cache.is_stale());
+                caches.insert(self.id, Box::new(LocalCache {
+                    items: Vec::with_capacity(self.cache_size),
+                    shared: Arc::downgrade(&self.shared),
+                    owner: Arc::downgrade(&self.alive)
+                }));
+            }
+            let cache = caches.get_mut(&self.id).expect("thread local cache was just inserted");
+            f(cache.as_any_mut().downcast_mut().expect("thread local cache has the wrong item type"))
+        }).ok()
+    }
+
+    /// Get a ThreadLocalPoolBox, from the local cache if possible.
+    #[track_caller]
+    pub fn get(&self) -> ThreadLocalPoolBox<'_, T> {
+        let cached = self.with_cache(|cache| {
+            if cache.items.is_empty() {
+                let batch = (self.cache_size / 2).max(1);
+                cache.items.extend(self.shared.take_batch(batch));
+            }
+            cache.items.pop()
+        }).flatten();
+        let (item, generation) = cached.unwrap_or_else(|| self.shared.take_one());
+        ThreadLocalPoolBox {
+            item,
+            generation,
+            pool: self
+        }
+    }
+
+    /// Put a released item in the local cache, flushing half of the cache if it is full.
+    fn release(&self, item: *mut T, generation: u64) {
+        let Some(reset) = self.local_reset else {
+            self.shared.release(item, generation);
+            return;
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| reset(unsafe { &mut *item })));
+        if let Err(payload) = result {
+            drop(self.shared.take(item, generation));
+            panic::resume_unwind(payload);
+        }
+        let cached = self.with_cache(|cache| {
+            if cache.items.len() >= self.cache_size {
+                cache.flush(self.cache_size / 2 + 1);
+            }
+            if self.cache_size > 0 {
+                cache.items.push((item, generation));
+                true
+            } else {
+                false
+            }
+        });
+        if cached != Some(true) {
+            self.shared.restore([(item, generation)]);
+        }
+    }
+
+    /// Get the number of items cached by the current thread.
+    pub fn local_available(&self) -> usize {
+        self.with_cache(|cache| cache.items.len()).unwrap_or(0)
+    }
+}
+
+impl<T: Poolable + Send + 'static> Drop for ThreadLocalPool<T> {
+    fn drop(&mut self) {
+        // Return the current thread's cache while the shared pool is still alive.
+        let cache = CACHES.try_with(|caches| caches.borrow_mut().remove(&self.id)).ok().flatten();
+        drop(cache);
+    }
+}
+
+/// Guard handed out by a ThreadLocalPool. When dropped, its item goes to the cache of the dropping thread.
+pub struct ThreadLocalPoolBox<'a, T: Poolable + Send + 'static> {
+    item: *mut T,
+    generation: u64,
+    pool: &'a ThreadLocalPool<T>
+}
+
+// SAFETY: the guard has exclusive access to its item, and any thread's cache may take it back.
+unsafe impl<T: Poolable + Send + 'static> Send for ThreadLocalPoolBox<'_, T> {}
+unsafe impl<T: Poolable + Send + Sync + 'static> Sync for ThreadLocalPoolBox<'_, T> {}
+
+impl<T: Poolable + Send + 'static> Deref for ThreadLocalPoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send + 'static> DerefMut for ThreadLocalPoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.item
+        }
+    }
+}
+
+impl<T: Poolable + Send + fmt::Debug + 'static> fmt::Debug for ThreadLocalPoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Poolable + Send + 'static> Drop for ThreadLocalPoolBox<'_, T> {
+    fn drop(&mut self) {
+        self.pool.release(self.item, self.generation);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+    use std::thread;
+    use crate::test_support::TestObject;
+
+    #[test]
+    fn test_thread_local_cache() {
+        let pool = ThreadLocalPool::<TestObject>::new(4);
+        let mut obj = pool.get();
+        obj.value = 1;
+        drop(obj);
+        assert_eq!(pool.local_available(), 1);
+        assert_eq!(pool.shared().available(), 0);
+        assert_eq!(pool.get().value, 0);
+
+        // Overflowing the cache moves half of it to the shared pool.
+        let objs: Vec<_> = (0..6).map(|_| pool.get()).collect();
+        drop(objs);
+        assert!(pool.local_available() <= 4);
+        assert_eq!(pool.local_available() + pool.shared().available(), 6);
+        assert_eq!(pool.shared().len(), 6);
+    }
+
+    #[test]
+    fn test_caches_return_on_thread_exit() {
+        let pool = Arc::new(ThreadLocalPool::<TestObject>::new(8));
+        // Scoped threads may be joined before their thread locals are destroyed, so use plain threads.
+        let workers: Vec<_> = (0..4).map(|worker| {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    let mut obj = pool.get();
+                    assert_eq!(obj.value, 0);
+                    obj.value = worker;
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(pool.shared().in_use(), 0);
+        assert_eq!(pool.shared().available(), pool.shared().len());
+
+        // The first get on this thread fills its cache with a batch of however many items are idle.
+        let obj = pool.get();
+        drop(obj);
+        assert!(pool.local_available() >= 1);
+        assert_eq!(pool.shared().in_use(), pool.local_available());
+    }
+
+    #[test]
+    fn test_releases_run_shared_pool_hooks() {
+        static RELEASED: AtomicUsize = AtomicUsize::new(0);
+        let raw = ObjectPool::new().with_on_release(|_: &mut TestObject| {
+            RELEASED.fetch_add(1, Ordering::Relaxed);
+        });
+        let pool = ThreadLocalPool::from_shared(Arc::new(ConcurrentObjectPool::from_raw(raw, 2)), 4);
+        let mut obj = pool.get();
+        obj.value = 1;
+        drop(obj);
+        // The hook runs on release, so the item goes back to the shared pool instead of the cache.
+        assert_eq!(RELEASED.load(Ordering::Relaxed), 1);
+        assert_eq!((pool.local_available(), pool.shared().available()), (0, 1));
+        assert_eq!(pool.get().value, 0);
+        assert_eq!(pool.shared().max_size(), 2);
+    }
+
+    #[test]
+    fn test_stale_caches_are_purged() {
+        let shared = Arc::new(ConcurrentObjectPool::bounded(4));
+        let pool = Arc::new(ThreadLocalPool::<TestObject>::from_shared(Arc::clone(&shared), 4));
+        let (to_worker, from_main) = mpsc::channel();
+        let (to_main, from_worker) = mpsc::channel();
+        let worker = {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                drop(pool.get());
+                let cached = pool.local_available();
+                drop(pool);
+                to_main.send(cached).unwrap();
+                from_main.recv().unwrap();
+                // Creating a cache for another pool returns the cache of the dropped one.
+                let other = ThreadLocalPool::<TestObject>::new(4);
+                drop(other.get());
+                to_main.send(other.local_available()).unwrap();
+                from_main.recv().unwrap();
+            })
+        };
+        assert_eq!(from_worker.recv().unwrap(), 1);
+        // The worker still holds a cache of the pool when it is dropped.
+        drop(pool);
+        assert_eq!(shared.in_use(), 1);
+        to_worker.send(()).unwrap();
+        assert_eq!(from_worker.recv().unwrap(), 1);
+        assert_eq!((shared.in_use(), shared.available()), (0, 1));
+        to_worker.send(()).unwrap();
+        worker.join().unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Instant;
+
+/// Pending deadlines of the timer thread and the wakers to call when they pass.
+struct TimerQueue {
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+    next_id: u64
+}
+
+impl TimerQueue {
+    /// Number of deadlines of cancelled sleepers the heap may hold beyond those of live ones before it is compacted.
+    const SLACK: usize = 64;
+
+    /// Forget the waker of a dropped sleeper. Its deadline stays in the heap until it passes, unless cancelled
+    /// deadlines outnumber live ones, e.g. from timeouts of acquires that succeeded, and the heap is compacted.
+    fn cancel(&mut self, id: u64) {
+        self.wakers.remove(&id);
+        if self.deadlines.len() > 2 * self.wakers.len() + TimerQueue::SLACK {
+            let wakers = &self.wakers;
+            self.deadlines.retain(|Reverse((_, id))| wakers.contains_key(id));
+        }
+    }
+}
+
+/// Executor independent timer: a single background thread wakes every `Sleep` whose deadline has passed.
+struct Timer {
+    queue: Mutex<TimerQueue>,
+    changed: Condvar
+}
+
+impl Timer {
+    /// Get the process-wide timer, starting its thread on first use.
+    fn get() -> &'static Timer {
+        static TIMER: OnceLock<&'static Timer> = OnceLock::new();
+        TIMER.get_or_init(|| {
+            let timer: &'static Timer = Box::leak(Box::new(Timer {
+                queue: Mutex::new(TimerQueue {
+                    deadlines: BinaryHeap::new(),
+                    wakers: HashMap::new(),
+                    next_id: 0
+                }),
+                changed: Condvar::new()
+            }));
+            thread::Builder::new()
+                .name("object_pool-timer".into())
+                .spawn(move || timer.run())
+                .expect("failed to spawn the timer thread");
+            timer
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TimerQueue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wake the sleepers whose deadline has passed, then wait for the next deadline or a new sleeper.
+    fn run(&self) {
+        let mut queue = self.lock();
+        loop {
+            let now = Instant::now();
+            let mut expired = Vec::new();
+            while let Some(&Reverse((deadline, id))) = queue.deadlines.peek() {
+                if deadline > now {
+                    break;
+                }
+                queue.deadlines.pop();
+                expired.extend(queue.wakers.remove(&id));
+            }
+            if !expired.is_empty() {
+                // Wake outside the lock, since a woken task may be polled right away and sleep again.
+                drop(queue);
+                expired.into_iter().for_each(Waker::wake);
+                queue = self.lock();
+                continue;
+            }
+            queue = match queue.deadlines.peek() {
+                Some(&Reverse((deadline, _))) => {
+                    self.changed.wait_timeout(queue, deadline - now).unwrap_or_else(PoisonError::into_inner).0
+                }
+                None => self.changed.wait(queue).unwrap_or_else(PoisonError::into_inner)
+            };
+        }
+    }
+}
+
+/// Future that completes once its deadline has passed.
+pub(crate) struct Sleep {
+    deadline: Instant,
+    /// Id under which the waker is registered with the timer thread.
+    id: Option<u64>
+}
+
+impl Sleep {
+    /// Sleep until `deadline`.
+    pub(crate) fn until(deadline: Instant) -> Sleep {
+        Sleep {
+            deadline,
+            id: None
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let timer = Timer::get();
+        let mut queue = timer.lock();
+        match self.id {
+            Some(id) => {
+                queue.wakers.insert(id, cx.waker().clone());
+            }
+            None => {
+                let id = queue.next_id;
+                queue.next_id += 1;
+                let earliest = queue.deadlines.peek().is_none_or(|&Reverse((next, _))| self.deadline < next);
+                queue.deadlines.push(Reverse((self.deadline, id)));
+                queue.wakers.insert(id, cx.waker().clone());
+                self.id = Some(id);
+                if earliest {
+                    timer.changed.notify_one();
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            Timer::get().lock().cancel(id);
+        }
+    }
+}
+
+/// Future that gives up on an `Unpin` future once a deadline passes, resolving to None.
+pub(crate) struct Timeout<F> {
+    future: F,
+    sleep: Sleep
+}
+
+impl<F: Future + Unpin> Timeout<F> {
+    /// Run `future` until `deadline`.
+    pub(crate) fn new(future: F, deadline: Instant) -> Timeout<F> {
+        Timeout {
+            future,
+            sleep: Sleep::until(deadline)
+        }
+    }
+}
+
+impl<F: Future + Unpin> Future for Timeout<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<F::Output>> {
+        if let Poll::Ready(output) = Pin::new(&mut self.future).poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        Pin::new(&mut self.sleep).poll(cx).map(|()| None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+    use std::time::Duration;
+
+    #[test]
+    fn test_dropped_sleeps_leave_no_deadlines() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let deadline = Instant::now() + Duration::from_secs(3600);
+        for _ in 0..1000 {
+            let mut sleep = Sleep::until(deadline);
+            assert!(Pin::new(&mut sleep).poll(&mut cx).is_pending());
+        }
+        // Other tests may sleep at the same time, but far fewer than the thousand cancelled deadlines.
+        assert!(Timer::get().lock().deadlines.len() < 1000);
+    }
+}
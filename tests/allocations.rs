@@ -0,0 +1,61 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use object_pool::object_pool::object_pool::{AutoReturnObjectPool, Poolable};
+
+/// Global allocator that counts the allocations made by each thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+struct Counter {
+    value: u64
+}
+
+impl Poolable for Counter {
+    fn new() -> Counter {
+        Counter {
+            value: 0
+        }
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+}
+
+#[test]
+fn test_small_pool_allocates_one_slab() {
+    let before = allocations();
+    let pool = AutoReturnObjectPool::<Counter>::new();
+    pool.reserve(8);
+    for _ in 0..3 {
+        let guards: Vec<_> = (0..8).map(|_| pool.get()).collect();
+        for mut guard in guards {
+            guard.value += 1;
+        }
+    }
+    // The guards vector allocates on each round; the pool itself only allocates its slab.
+    assert_eq!(allocations() - before, 1 + 3);
+    assert_eq!((pool.len(), pool.available()), (8, 8));
+}
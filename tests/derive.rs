@@ -0,0 +1,52 @@
+#![cfg(feature = "derive")]
+
+use object_pool::object_pool::object_pool::{AutoReturnObjectPool, Clear, Poolable};
+
+#[derive(Default)]
+struct Words(Vec<String>);
+
+impl Clear for Words {
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+fn rewind(offset: &mut usize) {
+    *offset = 1;
+}
+
+#[derive(Poolable)]
+pub struct Request {
+    words: Words,
+    pub retries: u32,
+    headers: std::collections::HashMap<String, Vec<u8>>,
+    callback: Option<fn(u32) -> u32>,
+    #[poolable(skip)]
+    pub(crate) served: u64,
+    #[poolable(default)]
+    buffer: Vec<u8>,
+    #[poolable(reset = rewind)]
+    offset: usize
+}
+
+#[test]
+fn test_derive_poolable() {
+    let pool = AutoReturnObjectPool::<Request>::new();
+    {
+        let mut request = pool.get();
+        request.words.0.push("hello".to_string());
+        request.retries = 3;
+        request.headers.insert("a".to_string(), vec![1]);
+        request.callback = Some(|x| x + 1);
+        request.served += 1;
+        request.buffer.extend_from_slice(&[1; 16]);
+        request.offset = 7;
+    }
+    let request = pool.get();
+    assert!(request.words.0.is_empty() && request.words.0.capacity() > 0);
+    assert_eq!(request.retries, 0);
+    assert!(request.headers.is_empty() && request.callback.is_none());
+    assert_eq!(request.served, 1);
+    assert_eq!(request.buffer.capacity(), 0);
+    assert_eq!(request.offset, 1);
+}